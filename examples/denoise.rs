@@ -0,0 +1,61 @@
+extern crate rusty;
+
+use rusty::graphics::{Canvas, Context, RenderControl};
+use rusty::math::vec3::{Vector, Vertex};
+use rusty::tracer::lights::SphericalLight;
+use rusty::tracer::objects::{Material, Plane, Sphere};
+use rusty::tracer::render::{render, Integrator, Settings};
+use rusty::tracer::Scene;
+
+fn main() -> Result<(), rusty::graphics::Error> {
+    let mut scene = Scene::default();
+    scene.add_object(Sphere {
+        center: Vertex {
+            x: 0.0,
+            y: -2.0,
+            z: -8.0,
+        },
+        radius: 3.0,
+        material: Material::new("#E0E0E0".parse().unwrap(), 0.8),
+    });
+    scene.add_object(Plane {
+        point: Vertex {
+            x: 0.0,
+            y: -5.0,
+            z: 0.0,
+        },
+        normal: Vector {
+            x: 0.0,
+            y: -1.0,
+            z: 0.0,
+        },
+        material: Material::new("#CC8844".parse().unwrap(), 0.6),
+    });
+    scene.add_light(SphericalLight {
+        position: Vertex {
+            x: 4.0,
+            y: 4.0,
+            z: -4.0,
+        },
+        base_color: "#FFFFFF".parse().unwrap(),
+        base_intensity: 3000.0,
+    });
+
+    let mut settings = Settings {
+        width: 320,
+        height: 240,
+        samples: 16,
+        integrator: Integrator::Path,
+        ..Settings::default()
+    };
+    for (denoise, filename) in &[(false, "noisy.png"), (true, "denoised.png")] {
+        settings.denoise = *denoise;
+        let frame = render(&scene, &settings, &RenderControl::default(), |_, _, _| {})
+            .expect("uncancelled render");
+        let mut canvas = Canvas::new(frame.width, frame.height);
+        frame.draw(&mut canvas);
+        Context::save(&canvas, filename)?;
+        println!("saved {}", filename);
+    }
+    Ok(())
+}
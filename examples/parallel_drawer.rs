@@ -2,35 +2,50 @@ extern crate rusty;
 
 use std::thread;
 
-use rusty::graphics::{CanvasLock, Color, Context};
+use rusty::graphics::{CanvasLock, Color, Context, RenderControl};
 
 const NUM_THREADS: usize = 4;
 
 fn main() -> Result<(), rusty::graphics::Error> {
+    let threads = thread::available_parallelism().map_or(NUM_THREADS, |n| n.get());
     let mut gui = Context::new(800, 600);
-    gui.run(drawer)
+    gui.run(move |canvas, control| drawer(canvas, control, threads))
 }
 
-fn drawer(canvas: CanvasLock) {
+fn drawer(canvas: CanvasLock, control: RenderControl, threads: usize) {
     let (width, height) = {
         let c = canvas.read().expect("read lock canvas");
         (c.width, c.height)
     };
-    for i in 0..NUM_THREADS {
-        let step = height / NUM_THREADS;
-        let (start, end) = (step * i, step * (i + 1));
-        let canvas_ = canvas.clone();
-        thread::spawn(move || {
-            fill_part(width, height, start, end, canvas_);
-        });
+    let step = height.div_ceil(threads);
+    thread::scope(|s| {
+        for i in 0..threads {
+            let (start, end) = (step * i, (step * (i + 1)).min(height));
+            let (canvas, control) = (&canvas, &control);
+            s.spawn(move || fill_part(width, height, start, end, canvas, control));
+        }
+    });
+    if !control.is_cancelled() {
+        println!("done");
     }
 }
 
-fn fill_part(w: usize, h: usize, start: usize, end: usize, canvas: CanvasLock) {
+fn fill_part(
+    w: usize,
+    h: usize,
+    start: usize,
+    end: usize,
+    canvas: &CanvasLock,
+    control: &RenderControl,
+) {
     println!("worker: {:3} -> {:3}", start, end);
     for y in start..end {
+        control.wait_while_paused();
+        if control.is_cancelled() {
+            return;
+        }
         for x in 0..w {
-            let blue = (((x * y) as f32 / (h.pow(2) + w.pow(2)) as f32).sqrt()) as f32;
+            let blue = ((x * y) as f32 / (h.pow(2) + w.pow(2)) as f32).sqrt();
             let color = Color {
                 red: (x as f32 / w as f32),
                 green: (y as f32 / h as f32),
@@ -0,0 +1,286 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::graphics::Region;
+use crate::tracer::camera::Camera;
+
+/// Why the drawer is asked to start over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Restart {
+    /// The scene file changed and has to be parsed again.
+    Reload,
+    /// Only the camera moved, see [`RenderControl::camera`].
+    Camera,
+    /// Only the region of interest changed, see [`RenderControl::region`].
+    Region,
+}
+
+/// Where a progressive render stands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Number of complete passes, one sample per pixel each.
+    pub samples: usize,
+    pub target: usize,
+    pub elapsed: Duration,
+    pub done: bool,
+    pub paused: bool,
+}
+
+struct ProgressState {
+    samples: usize,
+    target: usize,
+    started: Instant,
+    finished: Option<Instant>,
+    paused_at: Option<Instant>,
+    /// Time spent paused before `paused_at`.
+    paused_for: Duration,
+}
+
+impl ProgressState {
+    /// Ends the current pause, if any, adding it to the paused time.
+    fn resume(&mut self, now: Instant) {
+        if let Some(since) = self.paused_at.take() {
+            self.paused_for += now - since;
+        }
+    }
+}
+
+/// Diagnostics of the canvas pixel `(x, y)`, see
+/// [`RenderControl::set_inspector`].
+type Inspector = Arc<dyn Fn(usize, usize) -> String + Send + Sync>;
+
+#[derive(Default)]
+struct ControlState {
+    cancelled: AtomicBool,
+    closed: AtomicBool,
+    stopped: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    restart: Mutex<Option<Restart>>,
+    wakeup: Condvar,
+    camera: Mutex<Option<Camera>>,
+    region: Mutex<Option<Region>>,
+    progress: Mutex<Option<ProgressState>>,
+    inspector: Mutex<Option<Inspector>>,
+}
+
+/// Shared handle between the window and the drawer thread.
+///
+/// Render loops are expected to poll [`RenderControl::is_cancelled`] and
+/// return early once it is set, and to call
+/// [`RenderControl::wait_while_paused`] between chunks of work.
+#[derive(Clone, Default)]
+pub struct RenderControl {
+    state: Arc<ControlState>,
+}
+
+impl RenderControl {
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Stops the in-flight render.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+        // a paused render has to notice it
+        let _paused = self.state.paused.lock().expect("lock paused");
+        self.state.resumed.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Ends the in-flight render early, keeping what has converged so far.
+    pub fn stop(&self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+        let _paused = self.state.paused.lock().expect("lock paused");
+        self.state.resumed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.state.paused.lock().expect("lock paused")
+    }
+
+    /// Pauses or resumes the in-flight render.
+    pub fn set_paused(&self, paused: bool) {
+        if let Some(progress) = self.state.progress.lock().expect("lock progress").as_mut() {
+            match progress.paused_at {
+                None if paused && progress.finished.is_none() => {
+                    progress.paused_at = Some(Instant::now())
+                }
+                Some(_) if !paused => progress.resume(Instant::now()),
+                _ => {}
+            }
+        }
+        *self.state.paused.lock().expect("lock paused") = paused;
+        self.state.resumed.notify_all();
+    }
+
+    /// Blocks while the render is paused, unless it is cancelled or stopped.
+    pub fn wait_while_paused(&self) {
+        let mut paused = self.state.paused.lock().expect("lock paused");
+        while *paused && !self.is_cancelled() && !self.is_stopped() {
+            paused = self.state.resumed.wait(paused).expect("wait resume");
+        }
+    }
+
+    /// Stops the in-flight render and asks the drawer to start over.
+    pub fn restart(&self, reason: Restart) {
+        self.cancel();
+        let mut restart = self.state.restart.lock().expect("lock restart");
+        // a reload also picks up the latest camera, and both the latest
+        // region of interest
+        let pending = matches!(
+            (*restart, reason),
+            (Some(Restart::Reload), _) | (Some(Restart::Camera), Restart::Region)
+        );
+        if !pending {
+            *restart = Some(reason);
+        }
+        self.state.wakeup.notify_all();
+    }
+
+    /// Cancels the in-flight render for good, the window being closed.
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::Relaxed);
+        self.cancel();
+        let _restart = self.state.restart.lock().expect("lock restart");
+        self.state.wakeup.notify_all();
+    }
+
+    /// Blocks until a restart is requested, then clears the cancellation and
+    /// the pause. Returns `None` once the window is closed, the drawer then
+    /// being expected to return.
+    pub fn wait_restart(&self) -> Option<Restart> {
+        let mut restart = self.state.restart.lock().expect("lock restart");
+        loop {
+            if self.state.closed.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Some(reason) = restart.take() {
+                self.state.cancelled.store(false, Ordering::Relaxed);
+                self.state.stopped.store(false, Ordering::Relaxed);
+                *self.state.paused.lock().expect("lock paused") = false;
+                return Some(reason);
+            }
+            restart = self.state.wakeup.wait(restart).expect("wait restart");
+        }
+    }
+
+    /// Camera of the scene being rendered, if the drawer shared it.
+    pub fn camera(&self) -> Option<Camera> {
+        *self.state.camera.lock().expect("lock camera")
+    }
+
+    /// Shares the camera so that the window can move it around.
+    pub fn set_camera(&self, camera: Camera) {
+        *self.state.camera.lock().expect("lock camera") = Some(camera);
+    }
+
+    /// Pixels the drawer is asked to render, all of them when `None`.
+    pub fn region(&self) -> Option<Region> {
+        *self.state.region.lock().expect("lock region")
+    }
+
+    pub fn set_region(&self, region: Option<Region>) {
+        *self.state.region.lock().expect("lock region") = region;
+    }
+
+    /// Starts timing a render aiming for `target` samples per pixel.
+    pub fn begin(&self, target: usize) {
+        let now = Instant::now();
+        let paused_at = if self.is_paused() { Some(now) } else { None };
+        *self.state.progress.lock().expect("lock progress") = Some(ProgressState {
+            samples: 0,
+            target,
+            started: now,
+            finished: None,
+            paused_at,
+            paused_for: Duration::ZERO,
+        });
+    }
+
+    pub fn report(&self, samples: usize) {
+        if let Some(progress) = self.state.progress.lock().expect("lock progress").as_mut() {
+            progress.samples = samples;
+        }
+    }
+
+    /// Stops timing the render, a render stopped while paused not counting
+    /// the pause.
+    pub fn finish(&self) {
+        if let Some(progress) = self.state.progress.lock().expect("lock progress").as_mut() {
+            let now = Instant::now();
+            progress.resume(now);
+            progress.finished = Some(now);
+        }
+    }
+
+    /// Progress of the latest render, if one was started, its elapsed time
+    /// leaving out the pauses.
+    pub fn progress(&self) -> Option<Progress> {
+        let paused = self.is_paused();
+        self.state
+            .progress
+            .lock()
+            .expect("lock progress")
+            .as_ref()
+            .map(|p| Progress {
+                samples: p.samples,
+                target: p.target,
+                elapsed: (p.finished.or(p.paused_at).unwrap_or_else(Instant::now) - p.started)
+                    .saturating_sub(p.paused_for),
+                done: p.finished.is_some(),
+                paused,
+            })
+    }
+
+    /// Lets the window describe a clicked pixel, `inspector` being called
+    /// from the window thread with canvas coordinates.
+    pub fn set_inspector(
+        &self,
+        inspector: impl Fn(usize, usize) -> String + Send + Sync + 'static,
+    ) {
+        *self.state.inspector.lock().expect("lock inspector") = Some(Arc::new(inspector));
+    }
+
+    /// Diagnostics of the pixel `(x, y)`, if the drawer set an inspector.
+    pub fn inspect(&self, x: usize, y: usize) -> Option<String> {
+        // released before the call, which may wait for the scene
+        let inspector = self.state.inspector.lock().expect("lock inspector").clone();
+        inspector.map(|inspect| inspect(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_are_not_timed() {
+        let control = RenderControl::default();
+        control.set_paused(true);
+        control.begin(4);
+        assert_eq!(control.progress().unwrap().elapsed, Duration::ZERO);
+        // stopped while paused
+        control.stop();
+        control.finish();
+        let progress = control.progress().unwrap();
+        assert!(progress.done);
+        assert_eq!(progress.elapsed, Duration::ZERO);
+
+        let control = RenderControl::default();
+        control.begin(4);
+        control.set_paused(true);
+        let paused = control.progress().unwrap().elapsed;
+        assert_eq!(control.progress().unwrap().elapsed, paused);
+        control.finish();
+        assert_eq!(control.progress().unwrap().elapsed, paused);
+        // a finished render is not paused again
+        control.set_paused(false);
+        control.set_paused(true);
+        assert_eq!(control.progress().unwrap().elapsed, paused);
+    }
+}
@@ -1,43 +1,32 @@
+use std::borrow::Cow;
+
 use glium::glutin::{self, event_loop::EventLoop};
-use glium::{implement_vertex, uniform, Display, Program, Surface};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::{implement_vertex, uniform, Display, Program, Rect, Surface, VertexBuffer};
+
+use crate::graphics::Region;
 
 #[derive(Copy, Clone)]
 pub struct Vertex {
     pub position: [f32; 2],
-    pub color: [f32; 3],
+    pub tex_coords: [f32; 2],
 }
 
-implement_vertex!(Vertex, position, color);
-
-pub struct PositionConverter {
-    half_width: f32,
-    half_height: f32,
-}
-
-impl PositionConverter {
-    pub fn new(width: usize, height: usize) -> PositionConverter {
-        PositionConverter {
-            half_width: width as f32 / 2.0,
-            half_height: height as f32 / 2.0,
-        }
-    }
-
-    pub fn get(&self, x: usize, y: usize) -> [f32; 2] {
-        [(x as f32 - self.half_width), (y as f32 - self.half_height)]
-    }
-}
+implement_vertex!(Vertex, position, tex_coords);
 
 pub const VERTEX_SHADER: &str = r#"
     #version 140
 
     in vec2 position;
-    in vec3 color;
-    out vec3 rgb;
+    in vec2 tex_coords;
+    out vec2 uv;
 
     uniform mat4 matrix;
 
     void main() {
-        rgb = color;
+        uv = tex_coords;
         gl_Position = matrix * vec4(position, 0.0, 1.0);
     }
 "#;
@@ -45,10 +34,18 @@ pub const VERTEX_SHADER: &str = r#"
 pub const FRAGMENT_SHADER: &str = r#"
     #version 140
 
-    in vec3 rgb;
+    in vec2 uv;
     out vec4 color;
 
+    uniform sampler2D canvas;
+    // left, top, right and bottom of the selection, in texture coordinates
+    uniform vec4 selection;
+
     void main() {
+        vec3 rgb = texture(canvas, uv).rgb;
+        if (any(lessThan(uv, selection.xy)) || any(greaterThan(uv, selection.zw))) {
+            rgb *= 0.4;
+        }
         color = vec4(rgb, 1.0);
     }
 "#;
@@ -89,6 +86,7 @@ pub fn init_context(
 #[derive(Debug)]
 pub enum GpuError {
     Creation(glium::vertex::BufferCreationError),
+    Texture(glium::texture::TextureCreationError),
     Draw(glium::DrawError),
     SwapBuffers(glium::SwapBuffersError),
 }
@@ -99,6 +97,12 @@ impl From<glium::vertex::BufferCreationError> for GpuError {
     }
 }
 
+impl From<glium::texture::TextureCreationError> for GpuError {
+    fn from(val: glium::texture::TextureCreationError) -> Self {
+        Self::Texture(val)
+    }
+}
+
 impl From<glium::DrawError> for GpuError {
     fn from(val: glium::DrawError) -> Self {
         Self::Draw(val)
@@ -111,34 +115,109 @@ impl From<glium::SwapBuffersError> for GpuError {
     }
 }
 
-fn make_ortho(width: f32, height: f32) -> [[f32; 4]; 4] {
-    [
-        [2.0 / width, 0.0, 0.0, 0.0],
-        [0.0, -2.0 / height, 0.0, 0.0],
-        [0.0, 0.0, -1.0, 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ]
+/// Row-major RGB values uploaded to the texture.
+pub type Texels = Vec<(f32, f32, f32)>;
+
+/// Canvas mirrored in a float texture, drawn as a single quad.
+pub struct CanvasTexture {
+    texture: Texture2d,
+    quad: VertexBuffer<Vertex>,
 }
 
-pub fn render_buffer(
-    display: &Display,
-    buffer: Vec<Vertex>,
-    program: &Program,
-    viewport: (f32, f32),
-) -> Result<(), GpuError> {
-    let uniforms = uniform! {
-        matrix: make_ortho(viewport.0, viewport.1),
-    };
-    let vertex_buffer = glium::VertexBuffer::new(display, &buffer)?;
-    let mut target = display.draw();
-    target.clear_color(0.0, 0.0, 0.0, 0.0);
-    target.draw(
-        &vertex_buffer,
-        &glium::index::NoIndices(glium::index::PrimitiveType::Points),
-        program,
-        &uniforms,
-        &Default::default(),
-    )?;
-    target.finish()?;
-    Ok(())
+impl CanvasTexture {
+    pub fn new(display: &Display, width: usize, height: usize) -> Result<CanvasTexture, GpuError> {
+        let texture = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32F32F32,
+            MipmapsOption::NoMipmap,
+            width as u32,
+            height as u32,
+        )?;
+        // centered on the origin, texture rows going down like the canvas ones
+        let (w, h) = (width as f32 / 2.0, height as f32 / 2.0);
+        let quad = VertexBuffer::new(
+            display,
+            &[
+                Vertex {
+                    position: [-w, -h],
+                    tex_coords: [0.0, 0.0],
+                },
+                Vertex {
+                    position: [w, -h],
+                    tex_coords: [1.0, 0.0],
+                },
+                Vertex {
+                    position: [-w, h],
+                    tex_coords: [0.0, 1.0],
+                },
+                Vertex {
+                    position: [w, h],
+                    tex_coords: [1.0, 1.0],
+                },
+            ],
+        )?;
+        Ok(CanvasTexture { texture, quad })
+    }
+
+    /// Uploads the row-major `pixels` of `region`.
+    pub fn update(&self, region: Region, pixels: Texels) {
+        let image = RawImage2d {
+            data: Cow::Owned(pixels),
+            width: region.width as u32,
+            height: region.height as u32,
+            format: ClientFormat::F32F32F32,
+        };
+        let rect = Rect {
+            left: region.x as u32,
+            bottom: region.y as u32,
+            width: region.width as u32,
+            height: region.height as u32,
+        };
+        self.texture.write(rect, image);
+    }
+
+    /// Draws the canvas placed by `matrix`, see [`View::matrix`], dimming
+    /// what lies outside of `selection`.
+    ///
+    /// Zoomed in pixels stay sharp squares, zoomed out ones are averaged.
+    ///
+    /// [`View::matrix`]: crate::graphics::view::View::matrix
+    pub fn draw(
+        &self,
+        display: &Display,
+        program: &Program,
+        matrix: [[f32; 4]; 4],
+        selection: Option<Region>,
+    ) -> Result<(), GpuError> {
+        let (width, height) = self.texture.dimensions();
+        let selection = match selection {
+            Some(r) => [
+                r.x as f32 / width as f32,
+                r.y as f32 / height as f32,
+                (r.x + r.width) as f32 / width as f32,
+                (r.y + r.height) as f32 / height as f32,
+            ],
+            None => [0.0, 0.0, 1.0, 1.0],
+        };
+        let uniforms = uniform! {
+            matrix: matrix,
+            selection: selection,
+            canvas: self
+                .texture
+                .sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Linear),
+        };
+        let mut target = display.draw();
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+        target.draw(
+            &self.quad,
+            NoIndices(PrimitiveType::TriangleStrip),
+            program,
+            &uniforms,
+            &Default::default(),
+        )?;
+        target.finish()?;
+        Ok(())
+    }
 }
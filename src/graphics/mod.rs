@@ -1,16 +1,28 @@
 mod color;
+mod control;
 mod gpu;
+mod navigation;
+mod view;
 
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::{Duration, Instant, SystemTime};
 
 pub use color::Color;
+pub use control::{Progress, RenderControl, Restart};
 use glium::glutin::{
     self,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta,
+        VirtualKeyCode, WindowEvent,
+    },
+    platform::desktop::EventLoopExtDesktop,
 };
-use gpu::{GpuContextError, GpuError, PositionConverter, Vertex};
+use gpu::{CanvasTexture, GpuContextError, GpuError, Texels};
+use navigation::Navigation;
+use view::View;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Pixel {
@@ -18,12 +30,39 @@ pub enum Pixel {
     Blank,
 }
 
+/// Rectangle of pixels, in canvas coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// Pixels in both `self` and `other`, possibly empty.
+    pub fn intersect(self, other: Region) -> Region {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width).max(left);
+        let bottom = (self.y + self.height).min(other.y + other.height).max(top);
+        Region {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pixels: Vec<Pixel>,
-    dirty: bool,
+    /// Columns modified in each row since the last upload, from the first
+    /// one to the one past the last.
+    dirty: Vec<Option<(usize, usize)>>,
 }
 
 impl Canvas {
@@ -31,32 +70,32 @@ impl Canvas {
         Canvas {
             width,
             height,
-            pixels: vec![Pixel::Blank; (width * height) as usize],
-            dirty: false,
+            pixels: vec![Pixel::Blank; width * height],
+            dirty: vec![None; height],
         }
     }
 
     pub fn get(&self, x: usize, y: usize) -> Pixel {
-        if x > self.width || y > self.height {
+        if x >= self.width || y >= self.height {
             error!("invalid coordinates: ({}, {})", x, y);
             Pixel::Blank
         } else {
-            let index = (x + y * self.width) as usize;
+            let index = x + y * self.width;
             self.pixels[index]
         }
     }
 
     pub fn set(&mut self, x: usize, y: usize, c: Color) -> bool {
-        if x > self.width || y > self.height {
+        if x >= self.width || y >= self.height {
             error!("invalid coordinates: ({}, {})", x, y);
             false
         } else {
-            let index = (x + y * self.width) as usize;
+            let index = x + y * self.width;
             let old = self.pixels[index];
             let new = Pixel::Data(c);
             if old != new {
                 self.pixels[index] = new;
-                self.dirty = true;
+                self.mark_dirty(x, y);
                 true
             } else {
                 false
@@ -65,15 +104,15 @@ impl Canvas {
     }
 
     pub fn unset(&mut self, x: usize, y: usize) -> bool {
-        if x > self.width || y > self.height {
+        if x >= self.width || y >= self.height {
             error!("invalid coordinates: ({}, {})", x, y);
             false
         } else {
-            let index = (x + y * self.width) as usize;
+            let index = x + y * self.width;
             let old = self.pixels[index];
             if old != Pixel::Blank {
                 self.pixels[index] = Pixel::Blank;
-                self.dirty = true;
+                self.mark_dirty(x, y);
                 true
             } else {
                 false
@@ -81,17 +120,76 @@ impl Canvas {
         }
     }
 
-    fn as_raw(&self) -> Vec<Vertex> {
-        let pc = PositionConverter::new(self.width, self.height);
-        let mut data = Vec::new();
-        for x in 0..self.width {
-            for y in 0..self.height {
-                if let Pixel::Data(color) = self.get(x, y) {
-                    data.push(Vertex {
-                        position: pc.get(x, y),
-                        color: [color.red, color.green, color.blue],
-                    });
+    /// `top` above `bottom`, both being as wide.
+    pub fn stacked(top: &Canvas, bottom: &Canvas) -> Canvas {
+        assert_eq!(
+            top.width, bottom.width,
+            "stacked canvases of different widths"
+        );
+        let mut pixels = top.pixels.clone();
+        pixels.extend_from_slice(&bottom.pixels);
+        Canvas {
+            width: top.width,
+            height: top.height + bottom.height,
+            pixels,
+            dirty: vec![None; top.height + bottom.height],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|p| *p = Pixel::Blank);
+        let width = self.width;
+        self.dirty.iter_mut().for_each(|d| *d = Some((0, width)));
+    }
+
+    fn mark_dirty(&mut self, x: usize, y: usize) {
+        self.dirty[y] = Some(match self.dirty[y] {
+            Some((left, right)) => (left.min(x), right.max(x + 1)),
+            None => (x, x + 1),
+        });
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty.iter().any(Option::is_some)
+    }
+
+    /// Regions modified since the last call, each one spanning consecutive
+    /// modified rows so that rows far apart are uploaded separately.
+    fn take_dirty(&mut self) -> Vec<Region> {
+        let mut regions: Vec<Region> = Vec::new();
+        for (y, dirty) in self.dirty.iter_mut().enumerate() {
+            let (left, right) = match dirty.take() {
+                Some(columns) => columns,
+                None => continue,
+            };
+            match regions.last_mut() {
+                Some(region) if region.y + region.height == y => {
+                    let (x, end) = (region.x.min(left), (region.x + region.width).max(right));
+                    region.x = x;
+                    region.width = end - x;
+                    region.height += 1;
                 }
+                _ => regions.push(Region {
+                    x: left,
+                    y,
+                    width: right - left,
+                    height: 1,
+                }),
+            }
+        }
+        regions
+    }
+
+    /// Pixels of `region` in row-major order, blank ones being black.
+    fn texels(&self, region: Region) -> Texels {
+        let mut data = Vec::with_capacity(region.width * region.height);
+        for y in region.y..region.y + region.height {
+            let row = y * self.width;
+            for pixel in &self.pixels[row + region.x..row + region.x + region.width] {
+                data.push(match pixel {
+                    Pixel::Data(color) => (color.red, color.green, color.blue),
+                    Pixel::Blank => (0.0, 0.0, 0.0),
+                });
             }
         }
         data
@@ -132,17 +230,65 @@ pub struct CoordPixel {
 
 pub type CanvasLock = Arc<RwLock<Canvas>>;
 
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polled file, triggering a render restart when modified.
+struct Watch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl Watch {
+    fn new(path: PathBuf) -> Watch {
+        let modified = Self::modified(&path);
+        Watch {
+            path,
+            modified,
+            checked: Instant::now(),
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn changed(&mut self) -> bool {
+        if self.checked.elapsed() < WATCH_INTERVAL {
+            return false;
+        }
+        self.checked = Instant::now();
+        let modified = Self::modified(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 pub struct Context {
     width: usize,
     height: usize,
+    watch: Option<PathBuf>,
 }
 
 impl Context {
     pub fn new(width: usize, height: usize) -> Context {
-        Context { width, height }
+        Context {
+            width,
+            height,
+            watch: None,
+        }
     }
 
-    pub fn export(canvas: &Canvas) -> Result<String, Error> {
+    /// Restarts the drawer whenever `path` is modified.
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        self.watch = Some(path.into());
+    }
+
+    pub fn save(canvas: &Canvas, filename: impl AsRef<Path>) -> Result<(), Error> {
         use image::{ImageBuffer, Rgb};
         let img =
             ImageBuffer::from_fn(
@@ -157,51 +303,185 @@ impl Context {
                     Pixel::Blank => Rgb([0x00, 0x00, 0x00]),
                 },
             );
+        img.save(filename)?;
+        Ok(())
+    }
+
+    /// Name of the images exported at the current time.
+    pub fn export_name() -> String {
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .expect("get the now")
             .as_secs();
-        let filename: &str = &format!("rusty_{}.png", timestamp);
-        img.save(filename)?;
+        format!("rusty_{}.png", timestamp)
+    }
+
+    pub fn export(canvas: &Canvas) -> Result<String, Error> {
+        let filename: &str = &Self::export_name();
+        Self::save(canvas, filename)?;
         log::info!("exported as: {}", filename);
         Ok(filename.into())
     }
 
-    fn key_handler(input: KeyboardInput, canvas: &CanvasLock) -> bool {
-        match input {
-            KeyboardInput {
-                virtual_keycode: Some(virtual_code),
-                state: ElementState::Pressed,
-                ..
-            } => match virtual_code {
+    fn key_handler(input: KeyboardInput, canvas: &CanvasLock, control: &RenderControl) -> bool {
+        if let KeyboardInput {
+            virtual_keycode: Some(virtual_code),
+            state: ElementState::Pressed,
+            ..
+        } = input
+        {
+            match virtual_code {
                 VirtualKeyCode::Q | VirtualKeyCode::Escape => {
                     return true;
                 }
+                VirtualKeyCode::Return => control.stop(),
+                VirtualKeyCode::F | VirtualKeyCode::Pause => {
+                    control.set_paused(!control.is_paused())
+                }
                 VirtualKeyCode::E => {
                     let c_lock = canvas.read().expect("read lock canvas");
-                    if let Err(e) = Self::export(&*c_lock) {
+                    if let Err(e) = Self::export(&c_lock) {
                         log::error!("failed export generation: {:?}", e);
                     }
                 }
                 _ => {}
-            },
-            _ => {}
+            }
         }
         false
     }
 
-    pub fn run(&mut self, drawer: fn(CanvasLock)) -> Result<(), Error> {
+    /// Handles the image zoom and pan keys, returns `false` for the other
+    /// ones.
+    ///
+    /// - `+` and `-` zoom in and out around the window center
+    /// - the arrows move the image around
+    /// - `0` fits the whole image back in the window
+    fn view_key(input: KeyboardInput, view: &mut View) -> bool {
+        let code = match input {
+            KeyboardInput {
+                virtual_keycode: Some(code),
+                state: ElementState::Pressed,
+                ..
+            } => code,
+            _ => return false,
+        };
+        match code {
+            VirtualKeyCode::Equals | VirtualKeyCode::Add => view.zoom_center(1.0),
+            VirtualKeyCode::Minus | VirtualKeyCode::Subtract => view.zoom_center(-1.0),
+            VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => view.reset(),
+            VirtualKeyCode::Left => view.step(-1.0, 0.0),
+            VirtualKeyCode::Right => view.step(1.0, 0.0),
+            VirtualKeyCode::Up => view.step(0.0, -1.0),
+            VirtualKeyCode::Down => view.step(0.0, 1.0),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Pixels between the canvas positions `from` and `to`, clipped to the
+    /// canvas.
+    fn selection(from: (f32, f32), to: (f32, f32), width: usize, height: usize) -> Option<Region> {
+        let clip = |v: f32, max: usize| (v.max(0.0) as usize).min(max);
+        let (left, right) = (from.0.min(to.0).floor(), from.0.max(to.0).ceil());
+        let (top, bottom) = (from.1.min(to.1).floor(), from.1.max(to.1).ceil());
+        let (left, right) = (clip(left, width), clip(right, width));
+        let (top, bottom) = (clip(top, height), clip(bottom, height));
+        if right > left && bottom > top {
+            Some(Region {
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Prints the diagnostics of the canvas pixel under the window position
+    /// `cursor`.
+    fn inspect(canvas: &CanvasLock, control: &RenderControl, view: &View, cursor: (f64, f64)) {
+        let (x, y) = view.to_canvas(cursor.0, cursor.1);
+        let c_lock = canvas.read().expect("read lock canvas");
+        if x < 0.0 || y < 0.0 || x >= c_lock.width as f32 || y >= c_lock.height as f32 {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        match c_lock.get(x, y) {
+            Pixel::Data(c) => println!(
+                "pixel ({}, {}): ({:.4}, {:.4}, {:.4})",
+                x, y, c.red, c.green, c.blue
+            ),
+            Pixel::Blank => println!("pixel ({}, {}): blank", x, y),
+        }
+        drop(c_lock);
+        if let Some(report) = control.inspect(x, y) {
+            println!("{}", report);
+        }
+    }
+
+    fn title(progress: Progress) -> String {
+        format!(
+            "Rusty - {}/{} spp - {:.1}s{}",
+            progress.samples,
+            progress.target,
+            progress.elapsed.as_secs_f32(),
+            if progress.done {
+                " - done"
+            } else if progress.paused {
+                " - paused"
+            } else {
+                ""
+            },
+        )
+    }
+
+    /// Opens the window and starts `drawer` on its own thread.
+    ///
+    /// Closing the window cancels the render through the control handle,
+    /// waits for the drawer to return and then returns. A drawer supporting
+    /// restarts loops over [`RenderControl::wait_restart`] once its render is
+    /// done, sharing its camera through [`RenderControl::set_camera`] enables
+    /// the navigation.
+    ///
+    /// The image is letterboxed in the window, Ctrl + wheel zooms it around
+    /// the cursor and dragging with the middle button pans it. Clicking a
+    /// pixel without dragging prints its diagnostics, see
+    /// [`RenderControl::set_inspector`].
+    ///
+    /// Dragging with Shift held selects the region of interest and restarts
+    /// the drawer, see [`RenderControl::region`], a Shift click going back to
+    /// the whole image.
+    pub fn run<F>(&mut self, drawer: F) -> Result<(), Error>
+    where
+        F: FnOnce(CanvasLock, RenderControl) + Send + 'static,
+    {
         let width = self.width;
         let height = self.height;
         let canvas = Arc::new(RwLock::new(Canvas::new(width, height)));
-        let (display, pixel_program, event_loop) = gpu::init_context(width, height, "Rusty")?;
+        let (display, pixel_program, mut event_loop) = gpu::init_context(width, height, "Rusty")?;
+        let texture = CanvasTexture::new(&display, width, height)?;
+        let control = RenderControl::default();
+        let mut watch = self.watch.clone().map(Watch::new);
+        let mut navigation = Navigation::default();
+        let mut last_tick = Instant::now();
+        let mut title = String::new();
+        let mut view = View::new((width, height), display.get_framebuffer_dimensions());
+        let mut modifiers = ModifiersState::empty();
+        let mut cursor = (0.0, 0.0);
+        let mut panning = false;
+        let mut clicked = None;
+        let mut selecting: Option<((f32, f32), (f32, f32))> = None;
+        let mut redraw = true;
 
         let canvas_ = canvas.clone();
-        thread::spawn(move || {
-            drawer(canvas_);
-        });
+        let control_ = control.clone();
+        let mut drawer = Some(thread::spawn(move || {
+            drawer(canvas_, control_);
+        }));
+        let mut done = false;
 
-        event_loop.run(move |event, _, control_flow| {
+        event_loop.run_return(move |event, _, control_flow| {
             let next_frame_time = Instant::now() + Duration::from_nanos(16_666_667);
             *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
 
@@ -211,30 +491,224 @@ impl Context {
                         *control_flow = glutin::event_loop::ControlFlow::Exit;
                         return;
                     }
+                    WindowEvent::Resized(size) => {
+                        display.gl_window().resize(size);
+                        view.resize(size.width, size.height);
+                        redraw = true;
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        view.resize(new_inner_size.width, new_inner_size.height);
+                        redraw = true;
+                    }
+                    WindowEvent::ModifiersChanged(state) => modifiers = state,
                     WindowEvent::KeyboardInput { input, .. } => {
-                        if Self::key_handler(input, &canvas) {
+                        if Self::view_key(input, &mut view) {
+                            redraw = true;
+                        } else if !navigation.key(input, &control)
+                            && Self::key_handler(input, &canvas, &control)
+                        {
                             *control_flow = glutin::event_loop::ControlFlow::Exit;
                             return;
                         }
                     }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } if modifiers.shift() || selecting.is_some() => {
+                        let position = view.to_canvas(cursor.0, cursor.1);
+                        match (state, selecting.take()) {
+                            (ElementState::Pressed, _) => selecting = Some((position, position)),
+                            (ElementState::Released, Some((from, to))) => {
+                                let region = Self::selection(from, to, width, height);
+                                log::info!("region of interest: {:?}", region);
+                                control.set_region(region);
+                                control.restart(Restart::Region);
+                            }
+                            (ElementState::Released, None) => {}
+                        }
+                        redraw = true;
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        match (button, state) {
+                            (MouseButton::Middle, _) => panning = state == ElementState::Pressed,
+                            (MouseButton::Left, ElementState::Pressed) => clicked = Some(cursor),
+                            (MouseButton::Left, ElementState::Released)
+                                if clicked.take() == Some(cursor) =>
+                            {
+                                Self::inspect(&canvas, &control, &view, cursor);
+                            }
+                            _ => {}
+                        }
+                        navigation.mouse_button(button, state, &control);
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        if panning {
+                            view.drag(
+                                (position.x - cursor.0) as f32,
+                                (position.y - cursor.1) as f32,
+                            );
+                            redraw = true;
+                        }
+                        cursor = (position.x, position.y);
+                        if let Some((_, to)) = selecting.as_mut() {
+                            *to = view.to_canvas(cursor.0, cursor.1);
+                            redraw = true;
+                        }
+                        navigation.cursor_moved(position.x, position.y, &control);
+                    }
+                    WindowEvent::MouseWheel { delta, .. } if modifiers.ctrl() => {
+                        let notches = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+                        };
+                        view.zoom_at(notches, cursor.0, cursor.1);
+                        redraw = true;
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        navigation.scroll(delta, &control);
+                    }
                     _ => return,
                 },
                 Event::NewEvents(cause) => match cause {
-                    glutin::event::StartCause::ResumeTimeReached { .. } => (),
+                    glutin::event::StartCause::ResumeTimeReached { .. } => {
+                        if let Some(watch) = watch.as_mut() {
+                            if watch.changed() {
+                                log::info!("{} changed, restarting", watch.path.display());
+                                control.restart(Restart::Reload);
+                            }
+                        }
+                        navigation.fly(last_tick.elapsed().as_secs_f32(), &control);
+                        last_tick = Instant::now();
+                        if let Some(progress) = control.progress() {
+                            if progress.done && !done {
+                                log::info!("render done in {:.2?}", progress.elapsed);
+                            }
+                            done = progress.done;
+                            let status = Self::title(progress);
+                            if status != title {
+                                display.gl_window().window().set_title(&status);
+                                title = status;
+                            }
+                        }
+                    }
                     glutin::event::StartCause::Init => (),
                     _ => return,
                 },
+                Event::RedrawRequested(_) => redraw = true,
+                Event::LoopDestroyed => {
+                    control.close();
+                    if let Some(Err(e)) = drawer.take().map(|d| d.join()) {
+                        error!("drawer panicked: {:?}", e);
+                    }
+                    return;
+                }
                 _ => return,
             }
 
-            if canvas.read().expect("read lock canvas").dirty {
-                let mut c_lock = canvas.write().expect("read lock canvas");
-                let viewport = (width as f32, height as f32);
-                match gpu::render_buffer(&display, c_lock.as_raw(), &pixel_program, viewport) {
-                    Ok(_) => c_lock.dirty = false,
-                    Err(e) => error!("paint error: {:?}", e),
-                };
+            if canvas.read().expect("read lock canvas").is_dirty() {
+                let dirty = canvas.write().expect("write lock canvas").take_dirty();
+                // copied without holding up the drawer, pixels set meanwhile
+                // being marked again
+                let c_lock = canvas.read().expect("read lock canvas");
+                for region in dirty {
+                    texture.update(region, c_lock.texels(region));
+                    redraw = true;
+                }
+            }
+            if redraw {
+                redraw = false;
+                let selection = selecting.map(|(from, to)| {
+                    Self::selection(from, to, width, height).unwrap_or(Region {
+                        x: 0,
+                        y: 0,
+                        width: 0,
+                        height: 0,
+                    })
+                });
+                if let Err(e) = texture.draw(&display, &pixel_program, view.matrix(), selection) {
+                    error!("paint error: {:?}", e);
+                }
             }
         });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_is_clipped() {
+        let region = Context::selection((12.5, 3.2), (-4.0, 9.0), 10, 8);
+        assert_eq!(
+            region,
+            Some(Region {
+                x: 0,
+                y: 3,
+                width: 10,
+                height: 5,
+            })
+        );
+        assert_eq!(Context::selection((2.0, 2.0), (2.0, 5.0), 10, 8), None);
+        assert_eq!(Context::selection((-3.0, 1.0), (-1.0, 5.0), 10, 8), None);
+    }
+
+    #[test]
+    fn dirty_regions() {
+        let mut canvas = Canvas::new(10, 8);
+        assert!(!canvas.is_dirty());
+        let red = Color {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0,
+        };
+        canvas.set(2, 3, red);
+        canvas.set(5, 1, red);
+        canvas.set(4, 2, red);
+        canvas.set(7, 6, red);
+        assert!(!canvas.set(10, 1, red));
+        let regions = canvas.take_dirty();
+        assert_eq!(
+            regions,
+            [
+                Region {
+                    x: 2,
+                    y: 1,
+                    width: 4,
+                    height: 3,
+                },
+                Region {
+                    x: 7,
+                    y: 6,
+                    width: 1,
+                    height: 1,
+                },
+            ]
+        );
+        let pixels = canvas.texels(regions[0]);
+        assert_eq!(pixels.len(), 12);
+        assert_eq!(pixels[3], (1.0, 0.0, 0.0));
+        assert_eq!(pixels[8], (1.0, 0.0, 0.0));
+        assert_eq!(pixels[0], (0.0, 0.0, 0.0));
+        assert!(!canvas.is_dirty());
+        assert!(canvas.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn stacked() {
+        let mut top = Canvas::new(3, 2);
+        let bottom = Canvas::new(3, 1);
+        let white = Color {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        };
+        top.set(1, 1, white);
+        let canvas = Canvas::stacked(&top, &bottom);
+        assert_eq!((canvas.width, canvas.height), (3, 3));
+        assert_eq!(canvas.get(1, 1), Pixel::Data(white));
+        assert_eq!(canvas.get(1, 2), Pixel::Blank);
     }
 }
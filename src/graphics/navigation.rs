@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use glium::glutin::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+};
+
+use crate::graphics::control::{RenderControl, Restart};
+use crate::math::Radian;
+use crate::tracer::camera::Camera;
+use crate::tracer::scenefile::format_camera;
+
+/// Units per second when flying around.
+const MOVE_SPEED: f32 = 4.0;
+/// Radians per pixel of mouse drag.
+const LOOK_SPEED: f32 = 0.005;
+/// Distance factor applied for each wheel notch.
+const ZOOM_STEP: f32 = 0.9;
+/// Smallest angle kept between the view direction and the up vector.
+const MIN_PITCH_ANGLE: f32 = 0.05;
+
+const FLY_KEYS: [VirtualKeyCode; 6] = [
+    VirtualKeyCode::W,
+    VirtualKeyCode::A,
+    VirtualKeyCode::S,
+    VirtualKeyCode::D,
+    VirtualKeyCode::Space,
+    VirtualKeyCode::C,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Drag {
+    None,
+    Look,
+    Orbit,
+}
+
+/// Camera controls of the preview window.
+///
+/// - WASD moves forward, left, backward and right, Space and C up and down
+/// - dragging with the left button looks around
+/// - dragging with the right button orbits around the target
+/// - the wheel zooms toward the target
+/// - P prints the camera, as it also is when a move ends
+///
+/// The target sits in front of the camera at its
+/// [`target_distance`](Camera::target_distance), the look_at point of the
+/// scene file, that distance being changed by zooming.
+pub struct Navigation {
+    pressed: HashSet<VirtualKeyCode>,
+    drag: Drag,
+    /// Whether the camera moved since the drag started.
+    dragged: bool,
+    cursor: Option<(f64, f64)>,
+}
+
+impl Default for Navigation {
+    fn default() -> Navigation {
+        Navigation {
+            pressed: HashSet::new(),
+            drag: Drag::None,
+            dragged: false,
+            cursor: None,
+        }
+    }
+}
+
+impl Navigation {
+    /// Handles navigation keys, returns `false` for the other ones.
+    pub fn key(&mut self, input: KeyboardInput, control: &RenderControl) -> bool {
+        let code = match input.virtual_keycode {
+            Some(code) => code,
+            None => return false,
+        };
+        if code == VirtualKeyCode::P {
+            if input.state == ElementState::Pressed {
+                Self::print(control);
+            }
+            return true;
+        }
+        if !FLY_KEYS.contains(&code) {
+            return false;
+        }
+        match input.state {
+            ElementState::Pressed => {
+                self.pressed.insert(code);
+            }
+            ElementState::Released => {
+                self.pressed.remove(&code);
+                if self.pressed.is_empty() {
+                    Self::print(control);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn mouse_button(
+        &mut self,
+        button: MouseButton,
+        state: ElementState,
+        control: &RenderControl,
+    ) {
+        match (state, button) {
+            (ElementState::Pressed, MouseButton::Left) => self.drag = Drag::Look,
+            (ElementState::Pressed, MouseButton::Right) => self.drag = Drag::Orbit,
+            (ElementState::Released, MouseButton::Left)
+            | (ElementState::Released, MouseButton::Right)
+                if self.drag != Drag::None =>
+            {
+                self.drag = Drag::None;
+                // a click without a move inspects the pixel instead
+                if std::mem::take(&mut self.dragged) {
+                    Self::print(control);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn cursor_moved(&mut self, x: f64, y: f64, control: &RenderControl) {
+        let previous = self.cursor.replace((x, y));
+        let (dx, dy) = match previous {
+            Some((px, py)) if self.drag != Drag::None => ((x - px) as f32, (y - py) as f32),
+            _ => return,
+        };
+        self.dragged = true;
+        let drag = self.drag;
+        Self::update(control, |camera| {
+            let yaw = Radian(-dx * LOOK_SPEED);
+            let pitch = Radian(-dy * LOOK_SPEED);
+            match drag {
+                Drag::Look => look(camera, yaw, pitch),
+                Drag::Orbit => {
+                    let distance = camera.target_distance;
+                    let target = camera.position + camera.direction.normalize() * distance;
+                    look(camera, yaw, pitch);
+                    camera.position = target + camera.direction * -distance;
+                }
+                Drag::None => {}
+            }
+        });
+    }
+
+    pub fn scroll(&mut self, delta: MouseScrollDelta, control: &RenderControl) {
+        let notches = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+        };
+        if notches == 0.0 {
+            return;
+        }
+        Self::update(control, |camera| {
+            let distance = camera.target_distance * ZOOM_STEP.powf(notches);
+            let step = camera.target_distance - distance;
+            camera.position = camera.position + camera.direction.normalize() * step;
+            camera.target_distance = distance;
+        });
+    }
+
+    /// Moves the camera according to the held keys, `elapsed` seconds after
+    /// the previous call.
+    pub fn fly(&mut self, elapsed: f32, control: &RenderControl) {
+        if self.pressed.is_empty() {
+            return;
+        }
+        let axis = |positive, negative| {
+            let value = |code| {
+                if self.pressed.contains(&code) {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            value(positive) - value(negative)
+        };
+        let forward = axis(VirtualKeyCode::W, VirtualKeyCode::S);
+        let right = axis(VirtualKeyCode::D, VirtualKeyCode::A);
+        let up = axis(VirtualKeyCode::Space, VirtualKeyCode::C);
+        let step = MOVE_SPEED * elapsed;
+        Self::update(control, |camera| {
+            let (u, v, w) = camera.basis();
+            let offset = u * (right * step) + v * (up * step) + w * (-forward * step);
+            camera.position = camera.position + offset;
+        });
+    }
+
+    fn update(control: &RenderControl, change: impl FnOnce(&mut Camera)) {
+        if let Some(mut camera) = control.camera() {
+            change(&mut camera);
+            control.set_camera(camera);
+            control.restart(Restart::Camera);
+        }
+    }
+
+    fn print(control: &RenderControl) {
+        if let Some(camera) = control.camera() {
+            println!("{}", format_camera(&camera));
+        }
+    }
+}
+
+/// Turns the camera around the up vector then its horizontal axis.
+fn look(camera: &mut Camera, yaw: Radian, pitch: Radian) {
+    let up = camera.up.normalize();
+    let direction = camera.direction.normalize().rotate(up, yaw);
+    let right = direction.cross(up).normalize();
+    let pitched = direction.rotate(right, pitch);
+    let angle = pitched.dot(up).acos();
+    if angle > MIN_PITCH_ANGLE && angle < std::f32::consts::PI - MIN_PITCH_ANGLE {
+        camera.direction = pitched;
+    } else {
+        camera.direction = direction;
+    }
+}
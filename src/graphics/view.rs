@@ -0,0 +1,122 @@
+/// Smallest and largest zoom factors, relative to the letterboxed fit.
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 64.0;
+/// Zoom factor applied for each wheel notch or key press.
+const ZOOM_STEP: f32 = 1.25;
+/// Window pixels moved for each arrow key press.
+const PAN_STEP: f32 = 32.0;
+
+/// Placement of the canvas in the window.
+///
+/// The canvas is letterboxed to fit the window, then zoomed by `zoom` around
+/// the canvas point `pan` which stays at the center of the window. Both the
+/// window size and cursor positions are in physical pixels.
+pub struct View {
+    canvas: (f32, f32),
+    window: (f32, f32),
+    zoom: f32,
+    pan: (f32, f32),
+}
+
+impl View {
+    pub fn new(canvas: (usize, usize), window: (u32, u32)) -> View {
+        View {
+            canvas: (canvas.0 as f32, canvas.1 as f32),
+            window: (window.0.max(1) as f32, window.1.max(1) as f32),
+            zoom: 1.0,
+            pan: (0.0, 0.0),
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.window = (width.max(1) as f32, height.max(1) as f32);
+    }
+
+    /// Window pixels per canvas pixel.
+    fn scale(&self) -> f32 {
+        let fit = (self.window.0 / self.canvas.0).min(self.window.1 / self.canvas.1);
+        fit * self.zoom
+    }
+
+    /// Transform from canvas pixels, centered on the canvas, to clip space.
+    pub fn matrix(&self) -> [[f32; 4]; 4] {
+        let sx = 2.0 * self.scale() / self.window.0;
+        let sy = -2.0 * self.scale() / self.window.1;
+        [
+            [sx, 0.0, 0.0, 0.0],
+            [0.0, sy, 0.0, 0.0],
+            [0.0, 0.0, -1.0, 0.0],
+            [-self.pan.0 * sx, -self.pan.1 * sy, 0.0, 1.0],
+        ]
+    }
+
+    /// Canvas position under the window position `(x, y)`, which may be
+    /// outside of the canvas.
+    pub fn to_canvas(&self, x: f64, y: f64) -> (f32, f32) {
+        let scale = self.scale();
+        (
+            self.pan.0 + (x as f32 - self.window.0 / 2.0) / scale + self.canvas.0 / 2.0,
+            self.pan.1 + (y as f32 - self.window.1 / 2.0) / scale + self.canvas.1 / 2.0,
+        )
+    }
+
+    /// Zooms by `notches` steps, keeping the canvas point under the window
+    /// position `(x, y)` in place.
+    pub fn zoom_at(&mut self, notches: f32, x: f64, y: f64) {
+        let before = self.to_canvas(x, y);
+        self.zoom = (self.zoom * ZOOM_STEP.powf(notches)).clamp(MIN_ZOOM, MAX_ZOOM);
+        let after = self.to_canvas(x, y);
+        self.pan.0 += before.0 - after.0;
+        self.pan.1 += before.1 - after.1;
+    }
+
+    /// Zooms around the window center.
+    pub fn zoom_center(&mut self, notches: f32) {
+        let (x, y) = (self.window.0 as f64 / 2.0, self.window.1 as f64 / 2.0);
+        self.zoom_at(notches, x, y);
+    }
+
+    /// Moves the canvas along with a drag of `(dx, dy)` window pixels.
+    pub fn drag(&mut self, dx: f32, dy: f32) {
+        let scale = self.scale();
+        self.pan.0 -= dx / scale;
+        self.pan.1 -= dy / scale;
+    }
+
+    /// Moves the view by `(x, y)` arrow key presses.
+    pub fn step(&mut self, x: f32, y: f32) {
+        self.drag(-x * PAN_STEP, -y * PAN_STEP);
+    }
+
+    /// Back to the letterboxed canvas.
+    pub fn reset(&mut self) {
+        self.zoom = 1.0;
+        self.pan = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox() {
+        // twice as wide as the canvas ratio, bands on the left and right
+        let view = View::new((400, 300), (1600, 600));
+        assert_eq!(view.to_canvas(800.0, 300.0), (200.0, 150.0));
+        assert_eq!(view.to_canvas(400.0, 0.0), (0.0, 0.0));
+        assert_eq!(view.to_canvas(0.0, 0.0), (-200.0, 0.0));
+    }
+
+    #[test]
+    fn zoom_keeps_cursor_point() {
+        let mut view = View::new((400, 300), (800, 600));
+        let before = view.to_canvas(100.0, 500.0);
+        view.zoom_at(3.0, 100.0, 500.0);
+        let after = view.to_canvas(100.0, 500.0);
+        assert!((before.0 - after.0).abs() < 1e-3);
+        assert!((before.1 - after.1).abs() < 1e-3);
+        view.reset();
+        assert_eq!(view.to_canvas(400.0, 300.0), (200.0, 150.0));
+    }
+}
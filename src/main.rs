@@ -1,114 +1,500 @@
-use rusty::graphics::{CanvasLock, Context};
-use rusty::math::vec3::{Vector, Vertex};
-use rusty::tracer::lights::{DirectionalLight, SphericalLight};
-use rusty::tracer::objects::{Plane, Sphere};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use clap::{Parser, ValueEnum};
+use rusty::graphics::{Canvas, Context, Region, RenderControl, Restart};
+use rusty::tracer::camera::Eye;
+use rusty::tracer::graph::GraphError;
+use rusty::tracer::render::{render, Integrator, Settings};
+use rusty::tracer::scenefile::{SceneError, SceneFile};
 use rusty::tracer::{Scene, Screen};
 
-pub fn main() -> Result<(), rusty::graphics::Error> {
+/// The scene file could not be parsed (EX_DATAERR).
+const EXIT_SCENE: u8 = 65;
+/// Something went wrong in the preview window (EX_SOFTWARE).
+const EXIT_GRAPHICS: u8 = 70;
+/// A file could not be read or written (EX_IOERR).
+const EXIT_IO: u8 = 74;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IntegratorArg {
+    Direct,
+    Path,
+}
+
+/// How the images of the two eyes are written.
+#[derive(Clone, Copy, ValueEnum)]
+enum StereoArg {
+    /// One image per eye, suffixed with _left and _right
+    Separate,
+    /// The left eye above the right one in a single image
+    TopBottom,
+}
+
+/// Part of the image to render.
+#[derive(Clone, Copy)]
+enum RegionArg {
+    Pixels(Region),
+    /// Left, top, width and height as fractions of the image size.
+    Window([f32; 4]),
+}
+
+impl RegionArg {
+    fn resolve(self, width: usize, height: usize) -> Region {
+        match self {
+            RegionArg::Pixels(region) => region,
+            RegionArg::Window([x, y, w, h]) => {
+                let left = (x * width as f32).floor() as usize;
+                let top = (y * height as f32).floor() as usize;
+                let right = ((x + w) * width as f32).ceil() as usize;
+                let bottom = ((y + h) * height as f32).ceil() as usize;
+                Region {
+                    x: left,
+                    y: top,
+                    width: right.saturating_sub(left),
+                    height: bottom.saturating_sub(top),
+                }
+            }
+        }
+    }
+}
+
+/// Renders a scene file, either in a preview window or straight to an image.
+#[derive(Parser)]
+#[command(
+    version,
+    after_help = "Exit status: 0 on success, 2 on invalid arguments, 65 when the scene \
+                  is invalid, 70 on a window or GPU error and 74 on a file IO error."
+)]
+struct Cli {
+    /// Scene description file
+    #[arg(default_value = "scenes/demo.toml")]
+    scene: PathBuf,
+    /// Image written once the render is done, or directory of the frames
+    /// of a sequence [default: rusty_<timestamp>.png in headless mode, the
+    /// current directory for sequences]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Image size, as WIDTHxHEIGHT
+    #[arg(short, long, value_parser = parse_resolution)]
+    resolution: Option<(usize, usize)>,
+    /// Samples per pixel
+    #[arg(short, long)]
+    samples: Option<usize>,
+    /// Maximum number of bounces for the path integrator
+    #[arg(short = 'd', long)]
+    max_depth: Option<usize>,
+    /// Number of render threads [default: number of CPUs]
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+    /// Light transport algorithm
+    #[arg(short, long, value_enum)]
+    integrator: Option<IntegratorArg>,
+    /// Seed of the random sequences, for reproducible renders
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Only render X,Y,WIDTH,HEIGHT, in pixels or as fractions of the image
+    /// size like 0.25,0.25,0.5,0.5
+    #[arg(long, value_parser = parse_region)]
+    region: Option<RegionArg>,
+    /// Leave out the scene graph node at PATH, like car/wheel_front_left
+    #[arg(long, value_name = "PATH")]
+    hide: Vec<String>,
+    /// Filter the noise out of the final image
+    #[arg(long)]
+    denoise: bool,
+    /// Render without opening a window
+    #[arg(long, conflicts_with = "preview")]
+    headless: bool,
+    /// Render the frames of the animation as frame_0001.png, frame_0002.png...
+    #[arg(long, conflicts_with = "preview")]
+    sequence: bool,
+    /// Frames of the sequence, as FIRST-LAST [default: from the scene file]
+    #[arg(long, value_parser = parse_frames, requires = "sequence")]
+    frames: Option<(usize, usize)>,
+    /// Fraction of the frame interval the shutter stays open, blurring what
+    /// moves [default: from the scene file, or 0]
+    #[arg(long, value_parser = parse_shutter)]
+    shutter: Option<f32>,
+    /// Render an image for each eye, spaced by the interocular distance of
+    /// the camera
+    #[arg(long, value_enum, conflicts_with = "preview")]
+    stereo: Option<StereoArg>,
+    /// Skip the frames of the sequence already written
+    #[arg(long, requires = "sequence")]
+    resume: bool,
+    /// Follow the render in a window (default)
+    #[arg(long)]
+    preview: bool,
+}
+
+fn parse_resolution(src: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid resolution `{}`, expected WIDTHxHEIGHT", src);
+    let (width, height) = src.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_frames(src: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid frames `{}`, expected FIRST-LAST", src);
+    let (first, last) = src.split_once('-').ok_or_else(invalid)?;
+    match (first.parse(), last.parse()) {
+        (Ok(first), Ok(last)) if first <= last => Ok((first, last)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_shutter(src: &str) -> Result<f32, String> {
+    match src.parse() {
+        Ok(shutter) if (0.0..=1.0).contains(&shutter) => Ok(shutter),
+        _ => Err(format!(
+            "invalid shutter `{}`, expected a number from 0 to 1",
+            src
+        )),
+    }
+}
+
+fn parse_region(src: &str) -> Result<RegionArg, String> {
+    let invalid = || format!("invalid region `{}`, expected X,Y,WIDTH,HEIGHT", src);
+    let parts: Vec<&str> = src.split(',').collect();
+    if parts.len() != 4 {
+        return Err(invalid());
+    }
+    if parts.iter().any(|p| p.contains('.')) {
+        let mut window = [0.0; 4];
+        for (value, part) in window.iter_mut().zip(&parts) {
+            *value = part.trim().parse().map_err(|_| invalid())?;
+        }
+        let [x, y, w, h] = window;
+        if x < 0.0 || y < 0.0 || w <= 0.0 || h <= 0.0 || x + w > 1.0 || y + h > 1.0 {
+            return Err(format!(
+                "invalid region `{}`, fractions out of the image",
+                src
+            ));
+        }
+        Ok(RegionArg::Window(window))
+    } else {
+        let mut pixels = [0; 4];
+        for (value, part) in pixels.iter_mut().zip(&parts) {
+            *value = part.trim().parse().map_err(|_| invalid())?;
+        }
+        let [x, y, width, height] = pixels;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        Ok(RegionArg::Pixels(Region {
+            x,
+            y,
+            width,
+            height,
+        }))
+    }
+}
+
+impl Cli {
+    /// Hides the nodes given with `--hide` and sets the shutter, posing
+    /// the scene again, and returns the first node missing from the scene.
+    fn pose(&self, file: &mut SceneFile) -> Result<(), GraphError> {
+        let mut result = Ok(());
+        for path in &self.hide {
+            if let Err(e) = file.graph.set_visible(path, false) {
+                result = result.and(Err(e));
+            }
+        }
+        if let Some(shutter) = self.shutter {
+            file.animation.shutter = shutter;
+        }
+        if !self.hide.is_empty() || self.shutter.is_some() {
+            file.scene = file.scene_at(file.animation.start());
+        }
+        result
+    }
+
+    /// Overrides the scene file render settings with the given arguments.
+    fn apply(&self, settings: Settings) -> Settings {
+        let (width, height) = self.resolution.unwrap_or((settings.width, settings.height));
+        Settings {
+            width,
+            height,
+            samples: self.samples.unwrap_or(settings.samples),
+            max_depth: self.max_depth.unwrap_or(settings.max_depth),
+            integrator: match self.integrator {
+                Some(IntegratorArg::Direct) => Integrator::Direct,
+                Some(IntegratorArg::Path) => Integrator::Path,
+                None => settings.integrator,
+            },
+            seed: self.seed.unwrap_or(settings.seed),
+            denoise: self.denoise || settings.denoise,
+            threads: self.threads.unwrap_or(settings.threads),
+            region: match self.region {
+                Some(region) => Some(region.resolve(width, height)),
+                None => settings.region,
+            },
+        }
+    }
+}
+
+pub fn main() -> ExitCode {
     env_logger::init();
+    let cli = Cli::parse();
 
-    let mut gui = Context::new(800, 600);
-    gui.run(raytracer)
-}
-
-fn raytracer(canvas: CanvasLock) {
-    let mut scene = Scene::default();
-    scene.add_object(Sphere {
-        center: Vertex {
-            x: 0.0,
-            y: 0.0,
-            z: -10.0,
-        },
-        radius: 2.0,
-        base_color: "#00FFFF".parse().unwrap(),
-        base_albedo: 0.8,
-    });
-    scene.add_object(Sphere {
-        center: Vertex {
-            x: -2.0,
-            y: 2.0,
-            z: -6.0,
-        },
-        radius: 2.0,
-        base_color: "#FF00FF".parse().unwrap(),
-        base_albedo: 0.6,
-    });
-    scene.add_object(Sphere {
-        center: Vertex {
-            x: 3.0,
-            y: 0.0,
-            z: -15.0,
-        },
-        radius: 5.0,
-        base_color: "#FFFF00".parse().unwrap(),
-        base_albedo: 0.7,
-    });
-    scene.add_object(Sphere {
-        center: Vertex {
-            x: 3.0,
-            y: 10.0,
-            z: -12.0,
-        },
-        radius: 3.0,
-        base_color: "#CCCC00".parse().unwrap(),
-        base_albedo: 0.8,
-    });
-    scene.add_object(Plane {
-        point: Vertex {
-            x: 0.0,
-            y: -5.0,
-            z: 0.0,
-        },
-        normal: Vector {
-            x: 0.0,
-            y: -1.0,
-            z: 0.0,
-        },
-        base_color: "#CCCCCC".parse().unwrap(),
-        base_albedo: 0.5,
-    });
-    scene.add_light(DirectionalLight {
-        direction: Vector {
-            x: -0.5,
-            y: -1.5,
-            z: -1.0,
-        },
-        base_color: "#3BEB47".parse().unwrap(),
-        base_intensity: 0.6,
-    });
-    scene.add_light(SphericalLight {
-        position: Vertex {
-            x: 1.0,
-            y: -1.0,
-            z: -1.0,
-        },
-        base_color: "#FFFFFF".parse().unwrap(),
-        base_intensity: 1000.0,
-    });
-    scene.add_light(SphericalLight {
-        position: Vertex {
-            x: 1.0,
-            y: -1.0,
-            z: -1.0,
-        },
-        base_color: "#EB3BE4".parse().unwrap(),
-        base_intensity: 2000.0,
-    });
+    let mut file = match SceneFile::load(&cli.scene) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("{}: {}", cli.scene.display(), e);
+            return match e {
+                SceneError::Io(_) => ExitCode::from(EXIT_IO),
+                _ => ExitCode::from(EXIT_SCENE),
+            };
+        }
+    };
+    if let Err(e) = cli.pose(&mut file) {
+        eprintln!("error: invalid value for '--hide <PATH>': {}", e);
+        return ExitCode::from(2);
+    }
+    let settings = cli.apply(file.settings.clone());
+
+    if cli.stereo.is_some() && file.scene.camera.stereo.interocular == 0.0 {
+        log::warn!("the camera has no interocular distance, both eyes see the same");
+    }
+    if cli.sequence {
+        let frames = cli.frames.unwrap_or(file.animation.frames);
+        let dir = cli.output.clone().unwrap_or_else(|| PathBuf::from("."));
+        return sequence(&file, &settings, frames, &dir, cli.resume, cli.stereo);
+    }
+    let scene = file.scene;
+    if cli.headless || cli.stereo.is_some() {
+        headless(scene, settings, cli.output, cli.stereo)
+    } else {
+        preview(cli, scene, settings)
+    }
+}
+
+fn render_canvas(scene: &Scene, settings: &Settings) -> Canvas {
+    let start = Instant::now();
+    let frame = render(scene, settings, &RenderControl::default(), |_, _, _| {})
+        .expect("uncancelled render");
+    log::info!(
+        "rendered in {:.2?}, rays: {:?}",
+        start.elapsed(),
+        scene.stats.rays()
+    );
+    let mut canvas = Canvas::new(frame.width, frame.height);
+    frame.draw(&mut canvas);
+    canvas
+}
+
+/// Renders `scene` as seen through each eye in stereo, along with the
+/// suffix of their file names.
+fn render_views(
+    scene: &mut Scene,
+    settings: &Settings,
+    stereo: Option<StereoArg>,
+) -> Vec<(&'static str, Canvas)> {
+    let stereo = match stereo {
+        Some(stereo) => stereo,
+        None => return vec![("", render_canvas(scene, settings))],
+    };
+    let set_eye = |scene: &mut Scene, eye| {
+        scene.camera.eye = eye;
+        if let Some(closing) = &mut scene.closing_camera {
+            closing.eye = eye;
+        }
+    };
+    set_eye(scene, Some(Eye::Left));
+    let left = render_canvas(scene, settings);
+    set_eye(scene, Some(Eye::Right));
+    let right = render_canvas(scene, settings);
+    set_eye(scene, None);
+    match stereo {
+        StereoArg::Separate => vec![("_left", left), ("_right", right)],
+        StereoArg::TopBottom => vec![("", Canvas::stacked(&left, &right))],
+    }
+}
 
-    let (width, height) = {
-        let c = canvas.read().expect("read lock canvas");
-        (c.width, c.height)
+/// `path` with `suffix` appended to the file name before the extension.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}{}", stem, suffix);
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+fn headless(
+    mut scene: Scene,
+    settings: Settings,
+    output: Option<PathBuf>,
+    stereo: Option<StereoArg>,
+) -> ExitCode {
+    let path = output.unwrap_or_else(|| PathBuf::from(Context::export_name()));
+    for (suffix, canvas) in render_views(&mut scene, &settings, stereo) {
+        let path = with_suffix(&path, suffix);
+        if let Err(e) = Context::save(&canvas, &path) {
+            eprintln!("failed to save the image: {:?}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+        println!("{}", path.display());
+    }
+    ExitCode::SUCCESS
+}
+
+/// Renders the animation frames in `dir`, printing the path of each image.
+fn sequence(
+    file: &SceneFile,
+    settings: &Settings,
+    (first, last): (usize, usize),
+    dir: &Path,
+    resume: bool,
+    stereo: Option<StereoArg>,
+) -> ExitCode {
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("{}: {}", dir.display(), e);
+        return ExitCode::from(EXIT_IO);
+    }
+    let suffixes: &[&str] = match stereo {
+        Some(StereoArg::Separate) => &["_left", "_right"],
+        _ => &[""],
     };
-    for (point, ray) in Screen::new(width, height) {
-        let interception = match scene.trace(&ray) {
-            Some(i) => i,
-            None => continue,
-        };
-        let color = scene.compute_color(&interception);
-        canvas
-            .write()
-            .expect("write lock canvas")
-            .set(point.0, point.1, color);
-    }
-    log::info!("rays: {:?}", scene.stats.rays);
+    for frame in first..=last {
+        let path = dir.join(format!("frame_{:04}.png", frame));
+        if resume && suffixes.iter().all(|s| with_suffix(&path, s).exists()) {
+            log::info!("{} already rendered", path.display());
+            continue;
+        }
+        let mut scene = file.scene_at(file.animation.time(frame));
+        for (suffix, canvas) in render_views(&mut scene, settings, stereo) {
+            let path = with_suffix(&path, suffix);
+            // renamed once complete, so that an interrupted save is
+            // rendered again on resume
+            let partial = with_suffix(&path, ".partial");
+            if let Err(e) = Context::save(&canvas, &partial) {
+                eprintln!("failed to save the image: {:?}", e);
+                return ExitCode::from(EXIT_IO);
+            }
+            if let Err(e) = fs::rename(&partial, &path) {
+                eprintln!("{}: {}", path.display(), e);
+                return ExitCode::from(EXIT_IO);
+            }
+            println!("{}", path.display());
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Shares `scene` with the window so that clicking a pixel re-traces its
+/// primary ray.
+fn share_scene(scene: Scene, settings: &Settings, control: &RenderControl) -> Arc<RwLock<Scene>> {
+    let scene = Arc::new(RwLock::new(scene));
+    let (width, height) = (settings.width, settings.height);
+    let shared = scene.clone();
+    control.set_inspector(move |x, y| {
+        let scene = shared.read().expect("read lock scene");
+        let screen =
+            Screen::with_camera(scene.camera, width, height).with_motion(scene.closing_camera);
+        // as traced by the first sample of the pixel
+        match screen.ray_at_time(x as f32 + 0.5, y as f32 + 0.5, 0.5) {
+            Some(ray) => scene.inspect(&ray).to_string(),
+            None => "outside of the camera projection".to_string(),
+        }
+    });
+    scene
+}
+
+/// Renders in a window, starting over each time the scene file is saved.
+///
+/// Only the region of interest is rendered again when it is set, keeping
+/// the rest of the previous image.
+fn preview(cli: Cli, scene: Scene, settings: Settings) -> ExitCode {
+    let mut gui = Context::new(settings.width, settings.height);
+    gui.watch(&cli.scene);
+    // the exit status reflects the last save of the output file
+    let save_failed = Arc::new(AtomicBool::new(false));
+    let save_failed_ = save_failed.clone();
+    let result = gui.run(move |canvas, control| {
+        let (width, height) = (settings.width, settings.height);
+        control.set_camera(scene.camera);
+        control.set_region(settings.region);
+        let mut current = Some((share_scene(scene, &settings, &control), settings));
+        loop {
+            if let Some((scene, settings)) = &current {
+                let scene = scene.read().expect("read lock scene");
+                let frame = render(&scene, settings, &control, |x, y, run| {
+                    let mut c_lock = canvas.write().expect("write lock canvas");
+                    for (i, color) in run.iter().enumerate() {
+                        c_lock.set(x + i, y, *color);
+                    }
+                });
+                if let Some(frame) = frame {
+                    if settings.denoise {
+                        frame.draw(&mut canvas.write().expect("write lock canvas"));
+                    }
+                    log::info!("rays: {:?}", scene.stats.rays());
+                    if let Some(path) = &cli.output {
+                        let c_lock = canvas.read().expect("read lock canvas");
+                        let saved = Context::save(&c_lock, path);
+                        if let Err(e) = &saved {
+                            log::error!("failed to save {}: {:?}", path.display(), e);
+                        }
+                        save_failed_.store(saved.is_err(), Ordering::Relaxed);
+                    }
+                }
+            }
+
+            let restart = match control.wait_restart() {
+                Some(restart) => restart,
+                None => return,
+            };
+            if restart != Restart::Reload {
+                if let Some((scene, settings)) = current.as_mut() {
+                    settings.region = control.region();
+                    if let (Restart::Camera, Some(camera)) = (restart, control.camera()) {
+                        scene.write().expect("write lock scene").camera = camera;
+                        if settings.region.is_none() {
+                            canvas.write().expect("write lock canvas").clear();
+                        }
+                    }
+                }
+                continue;
+            }
+            // an invalid file keeps the last image until the next change
+            current = match SceneFile::load(&cli.scene) {
+                Ok(mut file) => {
+                    if let Err(e) = cli.pose(&mut file) {
+                        log::warn!("--hide: {}", e);
+                    }
+                    let settings = Settings {
+                        width,
+                        height,
+                        region: control.region(),
+                        ..cli.apply(file.settings)
+                    };
+                    control.set_camera(file.scene.camera);
+                    if settings.region.is_none() {
+                        canvas.write().expect("write lock canvas").clear();
+                    }
+                    Some((share_scene(file.scene, &settings, &control), settings))
+                }
+                Err(e) => {
+                    log::error!("{}: {}", cli.scene.display(), e);
+                    None
+                }
+            };
+        }
+    });
+    match result {
+        Ok(_) if save_failed.load(Ordering::Relaxed) => ExitCode::from(EXIT_IO),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("preview failed: {:?}", e);
+            ExitCode::from(EXIT_GRAPHICS)
+        }
+    }
 }
@@ -0,0 +1,369 @@
+use std::ops::Mul;
+
+use crate::math::quaternion::Quaternion;
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::Radian;
+
+/// Row-major 4x4 matrix, transforming column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Default for Matrix4 {
+    fn default() -> Matrix4 {
+        Matrix4::identity()
+    }
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        Matrix4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn translation(offset: Vector) -> Matrix4 {
+        Matrix4 {
+            m: [
+                [1.0, 0.0, 0.0, offset.x],
+                [0.0, 1.0, 0.0, offset.y],
+                [0.0, 0.0, 1.0, offset.z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn scaling(factors: Vector) -> Matrix4 {
+        Matrix4 {
+            m: [
+                [factors.x, 0.0, 0.0, 0.0],
+                [0.0, factors.y, 0.0, 0.0],
+                [0.0, 0.0, factors.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Rotation of `angle` around `axis`, counterclockwise when looking
+    /// down the axis.
+    pub fn from_axis_angle(axis: Vector, angle: Radian) -> Matrix4 {
+        Quaternion::from_axis_angle(axis, angle).into()
+    }
+
+    /// Frame at `eye` whose -z axis points toward `target` and y axis is
+    /// closest to `up`, mapping local coordinates to world ones.
+    ///
+    /// Its inverse is the usual view matrix.
+    pub fn look_at(eye: Vertex, target: Vertex, up: Vector) -> Matrix4 {
+        let w = -Vector::from_vertices(eye, target).normalize();
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+        Matrix4 {
+            m: [
+                [u.x, v.x, w.x, eye.x],
+                [u.y, v.y, w.y, eye.y],
+                [u.z, v.z, w.z, eye.z],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Entry-wise interpolation from `self` at `t = 0` to `other` at
+    /// `t = 1`, moving transformed points along straight lines.
+    pub fn lerp(&self, other: &Matrix4, t: f32) -> Matrix4 {
+        let mut m = self.m;
+        for (row, other) in m.iter_mut().zip(&other.m) {
+            for (value, other) in row.iter_mut().zip(other) {
+                *value += (other - *value) * t;
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    /// Inverse by Gauss-Jordan elimination, `None` when singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap())
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix4 { m: inv })
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    /// `self * rhs` applies `rhs` first.
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+impl Mul<Vertex> for Matrix4 {
+    type Output = Vertex;
+
+    fn mul(self, p: Vertex) -> Vertex {
+        let row = |i: usize| {
+            let r = self.m[i];
+            r[0] * p.x + r[1] * p.y + r[2] * p.z + r[3]
+        };
+        let w = row(3);
+        let (x, y, z) = (row(0), row(1), row(2));
+        if w == 1.0 {
+            Vertex { x, y, z }
+        } else {
+            Vertex {
+                x: x / w,
+                y: y / w,
+                z: z / w,
+            }
+        }
+    }
+}
+
+impl Mul<Vector> for Matrix4 {
+    type Output = Vector;
+
+    fn mul(self, v: Vector) -> Vector {
+        let row = |i: usize| {
+            let r = self.m[i];
+            r[0] * v.x + r[1] * v.y + r[2] * v.z
+        };
+        Vector {
+            x: row(0),
+            y: row(1),
+            z: row(2),
+        }
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Matrix4 {
+        let Quaternion { w, x, y, z } = q.normalize();
+        Matrix4 {
+            m: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+}
+
+impl From<Matrix4> for Quaternion {
+    /// Rotation of the upper 3x3 part of `m`, which has to be orthonormal
+    /// with a positive determinant.
+    fn from(m: Matrix4) -> Quaternion {
+        let m = m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // divides by the largest of the four components
+        if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quaternion {
+                w: s / 4.0,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[2][1] - m[1][2]) / s,
+                x: s / 4.0,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: s / 4.0,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quaternion {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: s / 4.0,
+            }
+        }
+        .normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Matrix4, b: Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-5, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Matrix4::translation(Vector {
+            x: 1.0,
+            y: -2.0,
+            z: 3.0,
+        }) * Matrix4::from_axis_angle(
+            Vector {
+                x: 1.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Radian(0.7),
+        ) * Matrix4::scaling(Vector {
+            x: 2.0,
+            y: 0.5,
+            z: 3.0,
+        });
+        let inverse = m.inverse().unwrap();
+        assert_close(m * inverse, Matrix4::identity());
+        assert_close(inverse * m, Matrix4::identity());
+        assert!(Matrix4::scaling(Vector {
+            x: 1.0,
+            y: 0.0,
+            z: 1.0,
+        })
+        .inverse()
+        .is_none());
+    }
+
+    #[test]
+    fn rotation_matches_rodrigues() {
+        let axis = Vector {
+            x: 0.0,
+            y: 0.6,
+            z: 0.8,
+        };
+        let v = Vector {
+            x: 1.0,
+            y: 2.0,
+            z: -1.0,
+        };
+        let rotated = Matrix4::from_axis_angle(axis, Radian(1.2)) * v;
+        let expected = v.rotate(axis, Radian(1.2));
+        assert!((rotated - expected).norm() < 1e-5);
+    }
+
+    #[test]
+    fn quaternion_round_trip() {
+        let axis = Vector {
+            x: 0.0,
+            y: 0.6,
+            z: 0.8,
+        };
+        // through each branch of the conversion
+        for angle in [0.3, 2.0, 3.1, -3.0] {
+            let m = Matrix4::from_axis_angle(axis, Radian(angle));
+            assert_close(Quaternion::from(m).into(), m);
+        }
+        for axis in [(1.0, 0.1, 0.0), (0.1, 1.0, 0.0), (0.0, 0.1, 1.0)] {
+            let (x, y, z) = axis;
+            let m = Matrix4::from_axis_angle(Vector { x, y, z }, Radian(3.0));
+            assert_close(Quaternion::from(m).into(), m);
+        }
+    }
+
+    #[test]
+    fn look_at_frame() {
+        let eye = Vertex {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let target = Vertex {
+            x: 1.0,
+            y: 2.0,
+            z: -7.0,
+        };
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let frame = Matrix4::look_at(eye, target, up);
+        let forward = frame
+            * Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            };
+        assert!((forward - Vector::from_vertices(eye, target).normalize()).norm() < 1e-6);
+        let origin = frame
+            * Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            };
+        assert_eq!(origin, eye);
+    }
+}
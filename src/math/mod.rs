@@ -1,9 +1,18 @@
 use std::convert::From;
 
+use vec3::{Vector, Vertex};
+
+pub mod matrix;
+pub mod noise;
+pub mod quaternion;
+pub mod sampling;
+pub mod transform;
 pub mod vec3;
 
-pub struct Degree(f32);
-pub struct Radian(f32);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Degree(pub f32);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Radian(pub f32);
 
 impl From<Radian> for Degree {
     fn from(angle: Radian) -> Degree {
@@ -17,6 +26,30 @@ impl From<Degree> for Radian {
     }
 }
 
+/// Values interpolated linearly, between keyframes or along color ramps.
+pub trait Lerp: Copy {
+    /// `self` at `t = 0` to `other` at `t = 1`.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vector {
+    fn lerp(self, other: Vector, t: f32) -> Vector {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vertex {
+    fn lerp(self, other: Vertex, t: f32) -> Vertex {
+        self + Vector::from_vertices(self, other) * t
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum QuadraticSolution {
     None,
@@ -32,12 +65,92 @@ pub fn solve_quadratic(a: f32, b: f32, c: f32) -> QuadraticSolution {
         QuadraticSolution::One(-0.5 * b / a)
     } else {
         QuadraticSolution::Two(
-            (-1.0 * b + discriminant.sqrt()) / (2.0 * a),
-            (-1.0 * b - discriminant.sqrt()) / (2.0 * a),
+            (-b + discriminant.sqrt()) / (2.0 * a),
+            (-b - discriminant.sqrt()) / (2.0 * a),
         )
     }
 }
 
+/// Real roots of `x³ + a x² + b x + c`, by increasing values.
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let mut roots = if r * r < q * q * q {
+        // three real roots, trigonometric solution, the ratio being kept
+        // in the acos domain against rounding
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        let third = 2.0 * std::f64::consts::PI / 3.0;
+        vec![
+            s * (theta / 3.0).cos() - a / 3.0,
+            s * (theta / 3.0 + third).cos() - a / 3.0,
+            s * (theta / 3.0 - third).cos() - a / 3.0,
+        ]
+    } else {
+        let u = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let v = if u == 0.0 { 0.0 } else { q / u };
+        vec![u + v - a / 3.0]
+    };
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Real roots of `a x⁴ + b x³ + c x² + d x + e`, by increasing values,
+/// found by Ferrari's method and polished by Newton steps.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // depressed quartic y⁴ + p y² + q y + r, x = y - b / 4
+    let shift = -b / 4.0;
+    let p = c - 3.0 * b * b / 8.0;
+    let q = d - b * c / 2.0 + b * b * b / 8.0;
+    let r = e - b * d / 4.0 + b * b * c / 16.0 - 3.0 * b * b * b * b / 256.0;
+    let mut roots = Vec::new();
+    let mut quadratic = |b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            roots.push((-b + root) / 2.0 + shift);
+            roots.push((-b - root) / 2.0 + shift);
+        }
+    };
+    if q.abs() < 1e-12 {
+        // biquadratic, quadratic in y²
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            for z in [
+                (-p + discriminant.sqrt()) / 2.0,
+                (-p - discriminant.sqrt()) / 2.0,
+            ] {
+                if z >= 0.0 {
+                    quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // y⁴ + p y² + q y + r = (y² + m + p / 2)² - 2m (y - q / 4m)², m
+        // being a positive root of the resolvent cubic
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(0.0, f64::max);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            quadratic(s, p / 2.0 + m - q / (2.0 * s));
+            quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        }
+    }
+    for x in &mut roots {
+        for _ in 0..2 {
+            let f = (((*x + b) * *x + c) * *x + d) * *x + e;
+            let df = ((4.0 * *x + 3.0 * b) * *x + 2.0 * c) * *x + d;
+            if df != 0.0 {
+                *x -= f / df;
+            }
+        }
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,4 +184,43 @@ mod tests {
         assert_eq!(solve_quadratic(1.0, 2.0, 3.0), QuadraticSolution::None);
         assert_eq!(solve_quadratic(1.0, 8.0, 17.0), QuadraticSolution::None);
     }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn cubic() {
+        // (x + 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(-4.0, 1.0, 6.0), &[-1.0, 2.0, 3.0]);
+        // (x - 1)(x² + 1)
+        assert_roots(solve_cubic(-1.0, 1.0, -1.0), &[1.0]);
+        // (x - 0.1)(x - 0.1 - 1e-7)(x + 0.2), close to a double root
+        let (r1, r2, r3) = (0.1, 0.1 + 1e-7, -0.2);
+        let roots = solve_cubic(-(r1 + r2 + r3), r1 * r2 + r1 * r3 + r2 * r3, -r1 * r2 * r3);
+        assert_eq!(roots.len(), 3);
+        for (root, expected) in roots.iter().zip(&[r3, r1, r2]) {
+            assert!((root - expected).abs() < 1e-6, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic(2.0, -20.0, 70.0, -100.0, 48.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x² - 1)(x² - 4), biquadratic
+        assert_roots(
+            solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+        );
+        // (x - 1)(x + 3)(x² + 1)
+        assert_roots(solve_quartic(1.0, 2.0, -2.0, 2.0, -3.0), &[-3.0, 1.0]);
+        assert!(solve_quartic(1.0, 0.0, 2.0, 0.0, 1.0).is_empty());
+    }
 }
@@ -0,0 +1,272 @@
+//! Solid noise functions, smooth pseudo-random values over the space.
+
+use crate::math::vec3::Vertex;
+
+/// Hash of an integer lattice point.
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8DA6_B343)
+        ^ (y as u32).wrapping_mul(0xD816_3841)
+        ^ (z as u32).wrapping_mul(0xCB1A_B31F);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^ (h >> 16)
+}
+
+/// Dot product of `(x, y, z)` with one of the twelve cube edge directions
+/// picked by `h`.
+fn gradient(h: u32, x: f32, y: f32, z: f32) -> f32 {
+    match h % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Improved Perlin gradient noise, in `[-1, 1]` and zero on the integer
+/// lattice.
+pub fn perlin(p: Vertex) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (x, y, z) = (p.x - x0, p.y - y0, p.z - z0);
+    let (i, j, k) = (x0 as i32, y0 as i32, z0 as i32);
+    let corner = |di: i32, dj: i32, dk: i32| {
+        let h = hash(i + di, j + dj, k + dk);
+        gradient(h, x - di as f32, y - dj as f32, z - dk as f32)
+    };
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+    let near = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+        v,
+    );
+    let far = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+        v,
+    );
+    lerp(near, far, w).clamp(-1.0, 1.0)
+}
+
+/// Simplex noise, in `[-1, 1]`, summing the gradients of the four corners
+/// of the tetrahedron around the point instead of a whole cube.
+pub fn simplex(p: Vertex) -> f32 {
+    const SKEW: f32 = 1.0 / 3.0;
+    const UNSKEW: f32 = 1.0 / 6.0;
+    let s = (p.x + p.y + p.z) * SKEW;
+    let (i, j, k) = (
+        (p.x + s).floor() as i32,
+        (p.y + s).floor() as i32,
+        (p.z + s).floor() as i32,
+    );
+    let t = (i + j + k) as f32 * UNSKEW;
+    let (x, y, z) = (p.x - i as f32 + t, p.y - j as f32 + t, p.z - k as f32 + t);
+    // the tetrahedron's corners, walking the axes by decreasing offsets
+    let (second, third) = if x >= y {
+        if y >= z {
+            ((1, 0, 0), (1, 1, 0))
+        } else if x >= z {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if y < z {
+        ((0, 0, 1), (0, 1, 1))
+    } else if x < z {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+    let sum: f32 = [(0, 0, 0), second, third, (1, 1, 1)]
+        .iter()
+        .map(|&(di, dj, dk)| {
+            let offset = (di + dj + dk) as f32 * UNSKEW;
+            let (cx, cy, cz) = (
+                x - di as f32 + offset,
+                y - dj as f32 + offset,
+                z - dk as f32 + offset,
+            );
+            let falloff = 0.6 - cx * cx - cy * cy - cz * cz;
+            if falloff <= 0.0 {
+                0.0
+            } else {
+                falloff.powi(4) * gradient(hash(i + di, j + dj, k + dk), cx, cy, cz)
+            }
+        })
+        .sum();
+    (32.0 * sum).clamp(-1.0, 1.0)
+}
+
+/// Distances to the closest and second closest feature points, one of them
+/// being randomly placed in each unit cell.
+pub fn worley(p: Vertex) -> (f32, f32) {
+    let (i, j, k) = (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+    let mut closest = (f32::INFINITY, f32::INFINITY);
+    for di in -1..=1 {
+        for dj in -1..=1 {
+            for dk in -1..=1 {
+                let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                let h = hash(ci, cj, ck);
+                let jitter = |shift: u32| ((h >> shift) & 0x3FF) as f32 / 1024.0;
+                let (x, y, z) = (
+                    ci as f32 + jitter(0) - p.x,
+                    cj as f32 + jitter(10) - p.y,
+                    ck as f32 + jitter(20) - p.z,
+                );
+                let distance = (x * x + y * y + z * z).sqrt();
+                if distance < closest.0 {
+                    closest = (distance, closest.0);
+                } else if distance < closest.1 {
+                    closest.1 = distance;
+                }
+            }
+        }
+    }
+    closest
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Noise {
+    Perlin,
+    Simplex,
+    /// Distance to the closest Worley feature point, making cells.
+    Worley,
+}
+
+impl Noise {
+    /// Noise value in `[-1, 1]`.
+    pub fn value(self, p: Vertex) -> f32 {
+        match self {
+            Self::Perlin => perlin(p),
+            Self::Simplex => simplex(p),
+            Self::Worley => (worley(p).0 * 2.0 - 1.0).min(1.0),
+        }
+    }
+}
+
+/// Octaves of noise, each `lacunarity` times finer and `gain` times weaker
+/// than the previous one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fractal {
+    pub noise: Noise,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal {
+            noise: Noise::Perlin,
+            octaves: 5,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fractal {
+    /// Fractional Brownian motion, the sum of the octaves, in `[-1, 1]`.
+    pub fn fbm(&self, p: Vertex) -> f32 {
+        self.sum(p, |n| n)
+    }
+
+    /// Sum of the absolute octaves, in `[0, 1]`, creased where the noise
+    /// changes sign.
+    pub fn turbulence(&self, p: Vertex) -> f32 {
+        self.sum(p, f32::abs)
+    }
+
+    /// Sum of `f` of the octaves, normalized by their amplitudes.
+    fn sum(&self, p: Vertex, f: impl Fn(f32) -> f32) -> f32 {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        for octave in 0..self.octaves.max(1) {
+            // shifted for the octaves not to line up at the origin
+            let shift = octave as f32 * 17.31;
+            let q = Vertex {
+                x: p.x * frequency + shift,
+                y: p.y * frequency + shift,
+                z: p.z * frequency + shift,
+            };
+            sum += amplitude * f(self.noise.value(q));
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        sum / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> impl Iterator<Item = Vertex> {
+        (0..500).map(|i| {
+            let t = i as f32 * 0.173;
+            Vertex {
+                x: t.sin() * 7.3 + t,
+                y: (t * 1.7).cos() * 3.1,
+                z: t * 0.37 - 20.0,
+            }
+        })
+    }
+
+    #[test]
+    fn noise_range() {
+        for noise in [Noise::Perlin, Noise::Simplex, Noise::Worley] {
+            let values: Vec<f32> = points().map(|p| noise.value(p)).collect();
+            assert!(values.iter().all(|v| (-1.0..=1.0).contains(v)));
+            // varying, and the same at the same points
+            let spread = values.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+            assert!(spread > 0.3, "{:?} {}", noise, spread);
+            let again: Vec<f32> = points().map(|p| noise.value(p)).collect();
+            assert_eq!(values, again);
+        }
+        let lattice = Vertex {
+            x: 3.0,
+            y: -2.0,
+            z: 7.0,
+        };
+        assert_eq!(perlin(lattice), 0.0);
+    }
+
+    #[test]
+    fn continuity() {
+        for p in points() {
+            let q = Vertex { x: p.x + 1e-3, ..p };
+            assert!((perlin(p) - perlin(q)).abs() < 0.01);
+            assert!((simplex(p) - simplex(q)).abs() < 0.02);
+            assert!((worley(p).0 - worley(q).0).abs() < 2e-3);
+        }
+    }
+
+    #[test]
+    fn fractals() {
+        let fractal = Fractal::default();
+        for p in points() {
+            assert!((-1.0..=1.0).contains(&fractal.fbm(p)));
+            assert!((0.0..=1.0).contains(&fractal.turbulence(p)));
+            let (f1, f2) = worley(p);
+            assert!(f1 <= f2);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::ops::Mul;
+
+use crate::math::vec3::Vector;
+use crate::math::Radian;
+
+/// Rotation as a unit quaternion `w + xi + yj + zk`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Quaternion {
+        Quaternion::identity()
+    }
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// Rotation of `angle` around `axis`, which does not need to be unit.
+    pub fn from_axis_angle(axis: Vector, angle: Radian) -> Quaternion {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle.0 / 2.0).sin_cos();
+        Quaternion {
+            w: cos,
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+        }
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let n = self.norm();
+        if n == 0.0 {
+            return Quaternion::identity();
+        }
+        Quaternion {
+            w: self.w / n,
+            x: self.x / n,
+            y: self.y / n,
+            z: self.z / n,
+        }
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    pub fn dot(&self, q: Quaternion) -> f32 {
+        self.w * q.w + self.x * q.x + self.y * q.y + self.z * q.z
+    }
+
+    pub fn rotate(&self, v: Vector) -> Vector {
+        let p = Quaternion {
+            w: 0.0,
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        };
+        let q = self.normalize();
+        let r = q * p * q.conjugate();
+        Vector {
+            x: r.x,
+            y: r.y,
+            z: r.z,
+        }
+    }
+
+    /// Unit axis and angle of the rotation, the axis being arbitrary for
+    /// the identity.
+    pub fn to_axis_angle(&self) -> (Vector, Radian) {
+        let q = self.normalize();
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin < 1e-6 {
+            let x = Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            };
+            return (x, Radian(0.0));
+        }
+        let axis = Vector {
+            x: q.x / sin,
+            y: q.y / sin,
+            z: q.z / sin,
+        };
+        (axis, Radian(2.0 * q.w.clamp(-1.0, 1.0).acos()))
+    }
+
+    /// Spherical interpolation from `self` at `t = 0` to `q` at `t = 1`,
+    /// along the shortest arc.
+    pub fn slerp(&self, q: Quaternion, t: f32) -> Quaternion {
+        let (a, mut b) = (self.normalize(), q.normalize());
+        let mut cos = a.dot(b);
+        if cos < 0.0 {
+            b = Quaternion {
+                w: -b.w,
+                x: -b.x,
+                y: -b.y,
+                z: -b.z,
+            };
+            cos = -cos;
+        }
+        // nearly parallel, falls back to a linear interpolation
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion {
+            w: a.w * wa + b.w * wb,
+            x: a.x * wa + b.x * wb,
+            y: a.y * wa + b.y * wb,
+            z: a.z * wa + b.z * wb,
+        }
+        .normalize()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    /// Hamilton product, `self * rhs` rotating by `rhs` first.
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        let (a, b) = (self, rhs);
+        Quaternion {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const Z: Vector = Vector {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+    const X: Vector = Vector {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    #[test]
+    fn rotate() {
+        let q = Quaternion::from_axis_angle(Z, Radian(FRAC_PI_2));
+        let y = q.rotate(X);
+        assert!((y.x).abs() < 1e-6 && (y.y - 1.0).abs() < 1e-6 && y.z.abs() < 1e-6);
+        let back = (q.conjugate() * q).rotate(X);
+        assert!((back - X).norm() < 1e-6);
+        // composition applies the right hand side first
+        let p = Quaternion::from_axis_angle(X, Radian(FRAC_PI_2));
+        let both = (p * q).rotate(X);
+        assert!((both - p.rotate(q.rotate(X))).norm() < 1e-6);
+        assert!((both.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let axis = Vector {
+            x: 0.0,
+            y: 0.6,
+            z: 0.8,
+        };
+        let (back, angle) = Quaternion::from_axis_angle(axis, Radian(2.5)).to_axis_angle();
+        assert!((back - axis).norm() < 1e-5);
+        assert!((angle.0 - 2.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_halfway() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(Z, Radian(FRAC_PI_2));
+        let half = a.slerp(b, 0.5);
+        let expected = Quaternion::from_axis_angle(Z, Radian(FRAC_PI_2 / 2.0));
+        assert!((half.dot(expected) - 1.0).abs() < 1e-6);
+        assert_eq!(a.slerp(b, 0.0), a);
+    }
+}
@@ -0,0 +1,142 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use crate::math::vec3::Vector;
+use crate::math::Radian;
+
+/// Small xorshift64* generator, deterministic for a given seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // the state must never be zero
+        Rng {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Builds two unit vectors forming an orthonormal basis with `normal`.
+pub fn orthonormal_basis(normal: Vector) -> (Vector, Vector) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    } else {
+        Vector {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+/// Cosine-weighted direction in the hemisphere around `normal`.
+pub fn cosine_hemisphere(normal: Vector, u1: f32, u2: f32) -> Vector {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    let z = (1.0 - u1).max(0.0).sqrt();
+    (tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * z).normalize()
+}
+
+/// Uniform point in the unit disc, by the concentric mapping of the square
+/// keeping nearby samples close.
+pub fn uniform_disc(u1: f32, u2: f32) -> (f32, f32) {
+    let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+    (r * phi.cos(), r * phi.sin())
+}
+
+/// Uniform point in the regular polygon of `sides` vertices on the unit
+/// circle, the first one at `angle`.
+pub fn uniform_polygon(sides: u32, angle: Radian, u1: f32, u2: f32) -> (f32, f32) {
+    let n = sides as f32;
+    // picks a triangle between the center and an edge, reusing u1 within it
+    let edge = (u1 * n).floor().min(n - 1.0);
+    let s = (u1 * n - edge).sqrt();
+    let (a, b) = (s * (1.0 - u2), s * u2);
+    let start = angle.0 + 2.0 * PI * edge / n;
+    let end = start + 2.0 * PI / n;
+    (
+        a * start.cos() + b * end.cos(),
+        a * start.sin() + b * end.sin(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_range() {
+        let mut rng = Rng::new(0);
+        for _ in 0..10_000 {
+            let v = rng.next_f32();
+            assert!((0.0..1.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn rng_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn hemisphere_side() {
+        let normal = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let d = cosine_hemisphere(normal, rng.next_f32(), rng.next_f32());
+            assert!(d.dot(normal) >= 0.0);
+            assert!((d.norm() - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn disc_and_polygon() {
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            let (x, y) = uniform_disc(rng.next_f32(), rng.next_f32());
+            assert!(x * x + y * y <= 1.0 + 1e-5);
+            // a square with its corners on the axes
+            let (x, y) = uniform_polygon(4, Radian(0.0), rng.next_f32(), rng.next_f32());
+            assert!(x.abs() + y.abs() <= 1.0 + 1e-5);
+        }
+        assert_eq!(uniform_disc(0.5, 0.5), (0.0, 0.0));
+    }
+}
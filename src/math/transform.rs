@@ -0,0 +1,120 @@
+use std::ops::Mul;
+
+use crate::math::matrix::Matrix4;
+use crate::math::quaternion::Quaternion;
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::Lerp;
+
+/// Invertible affine transform, keeping its inverse around to bring rays
+/// and points back to object space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    /// `None` when `matrix` is singular.
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        matrix
+            .inverse()
+            .map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Vertex) -> Vertex {
+        self.matrix * p
+    }
+
+    pub fn vector(&self, v: Vector) -> Vector {
+        self.matrix * v
+    }
+
+    /// Transformed surface normal, using the inverse transpose so that it
+    /// stays perpendicular to the surface under non-uniform scaling.
+    pub fn normal(&self, n: Vector) -> Vector {
+        (self.inverse.transpose() * n).normalize()
+    }
+
+    /// Transform going from `self` at `t = 0` to `other` at `t = 1`, the
+    /// translations and stretches being interpolated linearly and the
+    /// rotations along the shortest arc, so that turning objects keep their
+    /// size. `None` when the interpolated stretch is singular.
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Option<Transform> {
+        let (from_offset, from_rotation, from_stretch) = self.decompose();
+        let (to_offset, to_rotation, to_stretch) = other.decompose();
+        Transform::new(
+            Matrix4::translation(from_offset.lerp(to_offset, t))
+                * Matrix4::from(from_rotation.slerp(to_rotation, t))
+                * from_stretch.lerp(&to_stretch, t),
+        )
+    }
+
+    /// Translation, rotation and stretch of the transform, which is
+    /// `translation * rotation * stretch`, the stretch being upper triangular
+    /// with the scale factors on its diagonal and any shear above it.
+    fn decompose(&self) -> (Vector, Quaternion, Matrix4) {
+        let m = self.matrix.m;
+        let column = |j: usize| Vector {
+            x: m[0][j],
+            y: m[1][j],
+            z: m[2][j],
+        };
+        // QR decomposition of the linear part by Gram-Schmidt
+        let (a0, a1, a2) = (column(0), column(1), column(2));
+        let s00 = a0.norm();
+        let q0 = a0 / s00;
+        let s01 = q0.dot(a1);
+        let v1 = a1 - q0 * s01;
+        let s11 = v1.norm();
+        let q1 = v1 / s11;
+        let (s02, s12) = (q0.dot(a2), q1.dot(a2));
+        let v2 = a2 - q0 * s02 - q1 * s12;
+        let mut s22 = v2.norm();
+        let mut q2 = v2 / s22;
+        // a mirroring is left to the stretch
+        if q0.cross(q1).dot(q2) < 0.0 {
+            q2 = -q2;
+            s22 = -s22;
+        }
+        let rotation = Matrix4 {
+            m: [
+                [q0.x, q1.x, q2.x, 0.0],
+                [q0.y, q1.y, q2.y, 0.0],
+                [q0.z, q1.z, q2.z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        let stretch = Matrix4 {
+            m: [
+                [s00, s01, s02, 0.0],
+                [0.0, s11, s12, 0.0],
+                [0.0, 0.0, s22, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        (column(3), rotation.into(), stretch)
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    /// `self * rhs` applies `rhs` first.
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::num::FpCategory;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
+use crate::math::Radian;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vertex {
     pub x: f32,
@@ -8,7 +10,7 @@ pub struct Vertex {
     pub z: f32,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vector {
     pub x: f32,
     pub y: f32,
@@ -54,6 +56,24 @@ impl Vector {
     pub fn dot(&self, v: Vector) -> f32 {
         self.x * v.x + self.y * v.y + self.z * v.z
     }
+
+    /// Rotation around the unit vector `axis` (Rodrigues' formula).
+    pub fn rotate(&self, axis: Vector, angle: Radian) -> Vector {
+        let (sin, cos) = angle.0.sin_cos();
+        *self * cos + axis.cross(*self) * sin + axis * (axis.dot(*self) * (1.0 - cos))
+    }
+}
+
+impl Add<Vector> for Vertex {
+    type Output = Vertex;
+
+    fn add(self, rhs: Vector) -> Vertex {
+        Vertex {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
 }
 
 impl Add for Vector {
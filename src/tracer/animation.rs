@@ -0,0 +1,446 @@
+//! Keyframed properties, posing the camera, the scene graph nodes and the
+//! materials at any time of an animation.
+
+use std::collections::HashMap;
+
+use crate::graphics::Color;
+use crate::math::matrix::Matrix4;
+use crate::math::quaternion::Quaternion;
+use crate::math::transform::Transform;
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::{Degree, Lerp, Radian};
+use crate::tracer::camera::{Camera, Lens, Stereo};
+use crate::tracer::graph::{Content, SceneGraph};
+use crate::tracer::lights::Light;
+use crate::tracer::objects::Material;
+use crate::tracer::projection::{Orthographic, Projection};
+
+impl Lerp for Color {
+    fn lerp(self, other: Color, t: f32) -> Color {
+        Color {
+            red: self.red.lerp(other.red, t),
+            green: self.green.lerp(other.green, t),
+            blue: self.blue.lerp(other.blue, t),
+        }
+    }
+}
+
+impl Lerp for Camera {
+    fn lerp(self, other: Camera, t: f32) -> Camera {
+        Camera {
+            position: self.position.lerp(other.position, t),
+            direction: self.direction.lerp(other.direction, t).normalize(),
+            target_distance: self.target_distance.lerp(other.target_distance, t),
+            up: self.up.lerp(other.up, t).normalize(),
+            fov: Degree(self.fov.0.lerp(other.fov.0, t)),
+            projection: match (self.projection, other.projection) {
+                (Projection::Orthographic(a), Projection::Orthographic(b)) => Orthographic {
+                    height: a.height.lerp(b.height, t),
+                }
+                .into(),
+                (projection, _) => projection,
+            },
+            lens: Lens {
+                aperture: self.lens.aperture.lerp(other.lens.aperture, t),
+                focus_distance: self.lens.focus_distance.lerp(other.lens.focus_distance, t),
+                ..self.lens
+            },
+            stereo: Stereo {
+                interocular: self.stereo.interocular.lerp(other.stereo.interocular, t),
+                convergence: self.stereo.convergence.lerp(other.stereo.convergence, t),
+            },
+            eye: self.eye,
+        }
+    }
+}
+
+/// Rotation of `angle` around `axis`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    pub axis: Vector,
+    pub angle: Radian,
+}
+
+impl Rotation {
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::from_axis_angle(self.axis, self.angle)
+    }
+}
+
+impl Lerp for Rotation {
+    /// Turns around a shared axis by the angle difference, so that a full
+    /// turn can be keyed, and along the shortest arc otherwise.
+    fn lerp(self, other: Rotation, t: f32) -> Rotation {
+        let (a, b) = (self.axis.normalize(), other.axis.normalize());
+        if a.dot(b) > 1.0 - 1e-6 {
+            return Rotation {
+                axis: a,
+                angle: Radian(self.angle.0.lerp(other.angle.0, t)),
+            };
+        }
+        let from = Quaternion::from_axis_angle(a, self.angle);
+        let to = Quaternion::from_axis_angle(b, other.angle);
+        let (axis, angle) = from.slerp(to, t).to_axis_angle();
+        Rotation { axis, angle }
+    }
+}
+
+/// How a value moves from a keyframe to the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Cubic Bezier timing curve from `(0, 0)` to `(1, 1)` through the
+    /// control points `(x1, y1)` and `(x2, y2)`, the `x` ones being in
+    /// `[0, 1]`.
+    Bezier([f32; 4]),
+}
+
+impl Interpolation {
+    /// Slow in and slow out.
+    pub const EASE: Interpolation = Interpolation::Bezier([0.42, 0.0, 0.58, 1.0]);
+
+    /// Progress between the two keyframes at the fraction `t` of the time
+    /// between them.
+    pub fn ease(&self, t: f32) -> f32 {
+        let [x1, y1, x2, y2] = match self {
+            Self::Linear => return t,
+            Self::Bezier(points) => *points,
+        };
+        let bezier = |p1: f32, p2: f32, s: f32| {
+            let r = 1.0 - s;
+            3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+        };
+        // x grows with s when x1 and x2 are in [0, 1]
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..32 {
+            let s = (low + high) / 2.0;
+            if bezier(x1, x2, s) < t {
+                low = s;
+            } else {
+                high = s;
+            }
+        }
+        bezier(y1, y2, (low + high) / 2.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe<T> {
+    /// Seconds from the start of the animation.
+    pub time: f32,
+    pub value: T,
+    /// Toward the next keyframe.
+    pub interpolation: Interpolation,
+}
+
+/// Keyframes of a property, holding the first and last values before and
+/// after them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Lerp> Track<T> {
+    /// `None` without any keyframe.
+    pub fn new(mut keys: Vec<Keyframe<T>>) -> Option<Track<T>> {
+        if keys.is_empty() {
+            return None;
+        }
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Some(Track { keys })
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    /// Time of the last keyframe.
+    pub fn end(&self) -> f32 {
+        self.keys[self.keys.len() - 1].time
+    }
+
+    pub fn sample(&self, time: f32) -> T {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        let key = &self.keys[next - 1];
+        match self.keys.get(next) {
+            Some(to) => {
+                let t = (time - key.time) / (to.time - key.time);
+                key.value.lerp(to.value, key.interpolation.ease(t))
+            }
+            None => key.value,
+        }
+    }
+}
+
+/// Property animated by a track.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Channel {
+    /// Node transforms, applied in the node space on top of its own one.
+    Translate(Track<Vector>),
+    Rotate(Track<Rotation>),
+    Scale(Track<Vector>),
+    /// Light and material color.
+    Color(Track<Color>),
+    /// Light intensity.
+    Intensity(Track<f32>),
+    /// Material albedo.
+    Albedo(Track<f32>),
+    /// Camera position, target and vertical field of view in degrees.
+    Position(Track<Vertex>),
+    LookAt(Track<Vertex>),
+    Fov(Track<f32>),
+    /// Lens aperture radius and focus distance, for pulling focus.
+    Aperture(Track<f32>),
+    Focus(Track<f32>),
+}
+
+impl Channel {
+    pub fn end(&self) -> f32 {
+        match self {
+            Self::Translate(t) | Self::Scale(t) => t.end(),
+            Self::Rotate(t) => t.end(),
+            Self::Color(t) => t.end(),
+            Self::Intensity(t) | Self::Albedo(t) | Self::Fov(t) => t.end(),
+            Self::Aperture(t) | Self::Focus(t) => t.end(),
+            Self::Position(t) | Self::LookAt(t) => t.end(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Camera,
+    /// Scene graph path.
+    Node(String),
+    /// Material name, applied to the objects built with it.
+    Material(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animated {
+    pub target: Target,
+    pub channel: Channel,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub fps: f32,
+    /// Fraction of the frame interval the shutter stays open, blurring the
+    /// objects moving meanwhile.
+    pub shutter: f32,
+    /// First and last frames, both rendered.
+    pub frames: (usize, usize),
+    pub tracks: Vec<Animated>,
+    /// Point the camera keeps looking at as it moves, unless a look_at
+    /// track moves it too.
+    pub look_at: Option<Vertex>,
+}
+
+impl Default for Animation {
+    fn default() -> Animation {
+        Animation {
+            fps: 24.0,
+            shutter: 0.0,
+            frames: (1, 1),
+            tracks: Vec::new(),
+            look_at: None,
+        }
+    }
+}
+
+/// Animated parts of a node transform.
+#[derive(Default)]
+struct Pose {
+    translate: Option<Vector>,
+    rotate: Option<Rotation>,
+    scale: Option<Vector>,
+}
+
+impl Animation {
+    /// Time of the last keyframe of all tracks.
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(|t| t.channel.end())
+            .fold(0.0, f32::max)
+    }
+
+    /// Time of the first frame.
+    pub fn start(&self) -> f32 {
+        self.time(self.frames.0)
+    }
+
+    /// Time of `frame`, frame 1 being at time 0.
+    pub fn time(&self, frame: usize) -> f32 {
+        (frame as f32 - 1.0) / self.fps
+    }
+
+    /// Sets the animated properties at `time`, starting from their values
+    /// in `camera`, `graph` and `materials`.
+    pub fn apply(
+        &self,
+        time: f32,
+        camera: &mut Camera,
+        graph: &mut SceneGraph,
+        materials: &HashMap<String, Material>,
+    ) {
+        let mut poses: HashMap<&str, Pose> = HashMap::new();
+        let mut animated: HashMap<&str, Material> = HashMap::new();
+        let mut target = self.look_at;
+        for track in &self.tracks {
+            match (&track.target, &track.channel) {
+                (Target::Camera, Channel::Position(t)) => camera.position = t.sample(time),
+                (Target::Camera, Channel::LookAt(t)) => target = Some(t.sample(time)),
+                (Target::Camera, Channel::Fov(t)) => camera.fov = Degree(t.sample(time)),
+                (Target::Camera, Channel::Aperture(t)) => camera.lens.aperture = t.sample(time),
+                (Target::Camera, Channel::Focus(t)) => camera.lens.focus_distance = t.sample(time),
+                (Target::Node(path), Channel::Translate(t)) => {
+                    poses.entry(path).or_default().translate = Some(t.sample(time))
+                }
+                (Target::Node(path), Channel::Rotate(t)) => {
+                    poses.entry(path).or_default().rotate = Some(t.sample(time))
+                }
+                (Target::Node(path), Channel::Scale(t)) => {
+                    poses.entry(path).or_default().scale = Some(t.sample(time))
+                }
+                (Target::Node(path), Channel::Color(t)) => {
+                    if let Some(Content::Light(light)) =
+                        graph.find_mut(path).map(|n| &mut n.content)
+                    {
+                        match light {
+                            Light::Directional(l) => l.base_color = t.sample(time),
+                            Light::Spherical(l) => l.base_color = t.sample(time),
+                        }
+                    }
+                }
+                (Target::Node(path), Channel::Intensity(t)) => {
+                    if let Some(Content::Light(light)) =
+                        graph.find_mut(path).map(|n| &mut n.content)
+                    {
+                        match light {
+                            Light::Directional(l) => l.base_intensity = t.sample(time),
+                            Light::Spherical(l) => l.base_intensity = t.sample(time),
+                        }
+                    }
+                }
+                (Target::Material(name), channel) => {
+                    let material = match animated.get(name.as_str()).or_else(|| materials.get(name))
+                    {
+                        Some(m) => m.clone(),
+                        None => continue,
+                    };
+                    let material = match channel {
+                        Channel::Color(t) => Material {
+                            color: t.sample(time),
+                            ..material
+                        },
+                        Channel::Albedo(t) => Material {
+                            albedo: t.sample(time),
+                            ..material
+                        },
+                        _ => material,
+                    };
+                    animated.insert(name, material);
+                }
+                // checked when loading the scene
+                _ => {}
+            }
+        }
+
+        if let Some(target) = target {
+            let direction = Vector::from_vertices(camera.position, target);
+            camera.direction = direction.normalize();
+            camera.target_distance = direction.norm();
+        }
+        for (path, pose) in poses {
+            if let Some(node) = graph.find_mut(path) {
+                let matrix = pose
+                    .translate
+                    .map_or_else(Matrix4::identity, Matrix4::translation)
+                    * pose.rotate.map_or_else(Matrix4::identity, |r| r.matrix())
+                    * pose.scale.map_or_else(Matrix4::identity, Matrix4::scaling);
+                match Transform::new(matrix) {
+                    Some(transform) => node.transform = node.transform * transform,
+                    // scaled down to nothing
+                    None => node.visible = false,
+                }
+            }
+        }
+        if !animated.is_empty() {
+            let replace = |material: &Material| {
+                let name = material.name.as_deref()?;
+                animated.get(name).cloned()
+            };
+            graph.for_each_mut(|node| {
+                if let Content::Object(object) = &mut node.content {
+                    object.replace_materials(&replace);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key<T>(time: f32, value: T) -> Keyframe<T> {
+        Keyframe {
+            time,
+            value,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let track = Track::new(vec![key(2.0, 10.0), key(0.0, 0.0), key(3.0, 0.0)]).unwrap();
+        assert_eq!(track.sample(-1.0), 0.0);
+        assert_eq!(track.sample(1.0), 5.0);
+        assert_eq!(track.sample(2.5), 5.0);
+        assert_eq!(track.sample(4.0), 0.0);
+        assert!(Track::<f32>::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn ease() {
+        let ease = Interpolation::EASE;
+        assert!(ease.ease(0.0).abs() < 1e-5);
+        assert!((ease.ease(1.0) - 1.0).abs() < 1e-5);
+        assert!((ease.ease(0.5) - 0.5).abs() < 1e-4);
+        // slow start, then catching up
+        assert!(ease.ease(0.1) < 0.1);
+        assert!(ease.ease(0.9) > 0.9);
+    }
+
+    #[test]
+    fn full_turn() {
+        let y = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let track = Track::new(vec![
+            key(
+                0.0,
+                Rotation {
+                    axis: y,
+                    angle: Degree(0.0).into(),
+                },
+            ),
+            key(
+                1.0,
+                Rotation {
+                    axis: y,
+                    angle: Degree(360.0).into(),
+                },
+            ),
+        ])
+        .unwrap();
+        let half = track.sample(0.5);
+        assert!((Degree::from(half.angle).0 - 180.0).abs() < 1e-3);
+    }
+}
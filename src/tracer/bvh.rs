@@ -0,0 +1,349 @@
+//! Bounding volume hierarchy over the objects of a scene or a geometry.
+
+use crate::math::transform::Transform;
+use crate::math::vec3::{Vector, Vertex};
+use crate::tracer::Ray;
+
+/// Most objects kept in a leaf.
+const LEAF_SIZE: usize = 4;
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vertex,
+    pub max: Vertex,
+}
+
+impl Aabb {
+    /// Box containing both `a` and `b`.
+    pub fn new(a: Vertex, b: Vertex) -> Aabb {
+        Aabb {
+            min: Vertex {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+                z: a.z.min(b.z),
+            },
+            max: Vertex {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+                z: a.z.max(b.z),
+            },
+        }
+    }
+
+    pub fn union(&self, other: Aabb) -> Aabb {
+        Aabb {
+            min: Aabb::new(self.min, other.min).min,
+            max: Aabb::new(self.max, other.max).max,
+        }
+    }
+
+    pub fn include(&self, p: Vertex) -> Aabb {
+        self.union(Aabb { min: p, max: p })
+    }
+
+    pub fn centroid(&self) -> Vertex {
+        Vertex {
+            x: (self.min.x + self.max.x) / 2.0,
+            y: (self.min.y + self.max.y) / 2.0,
+            z: (self.min.z + self.max.z) / 2.0,
+        }
+    }
+
+    /// Box containing the 8 transformed corners.
+    pub fn transformed(&self, transform: &Transform) -> Aabb {
+        let (a, b) = (self.min, self.max);
+        let corner = |i: usize| Vertex {
+            x: if i & 1 == 0 { a.x } else { b.x },
+            y: if i & 2 == 0 { a.y } else { b.y },
+            z: if i & 4 == 0 { a.z } else { b.z },
+        };
+        let first = transform.point(corner(0));
+        (1..8).fold(Aabb::new(first, first), |bounds, i| {
+            bounds.include(transform.point(corner(i)))
+        })
+    }
+
+    /// Distance at which `ray` enters the box, if it does before `max`.
+    pub fn hit(&self, ray: &Ray, max: f32) -> Option<f32> {
+        let slab = |origin: f32, direction: f32, min: f32, max: f32| {
+            let inverse = 1.0 / direction;
+            let (t0, t1) = ((min - origin) * inverse, (max - origin) * inverse);
+            if inverse < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            }
+        };
+        let (o, d) = (ray.origin, ray.direction);
+        let (x0, x1) = slab(o.x, d.x, self.min.x, self.max.x);
+        let (y0, y1) = slab(o.y, d.y, self.min.y, self.max.y);
+        let (z0, z1) = slab(o.z, d.z, self.min.z, self.max.z);
+        // NaN from a zero direction on a slab border is ignored by min/max
+        let enter = x0.max(y0).max(z0).max(0.0);
+        let exit = x1.min(y1).min(z1).min(max);
+        if enter <= exit {
+            Some(enter)
+        } else {
+            None
+        }
+    }
+
+    fn axis(p: Vertex, axis: usize) -> f32 {
+        match axis {
+            0 => p.x,
+            1 => p.y,
+            _ => p.z,
+        }
+    }
+
+    /// Axis along which the box is the longest.
+    fn longest_axis(&self) -> usize {
+        let size = Vector::from_vertices(self.min, self.max);
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        end: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => *bounds,
+        }
+    }
+}
+
+/// Hierarchy over objects identified by their index, unbounded ones such
+/// as planes being tested on every ray.
+#[derive(Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Object indices, each leaf owning a range.
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy from the bounds of each object.
+    pub fn build(bounds: &[Option<Aabb>]) -> Bvh {
+        let mut bvh = Bvh::default();
+        let mut bounded = Vec::new();
+        for (index, b) in bounds.iter().enumerate() {
+            match b {
+                Some(b) => bounded.push((index, *b)),
+                None => bvh.unbounded.push(index),
+            }
+        }
+        if !bounded.is_empty() {
+            bvh.split(&mut bounded);
+        }
+        bvh
+    }
+
+    /// Adds the node holding `objects`, returning its index.
+    fn split(&mut self, objects: &mut [(usize, Aabb)]) -> usize {
+        let bounds = objects[1..]
+            .iter()
+            .fold(objects[0].1, |b, (_, o)| b.union(*o));
+        let id = self.nodes.len();
+        if objects.len() <= LEAF_SIZE {
+            let start = self.indices.len();
+            self.indices.extend(objects.iter().map(|(i, _)| *i));
+            self.nodes.push(Node::Leaf {
+                bounds,
+                start,
+                end: self.indices.len(),
+            });
+            return id;
+        }
+        let centroids = objects[1..].iter().fold(
+            Aabb::new(objects[0].1.centroid(), objects[0].1.centroid()),
+            |b, (_, o)| b.include(o.centroid()),
+        );
+        let axis = centroids.longest_axis();
+        objects.sort_by(|(_, a), (_, b)| {
+            let (a, b) = (
+                Aabb::axis(a.centroid(), axis),
+                Aabb::axis(b.centroid(), axis),
+            );
+            a.partial_cmp(&b).unwrap()
+        });
+        // children are filled in once built
+        self.nodes.push(Node::Leaf {
+            bounds,
+            start: 0,
+            end: 0,
+        });
+        let (left, right) = objects.split_at_mut(objects.len() / 2);
+        let left = self.split(left);
+        let right = self.split(right);
+        self.nodes[id] = Node::Inner {
+            bounds,
+            left,
+            right,
+        };
+        id
+    }
+
+    /// Bounds of all the objects, `None` if one of them is unbounded.
+    pub fn bounds(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.nodes.first().map(Node::bounds)
+    }
+
+    /// Closest hit along `ray`, `hit` intersecting the object of the given
+    /// index and returning the hit distance along with any data.
+    pub fn closest<T>(
+        &self,
+        ray: &Ray,
+        mut hit: impl FnMut(usize) -> Option<(f32, T)>,
+    ) -> Option<(f32, T)> {
+        let mut best: Option<(f32, T)> = None;
+        let mut test = |index: usize, best: &mut Option<(f32, T)>| {
+            if let Some((distance, data)) = hit(index) {
+                if best.as_ref().is_none_or(|(d, _)| distance < *d) {
+                    *best = Some((distance, data));
+                }
+            }
+        };
+        for &index in &self.unbounded {
+            test(index, &mut best);
+        }
+        if self.nodes.is_empty() {
+            return best;
+        }
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            let max = best.as_ref().map_or(f32::INFINITY, |(d, _)| *d);
+            match &self.nodes[id] {
+                Node::Leaf { bounds, start, end } => {
+                    if bounds.hit(ray, max).is_some() {
+                        for &index in &self.indices[*start..*end] {
+                            test(index, &mut best);
+                        }
+                    }
+                }
+                Node::Inner {
+                    bounds,
+                    left,
+                    right,
+                } => {
+                    if bounds.hit(ray, max).is_none() {
+                        continue;
+                    }
+                    let near = |child: usize| self.nodes[child].bounds().hit(ray, max);
+                    match (near(*left), near(*right)) {
+                        (Some(l), Some(r)) => {
+                            // the nearest child is popped first
+                            if l < r {
+                                stack.extend([*right, *left]);
+                            } else {
+                                stack.extend([*left, *right]);
+                            }
+                        }
+                        (Some(_), None) => stack.push(*left),
+                        (None, Some(_)) => stack.push(*right),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::RayKind;
+
+    fn cube(x: f32) -> Option<Aabb> {
+        Some(Aabb::new(
+            Vertex {
+                x: x - 0.5,
+                y: -0.5,
+                z: -0.5,
+            },
+            Vertex {
+                x: x + 0.5,
+                y: 0.5,
+                z: 0.5,
+            },
+        ))
+    }
+
+    #[test]
+    fn closest_hit() {
+        // a row of cubes along x and an unbounded object
+        let mut bounds: Vec<_> = (0..50).map(|i| cube(i as f32 * 2.0)).collect();
+        bounds.push(None);
+        let bvh = Bvh::build(&bounds);
+        assert_eq!(bvh.bounds(), None);
+
+        let ray = Ray {
+            kind: RayKind::Primary,
+            origin: Vertex {
+                x: 200.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: -1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            time: 0.0,
+            differentials: None,
+        };
+        let mut tested = 0;
+        let hit = bvh.closest(&ray, |index| {
+            tested += 1;
+            match bounds[index] {
+                Some(b) => b.hit(&ray, f32::INFINITY).map(|d| (d, index)),
+                // far behind everything
+                None => Some((1000.0, index)),
+            }
+        });
+        assert_eq!(hit, Some((101.5, 49)));
+        assert!(tested < 20, "{} objects tested", tested);
+    }
+
+    #[test]
+    fn transformed_bounds() {
+        let transform = Transform::new(crate::math::matrix::Matrix4::translation(Vector {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        }))
+        .unwrap();
+        let b = cube(0.0).unwrap().transformed(&transform);
+        assert_eq!(
+            b.centroid(),
+            Vertex {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0
+            }
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use crate::math::sampling::{uniform_disc, uniform_polygon};
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::{Degree, Radian};
+use crate::tracer::projection::Projection;
+use crate::tracer::{Ray, RayKind};
+
+/// Height of a 35mm full frame sensor, in meters.
+const SENSOR_HEIGHT: f32 = 0.024;
+
+/// Thin lens in front of the camera, a zero aperture making a pinhole
+/// camera where everything is in focus.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lens {
+    /// Radius of the aperture, in scene units.
+    pub aperture: f32,
+    /// Distance along the view direction of the plane in focus.
+    pub focus_distance: f32,
+    /// Diaphragm blades shaping the aperture as a regular polygon, the
+    /// aperture being a disc below 3.
+    pub blades: u32,
+    /// Rotation of the polygonal aperture.
+    pub rotation: Degree,
+}
+
+impl Default for Lens {
+    fn default() -> Lens {
+        Lens {
+            aperture: 0.0,
+            focus_distance: 1.0,
+            blades: 0,
+            rotation: Degree(0.0),
+        }
+    }
+}
+
+impl Lens {
+    /// Aperture radius of a lens at `f_stop` giving a vertical field of view
+    /// of `fov` on a full frame sensor, the scene being in meters.
+    pub fn aperture_of(f_stop: f32, fov: Degree) -> f32 {
+        let Radian(fov) = fov.into();
+        let focal_length = SENSOR_HEIGHT / 2.0 / (fov / 2.0).tan();
+        focal_length / (2.0 * f_stop)
+    }
+
+    /// Uniform point of the aperture in the lens plane, from `u1` and `u2`
+    /// in `[0, 1)`.
+    pub fn sample(&self, u1: f32, u2: f32) -> (f32, f32) {
+        let (x, y) = if self.blades >= 3 {
+            uniform_polygon(self.blades, self.rotation.into(), u1, u2)
+        } else {
+            uniform_disc(u1, u2)
+        };
+        (x * self.aperture, y * self.aperture)
+    }
+}
+
+/// Pair of eyes `interocular` apart, looking at the same point at the
+/// `convergence` distance, which appears at the depth of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub interocular: f32,
+    pub convergence: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Stereo {
+        Stereo {
+            interocular: 0.0,
+            convergence: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vertex,
+    pub direction: Vector,
+    /// Distance along `direction` of the point looked at, around which the
+    /// preview orbits.
+    pub target_distance: f32,
+    pub up: Vector,
+    /// Vertical field of view of a perspective projection, or across the
+    /// image circle of a fisheye.
+    pub fov: Degree,
+    pub projection: Projection,
+    pub lens: Lens,
+    pub stereo: Stereo,
+    /// Eye of the stereo pair seeing through the camera, both eyes being
+    /// at its position when `None`.
+    pub eye: Option<Eye>,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            position: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            target_distance: 1.0,
+            up: Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            fov: Degree(90.0),
+            projection: Projection::default(),
+            lens: Lens::default(),
+            stereo: Stereo::default(),
+            eye: None,
+        }
+    }
+}
+
+impl Camera {
+    /// Camera at `position` oriented toward `target`, focusing and
+    /// converging on it.
+    pub fn look_at(position: Vertex, target: Vertex, up: Vector, fov: Degree) -> Camera {
+        let direction = Vector::from_vertices(position, target);
+        let distance = direction.norm();
+        Camera {
+            position,
+            direction: direction.normalize(),
+            target_distance: distance,
+            up,
+            fov,
+            projection: Projection::default(),
+            lens: Lens {
+                focus_distance: distance,
+                ..Lens::default()
+            },
+            stereo: Stereo {
+                convergence: distance,
+                ..Stereo::default()
+            },
+            eye: None,
+        }
+    }
+
+    /// Right, up and backward unit vectors of the camera frame.
+    pub fn basis(&self) -> (Vector, Vector, Vector) {
+        let w = -self.direction.normalize();
+        let u = self.up.cross(w).normalize();
+        let v = w.cross(u);
+        (u, v, w)
+    }
+
+    /// Primary ray through the image position `(x, y)` of an image `aspect`
+    /// times wider than high, both coordinates going from -1 to 1, leaving
+    /// the center of the lens. `None` outside of the projected image.
+    pub fn ray(&self, x: f32, y: f32, aspect: f32) -> Option<Ray> {
+        let (offset, direction) = self.project(x, y, aspect)?;
+        Some(self.leaving(offset, direction))
+    }
+
+    /// Primary ray through `(x, y)` leaving the point of the aperture
+    /// sampled from `u1` and `u2`, converging with the rays of the other
+    /// points on the focus plane, or on the focus sphere of a fisheye.
+    pub fn lens_ray(&self, x: f32, y: f32, aspect: f32, u1: f32, u2: f32) -> Option<Ray> {
+        let (offset, direction) = self.project(x, y, aspect)?;
+        if self.lens.aperture <= 0.0 || !self.projection.has_lens() {
+            return Some(self.leaving(offset, direction));
+        }
+        let distance = match self.projection {
+            Projection::Fisheye(_) => self.lens.focus_distance / direction.norm(),
+            _ => self.lens.focus_distance / -direction.z,
+        };
+        let focus = offset + direction * distance;
+        let (x, y) = self.lens.sample(u1, u2);
+        let origin = offset + Vector { x, y, z: 0.0 };
+        Some(self.leaving(origin, focus - origin))
+    }
+
+    /// Projected ray leaving the eye, in the camera frame.
+    ///
+    /// Eyes are moved sideways and turned toward the point at the
+    /// convergence distance, panoramas placing them on a circle around the
+    /// camera for each direction, as in omni-directional stereo, so that
+    /// looking anywhere around gives a stereo pair.
+    fn project(&self, x: f32, y: f32, aspect: f32) -> Option<(Vector, Vector)> {
+        let (offset, direction) = self.projection.ray(x, y, aspect, self.fov.into())?;
+        let side = match self.eye {
+            Some(Eye::Left) => -0.5,
+            Some(Eye::Right) => 0.5,
+            None => return Some((offset, direction)),
+        };
+        let shift = self.stereo.interocular * side;
+        let (eye, distance) = if self.projection.has_lens() {
+            let eye = Vector {
+                x: shift,
+                y: 0.0,
+                z: 0.0,
+            };
+            let distance = match self.projection {
+                Projection::Fisheye(_) => self.stereo.convergence / direction.norm(),
+                _ => self.stereo.convergence / -direction.z,
+            };
+            (eye, distance)
+        } else {
+            // to the right of the direction, merging toward the poles
+            let d = direction.normalize();
+            let eye = Vector {
+                x: -d.z * shift,
+                y: 0.0,
+                z: d.x * shift,
+            };
+            (eye, self.stereo.convergence / direction.norm())
+        };
+        let target = offset + direction * distance;
+        Some((offset + eye, target - (offset + eye)))
+    }
+
+    /// Ray leaving the camera from `offset` toward `direction`, both in the
+    /// camera frame.
+    fn leaving(&self, offset: Vector, direction: Vector) -> Ray {
+        let (u, v, w) = self.basis();
+        let frame = |d: Vector| u * d.x + v * d.y + w * d.z;
+        Ray {
+            kind: RayKind::Primary,
+            origin: self.position + frame(offset),
+            direction: frame(direction),
+            time: 0.0,
+            differentials: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::projection::Equirectangular;
+
+    #[test]
+    fn thin_lens() {
+        let camera = Camera {
+            lens: Lens {
+                aperture: 0.5,
+                focus_distance: 4.0,
+                blades: 6,
+                ..Lens::default()
+            },
+            ..Camera::default()
+        };
+        let at = |ray: &Ray, z: f32| {
+            let t = (z - ray.origin.z) / ray.direction.z;
+            (
+                ray.origin.x + ray.direction.x * t,
+                ray.origin.y + ray.direction.y * t,
+            )
+        };
+        let center = camera.ray(0.3, -0.2, 1.0).unwrap();
+        let (x, y) = at(&center, -4.0);
+        for (u1, u2) in [(0.1, 0.9), (0.6, 0.3), (0.99, 0.01)] {
+            let ray = camera.lens_ray(0.3, -0.2, 1.0, u1, u2).unwrap();
+            assert!(ray.origin != center.origin);
+            // sharp on the focus plane, blurred in front of it
+            let (fx, fy) = at(&ray, -4.0);
+            assert!((fx - x).abs() < 1e-5 && (fy - y).abs() < 1e-5);
+            let (nx, _) = at(&ray, -1.0);
+            assert!((nx - at(&center, -1.0).0).abs() > 1e-3);
+        }
+        // a 50mm lens at f/2 on a full frame sensor
+        let fov = Degree(2.0 * (0.012f32 / 0.05).atan().to_degrees());
+        assert!((Lens::aperture_of(2.0, fov) - 0.0125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stereo() {
+        let camera = Camera {
+            stereo: Stereo {
+                interocular: 0.1,
+                convergence: 2.0,
+            },
+            ..Camera::default()
+        };
+        let eye = |eye, projection: Projection, x| {
+            let camera = Camera {
+                eye: Some(eye),
+                projection,
+                ..camera
+            };
+            camera.ray(x, 0.0, 2.0).unwrap()
+        };
+        let left = eye(Eye::Left, Projection::default(), 0.0);
+        let right = eye(Eye::Right, Projection::default(), 0.0);
+        assert!((left.origin.x + 0.05).abs() < 1e-6 && (right.origin.x - 0.05).abs() < 1e-6);
+        // converging at the screen depth
+        let t = 2.0 / -left.direction.z;
+        assert!((left.origin.x + left.direction.x * t).abs() < 1e-6);
+
+        // looking right from a panorama, the right eye is behind
+        let panorama = Equirectangular.into();
+        let right = eye(Eye::Right, panorama, 0.5);
+        assert!((right.origin.z - 0.05).abs() < 1e-6 && right.origin.x.abs() < 1e-6);
+        let left = eye(Eye::Left, panorama, 0.0);
+        assert!((left.origin.x + 0.05).abs() < 1e-6);
+    }
+}
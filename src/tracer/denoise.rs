@@ -0,0 +1,239 @@
+//! Edge-avoiding À-trous wavelet filter (Dammertz et al. 2010), guided by
+//! the albedo and normal buffers of a [`Frame`].
+
+use crate::graphics::Color;
+use crate::tracer::render::Frame;
+
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+#[derive(Clone, Debug)]
+pub struct Denoiser {
+    /// Number of filter passes, the kernel footprint doubles at each pass.
+    pub iterations: usize,
+    /// Sensitivity to color differences, halved at each pass.
+    pub color_phi: f32,
+    pub normal_phi: f32,
+    pub albedo_phi: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser {
+            iterations: 5,
+            color_phi: 0.5,
+            normal_phi: 0.1,
+            albedo_phi: 0.05,
+        }
+    }
+}
+
+fn rgb(c: Color) -> [f32; 3] {
+    [c.red, c.green, c.blue]
+}
+
+fn distance2(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+fn weight(d2: f32, phi: f32) -> f32 {
+    (-d2 / phi).exp().min(1.0)
+}
+
+impl Denoiser {
+    /// Returns the filtered beauty buffer of `frame`.
+    ///
+    /// Lighting is filtered separately from the surface albedo so that
+    /// texture-like color changes are not blurred away. Only the pixels of
+    /// the frame region are filtered, from each other, the others being
+    /// returned as they are.
+    pub fn apply(&self, frame: &Frame) -> Vec<Color> {
+        let width = frame.width;
+        let region = frame.region;
+        let (left, top) = (region.x as isize, region.y as isize);
+        let (right, bottom) = (left + region.width as isize, top + region.height as isize);
+        let albedo: Vec<[f32; 3]> = frame.albedo.iter().map(|c| rgb(*c)).collect();
+        let normal: Vec<[f32; 3]> = frame.normal.iter().map(|n| [n.x, n.y, n.z]).collect();
+        let demodulate = |c: f32, a: f32| if a > 1e-3 { c / a } else { c };
+        let mut current: Vec<[f32; 3]> = frame
+            .beauty
+            .iter()
+            .zip(&albedo)
+            .map(|(c, a)| {
+                [
+                    demodulate(c.red, a[0]),
+                    demodulate(c.green, a[1]),
+                    demodulate(c.blue, a[2]),
+                ]
+            })
+            .collect();
+
+        let mut color_phi = self.color_phi;
+        for pass in 0..self.iterations {
+            let step = 1isize << pass;
+            let mut next = current.clone();
+            for y in top..bottom {
+                for x in left..right {
+                    let center = (x + y * width as isize) as usize;
+                    let mut sum = [0.0; 3];
+                    let mut total = 0.0;
+                    for (j, kj) in KERNEL.iter().enumerate() {
+                        let sy = y + (j as isize - 2) * step;
+                        if sy < top || sy >= bottom {
+                            continue;
+                        }
+                        for (i, ki) in KERNEL.iter().enumerate() {
+                            let sx = x + (i as isize - 2) * step;
+                            if sx < left || sx >= right {
+                                continue;
+                            }
+                            let sample = sx as usize + sy as usize * width;
+                            let w = kj
+                                * ki
+                                * weight(distance2(current[center], current[sample]), color_phi)
+                                * weight(
+                                    distance2(normal[center], normal[sample]),
+                                    self.normal_phi,
+                                )
+                                * weight(
+                                    distance2(albedo[center], albedo[sample]),
+                                    self.albedo_phi,
+                                );
+                            for (s, c) in sum.iter_mut().zip(&current[sample]) {
+                                *s += c * w;
+                            }
+                            total += w;
+                        }
+                    }
+                    // the center sample always has a weight of at least 9/64
+                    next[center] = [sum[0] / total, sum[1] / total, sum[2] / total];
+                }
+            }
+            current = next;
+            color_phi /= 2.0;
+        }
+
+        current
+            .iter()
+            .zip(&albedo)
+            .map(|(c, a)| {
+                let remodulate = |c: f32, a: f32| if a > 1e-3 { c * a } else { c };
+                Color {
+                    red: remodulate(c[0], a[0]),
+                    green: remodulate(c[1], a[1]),
+                    blue: remodulate(c[2], a[2]),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Region;
+    use crate::math::sampling::Rng;
+    use crate::math::vec3::Vector;
+
+    fn up() -> Vector {
+        Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        }
+    }
+
+    fn gray(v: f32) -> Color {
+        Color {
+            red: v,
+            green: v,
+            blue: v,
+        }
+    }
+
+    #[test]
+    fn constant_image() {
+        let mut frame = Frame::new(16, 16);
+        frame.beauty = vec![gray(0.4); 256];
+        frame.albedo = vec![gray(0.8); 256];
+        frame.normal = vec![up(); 256];
+        for c in Denoiser::default().apply(&frame) {
+            assert!((c.red - 0.4).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn keeps_bright_values() {
+        let mut frame = Frame::new(16, 16);
+        frame.beauty = vec![gray(2.5); 256];
+        frame.albedo = vec![gray(0.8); 256];
+        frame.normal = vec![up(); 256];
+        for c in Denoiser::default().apply(&frame) {
+            assert!((c.red - 2.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn filters_within_the_region() {
+        let mut frame = Frame::new(16, 16);
+        frame.region = Region {
+            x: 4,
+            y: 4,
+            width: 8,
+            height: 8,
+        };
+        frame.albedo = vec![gray(0.8); 256];
+        frame.normal = vec![up(); 256];
+        for y in 4..12 {
+            for x in 4..12 {
+                let index = frame.index(x, y);
+                frame.beauty[index] = gray(0.4);
+            }
+        }
+        let denoised = Denoiser::default().apply(&frame);
+        for (index, c) in denoised.iter().enumerate() {
+            assert!((c.red - frame.beauty[index].red).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn reduces_noise() {
+        let mut rng = Rng::new(3);
+        let mut frame = Frame::new(32, 32);
+        frame.beauty = (0..1024)
+            .map(|_| gray(0.3 + 0.2 * rng.next_f32()))
+            .collect();
+        frame.albedo = vec![gray(0.5); 1024];
+        frame.normal = vec![up(); 1024];
+        let variance = |data: &[Color]| {
+            let mean = data.iter().map(|c| c.red).sum::<f32>() / data.len() as f32;
+            data.iter().map(|c| (c.red - mean).powi(2)).sum::<f32>() / data.len() as f32
+        };
+        let denoised = Denoiser::default().apply(&frame);
+        assert!(variance(&denoised) < variance(&frame.beauty) / 10.0);
+    }
+
+    #[test]
+    fn keeps_normal_edges() {
+        let mut frame = Frame::new(16, 16);
+        for y in 0..16 {
+            for x in 0..16 {
+                let index = frame.index(x, y);
+                frame.albedo[index] = gray(1.0);
+                if x < 8 {
+                    frame.beauty[index] = gray(0.9);
+                    frame.normal[index] = up();
+                } else {
+                    frame.beauty[index] = gray(0.1);
+                    frame.normal[index] = Vector {
+                        x: 1.0,
+                        y: 0.0,
+                        z: 0.0,
+                    };
+                }
+            }
+        }
+        let denoised = Denoiser::default().apply(&frame);
+        assert!(denoised[frame.index(7, 8)].red > 0.85);
+        assert!(denoised[frame.index(8, 8)].red < 0.15);
+    }
+}
@@ -0,0 +1,363 @@
+//! Scene graph of named nodes, flattened into the objects and lights of a
+//! [`Scene`] before tracing.
+//!
+//! Nodes are addressed by the `/` separated names of their ancestors, as in
+//! `car/wheel_front_left`, the root having the empty path. Unnamed nodes
+//! hold anonymous objects and lights and cannot be looked up.
+//!
+//! [`Scene`]: crate::tracer::Scene
+
+use std::fmt;
+
+use crate::math::matrix::Matrix4;
+use crate::math::transform::Transform;
+use crate::math::vec3::Vector;
+use crate::tracer::lights::Light;
+use crate::tracer::objects::{Moving, Object};
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    InvalidName(String),
+    Duplicate(String),
+    Unknown(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid node name `{}`", name),
+            Self::Duplicate(path) => write!(f, "duplicate node `{}`", path),
+            Self::Unknown(path) => write!(f, "unknown node `{}`", path),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+#[derive(Clone)]
+pub enum Content {
+    Group,
+    Object(Object),
+    Light(Light),
+}
+
+#[derive(Clone)]
+pub struct Node {
+    pub name: String,
+    /// Placement relative to the parent node.
+    pub transform: Transform,
+    /// Hidden nodes are left out of the flattened scene with their children.
+    pub visible: bool,
+    /// Linear motion in the parent space, in units per second.
+    pub velocity: Vector,
+    pub content: Content,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: &str, content: Content) -> Node {
+        Node {
+            name: name.to_string(),
+            transform: Transform::default(),
+            visible: true,
+            velocity: Vector::default(),
+            content,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn group(name: &str) -> Node {
+        Node::new(name, Content::Group)
+    }
+
+    pub fn object(name: &str, object: impl Into<Object>) -> Node {
+        Node::new(name, Content::Object(object.into()))
+    }
+
+    pub fn light(name: &str, light: impl Into<Light>) -> Node {
+        Node::new(name, Content::Light(light.into()))
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Node {
+        Node { transform, ..self }
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Node> {
+        self.children
+            .iter()
+            .find(|c| !name.is_empty() && c.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.children
+            .iter_mut()
+            .find(|c| !name.is_empty() && c.name == name)
+    }
+
+    /// Adds `node` as the last child, its name being unique among the
+    /// named children.
+    pub fn add(&mut self, node: Node) -> Result<&mut Node, GraphError> {
+        if node.name.contains('/') {
+            return Err(GraphError::InvalidName(node.name));
+        }
+        if self.child(&node.name).is_some() {
+            return Err(GraphError::Duplicate(node.name));
+        }
+        self.children.push(node);
+        Ok(self.children.last_mut().unwrap())
+    }
+}
+
+/// Flattens a graph posed at evenly spaced times of the shutter interval,
+/// from its opening to its closing, the objects whose placement changes
+/// moving through all the poses.
+///
+/// Poses only differ by their transforms and contents, visibility and
+/// lights being those of the first one.
+pub fn flatten_motion(poses: &[SceneGraph]) -> Flattened {
+    let mut flat = Flattened::default();
+    let roots: Vec<&Node> = poses.iter().map(|g| &g.root).collect();
+    let parents = vec![Transform::default(); poses.len()];
+    visit(&roots, &parents, "", &mut flat);
+    flat
+}
+
+/// Adds the node posed as `nodes`, placed by the `parents` transforms.
+fn visit(nodes: &[&Node], parents: &[Transform], path: &str, flat: &mut Flattened) {
+    let node = nodes[0];
+    if !node.visible {
+        return;
+    }
+    let transforms: Vec<Transform> = nodes
+        .iter()
+        .zip(parents)
+        .map(|(n, parent)| *parent * n.transform)
+        .collect();
+    let transform = transforms[0];
+    let path = SceneGraph::join(path, &node.name);
+    match &node.content {
+        Content::Group => {}
+        Content::Object(object) => {
+            let object = if transforms.iter().any(|t| *t != transform) {
+                Moving {
+                    transforms: transforms.clone(),
+                    object: Box::new(object.clone()),
+                }
+                .into()
+            } else if transform == Transform::default() {
+                // leaves untransformed objects as they are
+                object.clone()
+            } else {
+                object.clone().transformed(transform)
+            };
+            flat.objects.push(object);
+            flat.paths.push(path.clone());
+        }
+        Content::Light(light) => flat.lights.push(light.transformed(&transform)),
+    }
+    for i in 0..node.children.len() {
+        let posed: Vec<&Node> = nodes.iter().map(|n| &n.children[i]).collect();
+        visit(&posed, &transforms, &path, flat);
+    }
+}
+
+/// Objects and lights of a flattened graph, in depth-first order.
+#[derive(Default)]
+pub struct Flattened {
+    pub objects: Vec<Object>,
+    pub lights: Vec<Light>,
+    /// Path of the closest named node holding each object.
+    pub paths: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct SceneGraph {
+    root: Node,
+}
+
+impl Default for SceneGraph {
+    fn default() -> SceneGraph {
+        SceneGraph {
+            root: Node::group(""),
+        }
+    }
+}
+
+impl SceneGraph {
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node {
+        &mut self.root
+    }
+
+    pub fn find(&self, path: &str) -> Option<&Node> {
+        Self::names(path).try_fold(&self.root, |node, name| node.child(name))
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut Node> {
+        Self::names(path).try_fold(&mut self.root, |node, name| node.child_mut(name))
+    }
+
+    /// Adds `node` under the node at `parent`.
+    pub fn insert(&mut self, parent: &str, node: Node) -> Result<&mut Node, GraphError> {
+        let path = Self::join(parent, &node.name);
+        match self.find_mut(parent) {
+            Some(parent) => parent.add(node).map_err(|e| match e {
+                GraphError::Duplicate(_) => GraphError::Duplicate(path),
+                e => e,
+            }),
+            None => Err(GraphError::Unknown(parent.to_string())),
+        }
+    }
+
+    /// Calls `f` on every node, parents first.
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut Node)) {
+        let mut stack = vec![&mut self.root];
+        while let Some(node) = stack.pop() {
+            f(node);
+            stack.extend(node.children.iter_mut().rev());
+        }
+    }
+
+    /// Shows or hides the node at `path`.
+    pub fn set_visible(&mut self, path: &str, visible: bool) -> Result<(), GraphError> {
+        let node = self
+            .find_mut(path)
+            .ok_or_else(|| GraphError::Unknown(path.to_string()))?;
+        node.visible = visible;
+        Ok(())
+    }
+
+    /// Visible objects and lights, placed in the scene by the transforms of
+    /// all their ancestors.
+    pub fn flatten(&self) -> Flattened {
+        flatten_motion(std::slice::from_ref(self))
+    }
+
+    /// Moves the nodes having a velocity to where they are at `time`.
+    pub fn advance(&mut self, time: f32) {
+        self.for_each_mut(|node| {
+            if node.velocity != Vector::default() {
+                let offset = Transform::new(Matrix4::translation(node.velocity * time));
+                node.transform = offset.unwrap_or_default() * node.transform;
+            }
+        });
+    }
+
+    fn names(path: &str) -> impl Iterator<Item = &str> {
+        path.split('/').filter(|name| !name.is_empty())
+    }
+
+    fn join(parent: &str, name: &str) -> String {
+        match (parent.is_empty(), name.is_empty()) {
+            (_, true) => parent.to_string(),
+            (true, false) => name.to_string(),
+            (false, false) => format!("{}/{}", parent, name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::matrix::Matrix4;
+    use crate::math::vec3::{Vector, Vertex};
+    use crate::tracer::lights::SphericalLight;
+    use crate::tracer::objects::{Material, Sphere};
+
+    fn moved(x: f32) -> Transform {
+        Transform::new(Matrix4::translation(Vector { x, y: 0.0, z: 0.0 })).unwrap()
+    }
+
+    fn sphere() -> Sphere {
+        Sphere {
+            center: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            radius: 0.5,
+            material: Material::new("#FFFFFF".parse().unwrap(), 0.5),
+        }
+    }
+
+    fn car() -> SceneGraph {
+        let mut graph = SceneGraph::default();
+        let car = graph
+            .insert("", Node::group("car").with_transform(moved(10.0)))
+            .unwrap();
+        car.add(Node::object("wheel_front_left", sphere()).with_transform(moved(1.0)))
+            .unwrap();
+        car.add(Node::object("wheel_front_right", sphere()).with_transform(moved(-1.0)))
+            .unwrap();
+        car.add(Node::object("", sphere())).unwrap();
+        let light = SphericalLight {
+            position: Vertex {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            base_color: "#FFFFFF".parse().unwrap(),
+            base_intensity: 1.0,
+        };
+        graph.insert("car", Node::light("lamp", light)).unwrap();
+        graph
+    }
+
+    #[test]
+    fn lookup() {
+        let mut graph = car();
+        assert_eq!(
+            graph.find("car/wheel_front_left").unwrap().name,
+            "wheel_front_left"
+        );
+        assert_eq!(graph.find("/car/").unwrap().children().len(), 4);
+        assert!(graph.find("car/wheel_back_left").is_none());
+        assert_eq!(graph.find("").unwrap().children().len(), 1);
+
+        assert_eq!(
+            graph.insert("car", Node::group("wheel_front_left")).err(),
+            Some(GraphError::Duplicate("car/wheel_front_left".into()))
+        );
+        assert_eq!(
+            graph.insert("truck", Node::group("wheel")).err(),
+            Some(GraphError::Unknown("truck".into()))
+        );
+        assert_eq!(
+            graph.insert("car", Node::group("a/b")).err(),
+            Some(GraphError::InvalidName("a/b".into()))
+        );
+    }
+
+    #[test]
+    fn flatten() {
+        let mut graph = car();
+        let flat = graph.flatten();
+        assert_eq!(flat.objects.len(), 3);
+        assert_eq!(flat.lights.len(), 1);
+        assert_eq!(
+            flat.paths,
+            ["car/wheel_front_left", "car/wheel_front_right", "car"]
+        );
+        let center = |object: &Object| object.bounds().unwrap().centroid().x;
+        assert!((center(&flat.objects[0]) - 11.0).abs() < 1e-5);
+        assert!((center(&flat.objects[1]) - 9.0).abs() < 1e-5);
+        match flat.lights[0] {
+            Light::Spherical(l) => assert_eq!(l.position.x, 10.0),
+            _ => panic!("expected a spherical light"),
+        }
+
+        graph.set_visible("car/wheel_front_right", false).unwrap();
+        assert_eq!(graph.flatten().objects.len(), 2);
+        graph.set_visible("car", false).unwrap();
+        let flat = graph.flatten();
+        assert!(flat.objects.is_empty() && flat.lights.is_empty());
+        assert!(graph.set_visible("bike", false).is_err());
+    }
+}
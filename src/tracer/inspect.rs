@@ -0,0 +1,154 @@
+use std::fmt;
+
+use crate::graphics::Color;
+use crate::math::vec3::{Vector, Vertex};
+
+/// What a single ray went through, see [`Scene::inspect`].
+///
+/// [`Scene::inspect`]: crate::tracer::Scene::inspect
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    pub origin: Vertex,
+    pub direction: Vector,
+    pub hit: Option<Hit>,
+}
+
+/// Closest interception of an inspected ray.
+#[derive(Clone, Debug)]
+pub struct Hit {
+    /// Index of the object in the flattened scene.
+    pub object: usize,
+    /// Scene graph node holding the object.
+    pub path: Option<String>,
+    pub kind: &'static str,
+    pub color: Color,
+    pub albedo: f32,
+    pub distance: f32,
+    pub hitpoint: Vertex,
+    pub normal: Vector,
+    /// Normal perturbed by the relief of the material.
+    pub shading_normal: Vector,
+    /// Sum of the light contributions, as computed by the direct integrator.
+    pub direct: Color,
+    pub lights: Vec<LightSample>,
+}
+
+/// Contribution of one light to the direct lighting at a hit.
+#[derive(Clone, Debug)]
+pub struct LightSample {
+    /// Index of the light in the flattened scene.
+    pub light: usize,
+    pub contribution: Color,
+    /// Object casting a shadow, the contribution then being black.
+    pub occluder: Option<usize>,
+}
+
+struct Rgb(Color);
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({:.4}, {:.4}, {:.4})",
+            self.0.red, self.0.green, self.0.blue
+        )
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = self.direction;
+        writeln!(f, "ray direction: ({:.4}, {:.4}, {:.4})", d.x, d.y, d.z)?;
+        let hit = match &self.hit {
+            Some(hit) => hit,
+            None => return write!(f, "no hit"),
+        };
+        let (p, n) = (hit.hitpoint, hit.normal);
+        match &hit.path {
+            Some(path) => writeln!(f, "object #{} ({}) in {}", hit.object, hit.kind, path)?,
+            None => writeln!(f, "object #{} ({})", hit.object, hit.kind)?,
+        }
+        writeln!(
+            f,
+            "material: color {}, albedo {:.3}",
+            Rgb(hit.color),
+            hit.albedo
+        )?;
+        writeln!(f, "distance: {:.4}", hit.distance)?;
+        writeln!(f, "hitpoint: ({:.4}, {:.4}, {:.4})", p.x, p.y, p.z)?;
+        writeln!(f, "normal: ({:.4}, {:.4}, {:.4})", n.x, n.y, n.z)?;
+        if hit.shading_normal != n {
+            let s = hit.shading_normal;
+            writeln!(f, "shading normal: ({:.4}, {:.4}, {:.4})", s.x, s.y, s.z)?;
+        }
+        for sample in &hit.lights {
+            match sample.occluder {
+                Some(occluder) => writeln!(
+                    f,
+                    "light #{}: shadowed by object #{}",
+                    sample.light, occluder
+                )?,
+                None => writeln!(f, "light #{}: {}", sample.light, Rgb(sample.contribution))?,
+            }
+        }
+        write!(f, "direct lighting: {}", Rgb(hit.direct))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::vec3::{Vector, Vertex};
+    use crate::tracer::lights::DirectionalLight;
+    use crate::tracer::objects::{Material, Sphere};
+    use crate::tracer::{Ray, RayKind, Scene};
+
+    fn sphere(y: f32, z: f32, radius: f32) -> Sphere {
+        Sphere {
+            center: Vertex { x: 0.0, y, z },
+            radius,
+            material: Material::new("#FF8800".parse().unwrap(), 0.8),
+        }
+    }
+
+    #[test]
+    fn lights_and_shadows() {
+        let mut scene = Scene::default();
+        scene.add_object(sphere(0.0, -4.0, 1.0));
+        // above the front of the first sphere
+        scene.add_object(sphere(3.0, -3.0, 0.5));
+        let light = |y, z| DirectionalLight {
+            direction: Vector { x: 0.0, y, z },
+            base_color: "#FFFFFF".parse().unwrap(),
+            base_intensity: 2.0,
+        };
+        scene.add_light(light(0.0, -1.0));
+        scene.add_light(light(-1.0, 0.0));
+        let ray = Ray {
+            kind: RayKind::Primary,
+            origin: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            time: 0.0,
+            differentials: None,
+        };
+
+        let inspection = scene.inspect(&ray);
+        let hit = inspection.hit.expect("sphere hit");
+        assert_eq!(hit.object, 0);
+        assert!((hit.distance - 3.0).abs() < 1e-5);
+        assert_eq!(hit.lights.len(), 2);
+        assert_eq!(hit.lights[0].occluder, None);
+        assert!(hit.lights[0].contribution.red > 0.0);
+        assert_eq!(hit.lights[1].occluder, Some(1));
+
+        let interception = scene.trace(&ray).unwrap();
+        assert_eq!(scene.compute_color(&interception), hit.direct);
+    }
+}
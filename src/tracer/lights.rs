@@ -1,4 +1,5 @@
 use crate::graphics::Color;
+use crate::math::transform::Transform;
 use crate::math::vec3::{Vector, Vertex};
 
 pub trait RenderableLight {
@@ -42,6 +43,22 @@ impl Light {
             Self::Spherical(l) => l.intensity(point),
         }
     }
+
+    /// `self` placed in the scene by `transform`.
+    pub fn transformed(&self, transform: &Transform) -> Light {
+        match *self {
+            Self::Directional(l) => DirectionalLight {
+                direction: transform.vector(l.direction),
+                ..l
+            }
+            .into(),
+            Self::Spherical(l) => SphericalLight {
+                position: transform.point(l.position),
+                ..l
+            }
+            .into(),
+        }
+    }
 }
 
 impl From<DirectionalLight> for Light {
@@ -73,7 +90,7 @@ impl RenderableLight for DirectionalLight {
     }
 
     fn distance(&self, _: Vertex) -> f32 {
-        ::std::f32::INFINITY
+        f32::INFINITY
     }
 
     fn intensity(&self, _: Vertex) -> f32 {
@@ -0,0 +1,191 @@
+//! Polygon meshes read from Wavefront OBJ files.
+//!
+//! Only vertex positions (`v`), texture coordinates (`vt`) and faces (`f`)
+//! are read, polygons being kept whole for subdivision and split in fans of
+//! triangles to be rendered. Other statements are ignored.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::math::vec3::Vertex;
+use crate::tracer::texture::Uv;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(std::io::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+impl From<std::io::Error> for MeshError {
+    fn from(val: std::io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+/// Polygon of a mesh, as indices of its corners, counterclockwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Face {
+    /// Indices in the positions.
+    pub vertices: Vec<usize>,
+    /// Indices in the texture coordinates, when the face gives them.
+    pub uvs: Option<Vec<usize>>,
+}
+
+/// Faces sharing vertex positions and texture coordinates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<Vertex>,
+    pub texcoords: Vec<Uv>,
+    pub faces: Vec<Face>,
+}
+
+impl Mesh {
+    /// Fans of triangles splitting the faces, with their texture coordinates.
+    pub fn triangles(&self) -> impl Iterator<Item = ([Vertex; 3], Option<[Uv; 3]>)> + '_ {
+        self.faces.iter().flat_map(move |face| {
+            (1..face.vertices.len() - 1).map(move |i| {
+                let corners = [0, i, i + 1];
+                (
+                    corners.map(|c| self.positions[face.vertices[c]]),
+                    face.uvs
+                        .as_ref()
+                        .map(|uvs| corners.map(|c| self.texcoords[uvs[c]])),
+                )
+            })
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Mesh, MeshError> {
+        Mesh::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(src: &str) -> Result<Mesh, MeshError> {
+        let mut mesh = Mesh::default();
+        for (n, line) in src.lines().enumerate() {
+            let invalid = |message: String| MeshError::Invalid {
+                line: n + 1,
+                message,
+            };
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let coords: Vec<f32> = words
+                        .take(3)
+                        .map(|w| {
+                            w.parse()
+                                .map_err(|_| invalid(format!("invalid number `{}`", w)))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    match coords[..] {
+                        [x, y, z] => mesh.positions.push(Vertex { x, y, z }),
+                        _ => return Err(invalid("expected 3 coordinates".into())),
+                    }
+                }
+                Some("vt") => {
+                    let coords: Vec<f32> = words
+                        .take(2)
+                        .map(|w| {
+                            w.parse()
+                                .map_err(|_| invalid(format!("invalid number `{}`", w)))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    match coords[..] {
+                        [u, v] => mesh.texcoords.push(Uv { u, v }),
+                        [u] => mesh.texcoords.push(Uv { u, v: 0.0 }),
+                        _ => return Err(invalid("expected texture coordinates".into())),
+                    }
+                }
+                Some("f") => {
+                    // `v`, `v/vt`, `v//vn` or `v/vt/vn`, negative indices
+                    // counting from the last one
+                    let resolve = |index: &str, len: usize| match index.parse::<isize>() {
+                        Ok(i) if i > 0 => Some(i as usize - 1),
+                        Ok(i) if i < 0 => len.checked_sub(i.unsigned_abs()),
+                        _ => None,
+                    };
+                    let mut vertices = Vec::new();
+                    let mut uvs = Vec::new();
+                    for w in words {
+                        let mut indices = w.split('/');
+                        let vertex = resolve(indices.next().unwrap_or(""), mesh.positions.len())
+                            .filter(|&i| i < mesh.positions.len())
+                            .ok_or_else(|| invalid(format!("invalid vertex `{}`", w)))?;
+                        vertices.push(vertex);
+                        match indices.next() {
+                            Some(index) if !index.is_empty() => {
+                                let uv = resolve(index, mesh.texcoords.len())
+                                    .filter(|&i| i < mesh.texcoords.len())
+                                    .ok_or_else(|| {
+                                        invalid(format!("invalid texture coordinates `{}`", w))
+                                    })?;
+                                uvs.push(uv);
+                            }
+                            _ => {}
+                        }
+                    }
+                    if vertices.len() < 3 {
+                        return Err(invalid("a face needs at least 3 vertices".into()));
+                    }
+                    let textured = uvs.len() == vertices.len();
+                    mesh.faces.push(Face {
+                        vertices,
+                        uvs: if textured { Some(uvs) } else { None },
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let src = "# a unit square and a triangle\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vn 0 0 1\n\
+                   f 1//1 2//1 3//1 4//1\n\
+                   f -1 -2 -3\n";
+        let mesh = Mesh::parse(src).unwrap();
+        assert_eq!(mesh.faces.len(), 2);
+        assert_eq!(mesh.faces[1].vertices, [3, 2, 1]);
+        let triangles: Vec<_> = mesh.triangles().collect();
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(triangles[1].0[2].y, 1.0);
+        assert_eq!(triangles[2].0[0].x, 0.0);
+        assert_eq!(triangles[2].0[0].y, 1.0);
+
+        assert!(triangles.iter().all(|(_, uvs)| uvs.is_none()));
+
+        let textured = Mesh::parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5 0\nvt 1 1\nf 1/1 2/2 3/1\n");
+        assert_eq!(
+            textured.unwrap().triangles().next().unwrap().1,
+            Some([
+                Uv { u: 0.5, v: 0.0 },
+                Uv { u: 1.0, v: 1.0 },
+                Uv { u: 0.5, v: 0.0 }
+            ])
+        );
+
+        match Mesh::parse("v 0 0 0\nf 1 2 3\n") {
+            Err(MeshError::Invalid { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected an invalid mesh"),
+        }
+    }
+}
@@ -1,12 +1,35 @@
+pub mod animation;
+pub mod bvh;
+pub mod camera;
+pub mod denoise;
+pub mod graph;
+pub mod inspect;
 pub mod lights;
+pub mod mesh;
 pub mod objects;
+pub mod procedural;
+pub mod projection;
+pub mod render;
+pub mod scenefile;
+pub mod subdivision;
+pub mod texture;
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 
 use crate::graphics::Color;
+use crate::math::sampling::{cosine_hemisphere, Rng};
+use crate::math::transform::Transform;
 use crate::math::vec3::{Vector, Vertex};
+use crate::math::Lerp;
+use bvh::Bvh;
+use camera::Camera;
+use graph::SceneGraph;
+use inspect::{Hit, Inspection, LightSample};
 use lights::Light;
-use objects::Object;
+use objects::{Material, Object, RenderableObject};
+use texture::{Surface, Uv};
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum RayKind {
@@ -20,11 +43,29 @@ pub struct Ray {
     pub kind: RayKind,
     pub origin: Vertex,
     pub direction: Vector,
+    /// When the ray is traced, from 0 as the shutter opens to 1 as it
+    /// closes, secondary rays keeping the time of their primary one.
+    pub time: f32,
+    /// Rays through the neighbouring pixels, only followed by primary rays.
+    pub differentials: Option<Differentials>,
+}
+
+/// Rays through the pixels to the right of and below the one of a primary
+/// ray, giving the footprint of the pixel on the surfaces it hits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Differentials {
+    pub x_origin: Vertex,
+    pub x_direction: Vector,
+    pub y_origin: Vertex,
+    pub y_direction: Vector,
 }
 
 pub type Tracer = dyn Iterator<Item = Ray>;
 
 pub struct Screen {
+    camera: Camera,
+    /// Camera as the shutter closes, when it moves.
+    closing: Option<Camera>,
     width: f32,
     height: f32,
     cursor: usize,
@@ -33,6 +74,10 @@ pub struct Screen {
 
 impl Screen {
     pub fn new(width: usize, height: usize) -> Screen {
+        Screen::with_camera(Camera::default(), width, height)
+    }
+
+    pub fn with_camera(camera: Camera, width: usize, height: usize) -> Screen {
         let mut points = Vec::new();
         for y in 0..height {
             for x in 0..width {
@@ -40,16 +85,87 @@ impl Screen {
             }
         }
         Screen {
+            camera,
+            closing: None,
             width: width as f32,
             height: height as f32,
             cursor: 0,
             points,
         }
     }
+
+    /// Camera moving linearly to `closing` over the shutter interval.
+    pub fn with_motion(self, closing: Option<Camera>) -> Screen {
+        Screen { closing, ..self }
+    }
+
+    /// Primary ray going through the continuous screen position `(x, y)`,
+    /// pixel `(i, j)` covering `[i, i + 1) x [j, j + 1)`, `None` where the
+    /// camera projection does not cover the screen.
+    pub fn ray_at(&self, x: f32, y: f32) -> Option<Ray> {
+        self.ray_at_time(x, y, 0.0)
+    }
+
+    /// Primary ray through `(x, y)` at `time` of the shutter interval,
+    /// leaving the center of the lens.
+    pub fn ray_at_time(&self, x: f32, y: f32, time: f32) -> Option<Ray> {
+        let camera = self.camera_at(time);
+        let aspect = self.width / self.height;
+        self.pixel_ray(x, y, time, |x, y| camera.ray(x, y, aspect))
+    }
+
+    /// Primary ray through `(x, y)` at `time`, leaving the point of the
+    /// lens aperture sampled from the two numbers of `lens` in `[0, 1)`.
+    pub fn lens_ray(&self, x: f32, y: f32, time: f32, lens: (f32, f32)) -> Option<Ray> {
+        let camera = self.camera_at(time);
+        let aspect = self.width / self.height;
+        self.pixel_ray(x, y, time, |x, y| {
+            camera.lens_ray(x, y, aspect, lens.0, lens.1)
+        })
+    }
+
+    /// Ray of `project` through `(x, y)`, with the rays one pixel to the
+    /// right and one below as differentials.
+    fn pixel_ray(
+        &self,
+        x: f32,
+        y: f32,
+        time: f32,
+        project: impl Fn(f32, f32) -> Option<Ray>,
+    ) -> Option<Ray> {
+        let at = |x, y| {
+            let (x, y) = self.normalized(x, y);
+            project(x, y)
+        };
+        let mut ray = at(x, y)?;
+        ray.time = time;
+        // none across the edges of the projection
+        ray.differentials = match (at(x + 1.0, y), at(x, y + 1.0)) {
+            (Some(rx), Some(ry)) => Some(Differentials {
+                x_origin: rx.origin,
+                x_direction: rx.direction,
+                y_origin: ry.origin,
+                y_direction: ry.direction,
+            }),
+            _ => None,
+        };
+        Some(ray)
+    }
+
+    fn camera_at(&self, time: f32) -> Camera {
+        match self.closing {
+            Some(closing) => self.camera.lerp(closing, time),
+            None => self.camera,
+        }
+    }
+
+    fn normalized(&self, x: f32, y: f32) -> (f32, f32) {
+        ((x / self.width) * 2.0 - 1.0, 1.0 - (y / self.height) * 2.0)
+    }
 }
 
 impl Iterator for Screen {
-    type Item = ((usize, usize), Ray);
+    type Item = ((usize, usize), Option<Ray>);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.cursor == self.points.len() {
@@ -58,35 +174,33 @@ impl Iterator for Screen {
 
         let point = self.points[self.cursor];
         self.cursor += 1;
-        let origin = Vertex {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        };
-        let screen_vec = Vertex {
-            x: (((point.0 + 0.5) / self.width) * 2.0 - 1.0) * self.width / self.height,
-            y: 1.0 - ((point.1 + 0.5) / self.height) * 2.0,
-            z: -1.0,
-        };
         Some((
             (point.0 as usize, point.1 as usize),
-            Ray {
-                kind: RayKind::Primary,
-                origin,
-                direction: Vector::from_vertices(origin, screen_vec),
-            },
+            self.ray_at(point.0 + 0.5, point.1 + 0.5),
         ))
     }
 }
 
-pub struct Interception {
-    pub object: Object,
+pub struct Interception<'a> {
+    /// Primitive hit, in its own space when `transform` is set.
+    pub object: &'a dyn RenderableObject,
     pub distance: f32,
     pub hitpoint: Vertex,
+    /// Placement of the instanced primitive in the scene.
+    pub transform: Option<Transform>,
+    /// Instance material replacing the primitive one.
+    pub material: Option<&'a Material>,
+    /// Time of the ray, see [`Ray::time`].
+    pub time: f32,
+    /// Face of the primitive hit, see [`RenderableObject::intercept_face`].
+    pub face: usize,
+    /// Texture coordinates at the hitpoint, only computed for the closest
+    /// interception of the traced rays.
+    pub surface: Surface,
 }
 
-impl Interception {
-    pub fn new(object: Object, ray: &Ray, distance: f32) -> Interception {
+impl<'a> Interception<'a> {
+    pub fn new(object: &'a dyn RenderableObject, ray: &Ray, distance: f32) -> Interception<'a> {
         Interception {
             object,
             distance,
@@ -95,74 +209,398 @@ impl Interception {
                 y: ray.origin.y + ray.direction.y * distance,
                 z: ray.origin.z + ray.direction.z * distance,
             },
+            transform: None,
+            material: None,
+            time: ray.time,
+            face: 0,
+            surface: Surface::default(),
+        }
+    }
+
+    /// Fills in the texture coordinates at the hitpoint, and how they change
+    /// to the neighbouring pixels when `ray` has differentials.
+    pub fn compute_surface(&mut self, ray: &Ray) {
+        let mut surface = match &self.transform {
+            Some(t) => {
+                let surface = self
+                    .object
+                    .surface(t.inverse().point(self.hitpoint), self.face);
+                Surface {
+                    dpdu: t.vector(surface.dpdu),
+                    dpdv: t.vector(surface.dpdv),
+                    ..surface
+                }
+            }
+            None => self.object.surface(self.hitpoint, self.face),
+        };
+        surface.scene_point = self.hitpoint;
+        if let Some(d) = &ray.differentials {
+            // offset rays meeting the tangent plane
+            let normal = self.normal();
+            let along = |origin: Vertex, direction: Vector| {
+                let t = normal.dot(Vector::from_vertices(origin, self.hitpoint))
+                    / normal.dot(direction);
+                if !t.is_finite() {
+                    return Uv::default();
+                }
+                surface.uv_along(Vector::from_vertices(self.hitpoint, origin + direction * t))
+            };
+            let (dx, dy) = (
+                along(d.x_origin, d.x_direction),
+                along(d.y_origin, d.y_direction),
+            );
+            surface.dx = dx;
+            surface.dy = dy;
+        }
+        self.surface = surface;
+    }
+
+    pub fn material(&self) -> &Material {
+        self.material.unwrap_or_else(|| self.object.material())
+    }
+
+    /// Color at the hitpoint, textured once the surface is computed.
+    pub fn color(&self) -> Color {
+        self.material().color_at(&self.surface)
+    }
+
+    pub fn albedo(&self) -> f32 {
+        self.material().albedo_at(&self.surface)
+    }
+
+    /// Normal perturbed by the relief of the material, for shading, the
+    /// geometric normal still offsetting the secondary rays off the surface.
+    pub fn shading_normal(&self) -> Vector {
+        let normal = self.normal();
+        let relief = match &self.material().relief {
+            Some(relief) => relief,
+            None => return normal,
+        };
+        let normal = normal.normalize();
+        match &self.transform {
+            Some(t) => {
+                let inverse = t.inverse();
+                relief.normal(normal, &self.surface, |v| inverse.vector(v))
+            }
+            None => relief.normal(normal, &self.surface, |v| v),
+        }
+    }
+
+    /// Geometric surface normal at the hitpoint, in scene space.
+    pub fn normal(&self) -> Vector {
+        match &self.transform {
+            Some(t) => {
+                let local = t.inverse().point(self.hitpoint);
+                t.normal(self.object.compute_normal(local, self.face))
+            }
+            None => self.object.compute_normal(self.hitpoint, self.face),
         }
     }
 }
 
 #[derive(Default)]
 pub struct Statistics {
-    pub rays: HashMap<RayKind, usize>,
+    counters: [AtomicUsize; 4],
 }
 
 impl Statistics {
-    pub fn count_ray(&mut self, ray: &Ray) {
-        let counter = self.rays.entry(ray.kind.clone()).or_insert(0);
-        *counter += 1;
+    pub fn count_ray(&self, ray: &Ray) {
+        self.counters[ray.kind.clone() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rays(&self) -> HashMap<RayKind, usize> {
+        let kinds = [
+            RayKind::Primary,
+            RayKind::Shadow,
+            RayKind::Reflection,
+            RayKind::Refraction,
+        ];
+        kinds
+            .iter()
+            .map(|kind| {
+                let count = self.counters[kind.clone() as usize].load(Ordering::Relaxed);
+                (kind.clone(), count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
     }
 }
 
+/// Objects are traced through a hierarchy over their bounds, built on the
+/// first trace, instances holding their own one.
 #[derive(Default)]
 pub struct Scene {
-    pub objects: Vec<Object>,
+    pub camera: Camera,
+    objects: Vec<Object>,
     pub lights: Vec<Light>,
+    /// Camera as the shutter closes, when it moves over the interval.
+    pub closing_camera: Option<Camera>,
     pub stats: Statistics,
+    bvh: OnceLock<Bvh>,
+    /// Scene graph path of each object, when loaded from one.
+    paths: Vec<String>,
 }
 
 impl Scene {
+    /// Replaces the objects and lights with the flattened `graph`.
+    pub fn load_graph(&mut self, graph: &SceneGraph) {
+        self.load_motion(std::slice::from_ref(graph));
+    }
+
+    /// Replaces the objects and lights with the graph moving through
+    /// `poses` over the shutter interval, see [`graph::flatten_motion`].
+    pub fn load_motion(&mut self, poses: &[SceneGraph]) {
+        let flat = graph::flatten_motion(poses);
+        self.objects = flat.objects;
+        self.lights = flat.lights;
+        self.paths = flat.paths;
+        self.bvh = OnceLock::new();
+    }
+
+    /// Path of the scene graph node holding an object, `None` for the root.
+    pub fn path_of(&self, object: usize) -> Option<&str> {
+        self.paths
+            .get(object)
+            .map(String::as_str)
+            .filter(|p| !p.is_empty())
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// Objects to edit in place, the hierarchy over them being built again
+    /// on the next trace.
+    pub fn objects_mut(&mut self) -> &mut Vec<Object> {
+        self.bvh = OnceLock::new();
+        &mut self.objects
+    }
+
     pub fn add_object(&mut self, object: impl Into<Object>) {
-        self.objects.push(object.into())
+        self.objects.push(object.into());
+        self.bvh = OnceLock::new();
     }
 
     pub fn add_light(&mut self, object: impl Into<Light>) {
         self.lights.push(object.into())
     }
 
-    pub fn trace(&mut self, ray: &Ray) -> Option<Interception> {
-        // TODO smarter ray counting would avoid mut borrow
-        self.stats.count_ray(&ray);
-        self.objects
-            .iter()
-            .filter_map(|o| o.intercept(ray).map(|d| Interception::new(*o, ray, d)))
-            .min_by(|d1, d2| d1.distance.partial_cmp(&d2.distance).unwrap())
+    pub fn trace(&self, ray: &Ray) -> Option<Interception<'_>> {
+        self.stats.count_ray(ray);
+        let (_, mut interception) = self.closest(ray)?;
+        interception.compute_surface(ray);
+        Some(interception)
     }
 
-    pub fn compute_color(&mut self, interception: &Interception) -> Color {
-        let hitpoint = interception.hitpoint;
+    /// Closest interception along `ray` and the index of the object hit.
+    fn closest(&self, ray: &Ray) -> Option<(usize, Interception<'_>)> {
+        let bvh = self.bvh.get_or_init(|| {
+            let bounds: Vec<_> = self.objects.iter().map(Object::bounds).collect();
+            Bvh::build(&bounds)
+        });
+        bvh.closest(ray, |n| {
+            self.objects[n].hit(ray).map(|i| (i.distance, (n, i)))
+        })
+        .map(|(_, hit)| hit)
+    }
+
+    pub fn compute_color(&self, interception: &Interception) -> Color {
+        let (normal, shading) = (interception.normal(), interception.shading_normal());
         let mut color = Color::default();
-        let normal = interception.object.compute_normal(hitpoint);
-        for light in self.lights.clone() {
-            let light_direction = light.direction_from(hitpoint);
-            let shadow_origin = Vertex {
-                x: hitpoint.x + normal.x * 1e-5,
-                y: hitpoint.y + normal.y * 1e-5,
-                z: hitpoint.z + normal.z * 1e-5,
-            };
-            let shadow_ray = Ray {
-                origin: shadow_origin,
-                direction: light_direction,
-                kind: RayKind::Shadow,
-            };
-            let enlighted = match self.trace(&shadow_ray) {
-                Some(i) => i.distance > light.distance(hitpoint),
-                None => true,
-            };
-            if enlighted {
-                let power = normal.dot(light_direction).max(0.0) * light.intensity(hitpoint);
-                let reflected = interception.object.albedo() / std::f32::consts::PI;
+        for light in &self.lights {
+            color += self
+                .light_contribution(interception, normal, shading, light)
+                .0;
+        }
+        color
+    }
+
+    /// Light reflected from `light` at the interception, or the index of the
+    /// object casting a shadow, the shadow ray leaving from the geometric
+    /// `normal` side.
+    fn light_contribution(
+        &self,
+        interception: &Interception,
+        normal: Vector,
+        shading: Vector,
+        light: &Light,
+    ) -> (Color, Option<usize>) {
+        let hitpoint = interception.hitpoint;
+        let light_direction = light.direction_from(hitpoint);
+        let shadow_ray = Ray {
+            origin: offset_origin(hitpoint, normal),
+            direction: light_direction,
+            kind: RayKind::Shadow,
+            time: interception.time,
+            differentials: None,
+        };
+        self.stats.count_ray(&shadow_ray);
+        match self.closest(&shadow_ray) {
+            Some((occluder, i)) if i.distance <= light.distance(hitpoint) => {
+                (Color::default(), Some(occluder))
+            }
+            _ => {
+                let power = shading.dot(light_direction).max(0.0) * light.intensity(hitpoint);
+                let reflected = interception.albedo() / std::f32::consts::PI;
                 let light_color = light.color() * power * reflected;
-                color += interception.object.color() * light_color;
+                (interception.color() * light_color, None)
+            }
+        }
+    }
+
+    /// Traces `ray` like a primary ray, recording what it hits and how each
+    /// light contributes to the direct lighting there.
+    pub fn inspect(&self, ray: &Ray) -> Inspection {
+        self.stats.count_ray(ray);
+        let hit = self.closest(ray).map(|(object, mut i)| {
+            i.compute_surface(ray);
+            let (normal, shading) = (i.normal(), i.shading_normal());
+            let lights: Vec<LightSample> = self
+                .lights
+                .iter()
+                .enumerate()
+                .map(|(light, l)| {
+                    let (contribution, occluder) = self.light_contribution(&i, normal, shading, l);
+                    LightSample {
+                        light,
+                        contribution,
+                        occluder,
+                    }
+                })
+                .collect();
+            Hit {
+                object,
+                path: self.path_of(object).map(str::to_string),
+                kind: i.object.kind(),
+                color: i.color(),
+                albedo: i.albedo(),
+                distance: i.distance,
+                hitpoint: i.hitpoint,
+                normal,
+                shading_normal: shading,
+                direct: lights
+                    .iter()
+                    .fold(Color::default(), |sum, l| sum + l.contribution),
+                lights,
             }
+        });
+        Inspection {
+            origin: ray.origin,
+            direction: ray.direction,
+            hit,
+        }
+    }
+
+    /// Path traced radiance along `ray`, following up to `depth` diffuse
+    /// bounces and computing direct lighting at each of them.
+    pub fn compute_radiance(&self, ray: &Ray, depth: usize, rng: &mut Rng) -> Color {
+        if depth == 0 {
+            return Color::default();
+        }
+        match self.trace(ray) {
+            Some(i) => self.compute_path(&i, depth, rng),
+            None => Color::default(),
+        }
+    }
+
+    /// Path traced radiance leaving an already found interception.
+    pub fn compute_path(&self, interception: &Interception, depth: usize, rng: &mut Rng) -> Color {
+        let mut color = self.compute_color(interception);
+        if depth > 1 {
+            let (normal, shading) = (interception.normal(), interception.shading_normal());
+            let bounce = Ray {
+                kind: RayKind::Reflection,
+                origin: offset_origin(interception.hitpoint, normal),
+                direction: cosine_hemisphere(shading, rng.next_f32(), rng.next_f32()),
+                time: interception.time,
+                differentials: None,
+            };
+            // cosine sampling cancels out the lambertian 1/pi and cos terms
+            let incoming = self.compute_radiance(&bounce, depth - 1, rng);
+            color += interception.color() * incoming * interception.albedo();
         }
         color
     }
 }
+
+/// Moves a secondary ray origin off the surface to avoid self-intersection.
+fn offset_origin(hitpoint: Vertex, normal: Vector) -> Vertex {
+    Vertex {
+        x: hitpoint.x + normal.x * 1e-5,
+        y: hitpoint.y + normal.y * 1e-5,
+        z: hitpoint.z + normal.z * 1e-5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use objects::{Plane, Sphere};
+
+    #[test]
+    fn pixel_footprint() {
+        let mut scene = Scene::default();
+        scene.add_object(Plane {
+            point: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            normal: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            material: Material::new(Color::default(), 1.0),
+        });
+        let screen = Screen::new(100, 100);
+        let footprint = |x: f32, y: f32| {
+            let surface = scene.trace(&screen.ray_at(x, y).unwrap()).unwrap().surface;
+            let length = |d: Uv| (d.u * d.u + d.v * d.v).sqrt();
+            (length(surface.dx), length(surface.dy))
+        };
+        // a 90 degree field of view over 100 pixels, one unit away
+        let (dx, dy) = footprint(50.5, 50.5);
+        assert!((dx - 0.02).abs() < 1e-4 && (dy - 0.02).abs() < 1e-4);
+        // the same all over a plane parallel to the image
+        let (dx, _) = footprint(0.5, 90.5);
+        assert!((dx - 0.02).abs() < 1e-4);
+
+        let ray = Ray {
+            differentials: None,
+            ..screen.ray_at(50.5, 50.5).unwrap()
+        };
+        assert_eq!(scene.trace(&ray).unwrap().surface.dx, Uv::default());
+    }
+
+    #[test]
+    fn edited_objects_are_traced() {
+        let mut scene = Scene::default();
+        scene.add_object(Sphere {
+            center: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: -4.0,
+            },
+            radius: 1.0,
+            material: Material::new(Color::default(), 1.0),
+        });
+        let ray = Screen::new(10, 10).ray_at(5.0, 5.0).unwrap();
+        assert_eq!(scene.trace(&ray).unwrap().distance, 3.0);
+        scene.objects_mut().push(
+            Sphere {
+                center: Vertex {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -2.0,
+                },
+                radius: 0.5,
+                material: Material::new(Color::default(), 1.0),
+            }
+            .into(),
+        );
+        assert_eq!(scene.trace(&ray).unwrap().distance, 1.5);
+        scene.objects_mut().clear();
+        assert!(scene.trace(&ray).is_none());
+    }
+}
@@ -1,34 +1,204 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
 use crate::graphics::Color;
+use crate::math::sampling::orthonormal_basis;
+use crate::math::transform::Transform;
 use crate::math::vec3::{Vector, Vertex};
-use crate::math::{solve_quadratic, QuadraticSolution};
-use crate::tracer::Ray;
+use crate::math::{solve_quadratic, solve_quartic, QuadraticSolution};
+use crate::tracer::bvh::{Aabb, Bvh};
+use crate::tracer::texture::{Relief, Surface, Texture, Uv};
+use crate::tracer::{Interception, Ray};
 
 pub trait RenderableObject {
-    fn color(&self) -> Color;
-    fn albedo(&self) -> f32;
+    /// Name of the primitive in scene files.
+    fn kind(&self) -> &'static str;
+    fn material(&self) -> &Material;
     fn intercept(&self, ray: &Ray) -> Option<f32>;
-    fn compute_normal(&self, hitpoint: Vertex) -> Vector;
+    /// Distance along `ray` and the face hit, which the primitives made of
+    /// surfaces meeting at an edge tell apart here rather than from the
+    /// hitpoint.
+    fn intercept_face(&self, ray: &Ray) -> Option<(f32, usize)> {
+        self.intercept(ray).map(|distance| (distance, 0))
+    }
+    fn compute_normal(&self, hitpoint: Vertex, face: usize) -> Vector;
+    /// Texture coordinates and their derivatives at a point of the `face`.
+    fn surface(&self, hitpoint: Vertex, face: usize) -> Surface;
+    /// Box containing the object, `None` when it is infinite.
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+/// Diffuse reflectance, the textures scaling the color and the albedo over
+/// the surface, which the relief makes look uneven.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub color: Color,
+    pub albedo: f32,
+    pub color_texture: Option<Arc<dyn Texture<Color>>>,
+    pub albedo_texture: Option<Arc<dyn Texture<f32>>>,
+    pub relief: Option<Relief>,
+    /// Name in the scene file, by which animation tracks find it.
+    pub name: Option<Arc<str>>,
+}
+
+impl Material {
+    pub fn new(color: Color, albedo: f32) -> Material {
+        Material {
+            color,
+            albedo,
+            color_texture: None,
+            albedo_texture: None,
+            relief: None,
+            name: None,
+        }
+    }
+
+    pub fn color_at(&self, surface: &Surface) -> Color {
+        match &self.color_texture {
+            Some(texture) => self.color * texture.value(surface),
+            None => self.color,
+        }
+    }
+
+    pub fn albedo_at(&self, surface: &Surface) -> f32 {
+        match &self.albedo_texture {
+            Some(texture) => self.albedo * texture.value(surface),
+            None => self.albedo,
+        }
+    }
 }
 
 // TODO proc macro to impl RenderableObject
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum Object {
     Plane(Plane),
     Sphere(Sphere),
+    Triangle(Triangle),
+    Cuboid(Cuboid),
+    Disc(Disc),
+    Quad(Quad),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Torus(Torus),
+    Transformed(Transformed),
+    Instance(Instance),
+    Moving(Moving),
 }
 
 impl Object {
-    pub fn color(&self) -> Color {
+    /// Name of the shape, as written in scene files.
+    pub fn kind(&self) -> &'static str {
         match self {
-            Self::Plane(o) => o.color(),
-            Self::Sphere(o) => o.color(),
+            Self::Plane(o) => o.kind(),
+            Self::Sphere(o) => o.kind(),
+            Self::Triangle(o) => o.kind(),
+            Self::Cuboid(o) => o.kind(),
+            Self::Disc(o) => o.kind(),
+            Self::Quad(o) => o.kind(),
+            Self::Cylinder(o) => o.kind(),
+            Self::Cone(o) => o.kind(),
+            Self::Torus(o) => o.kind(),
+            Self::Transformed(o) => o.object.kind(),
+            Self::Instance(_) => "instance",
+            Self::Moving(o) => o.object.kind(),
         }
     }
 
-    pub fn albedo(&self) -> f32 {
+    /// `self` placed in the scene by `transform`.
+    pub fn transformed(self, transform: Transform) -> Object {
+        match self {
+            Self::Instance(o) => Instance {
+                transform: transform * o.transform,
+                ..o
+            }
+            .into(),
+            // keeps a single level of indirection
+            Self::Transformed(o) => Transformed {
+                transform: transform * o.transform,
+                object: o.object,
+            }
+            .into(),
+            Self::Moving(o) => Moving {
+                transforms: o.transforms.iter().map(|t| transform * *t).collect(),
+                object: o.object,
+            }
+            .into(),
+            object => Transformed {
+                transform,
+                object: Box::new(object),
+            }
+            .into(),
+        }
+    }
+
+    /// Material of the object, `None` for instances shaded through the
+    /// primitive found by `Object::hit`.
+    pub fn material(&self) -> Option<&Material> {
         match self {
-            Self::Plane(o) => o.albedo(),
-            Self::Sphere(o) => o.albedo(),
+            Self::Plane(o) => Some(o.material()),
+            Self::Sphere(o) => Some(o.material()),
+            Self::Triangle(o) => Some(o.material()),
+            Self::Cuboid(o) => Some(o.material()),
+            Self::Disc(o) => Some(o.material()),
+            Self::Quad(o) => Some(o.material()),
+            Self::Cylinder(o) => Some(o.material()),
+            Self::Cone(o) => Some(o.material()),
+            Self::Torus(o) => Some(o.material()),
+            Self::Transformed(o) => o.material(),
+            Self::Instance(o) => o.material.as_ref(),
+            Self::Moving(o) => o.object.material(),
+        }
+    }
+
+    /// Untextured color.
+    pub fn color(&self) -> Color {
+        self.material().map_or(Color::default(), |m| m.color)
+    }
+
+    /// Untextured albedo.
+    pub fn albedo(&self) -> f32 {
+        self.material().map_or(0.0, |m| m.albedo)
+    }
+
+    /// Replaces the materials for which `replace` gives a new one, going
+    /// down into transformed, moving and instanced objects, and returns
+    /// whether any was. Instances without an override get their own copy of
+    /// the geometry.
+    pub fn replace_materials(&mut self, replace: &dyn Fn(&Material) -> Option<Material>) -> bool {
+        let material = match self {
+            Self::Plane(o) => &mut o.material,
+            Self::Sphere(o) => &mut o.material,
+            Self::Triangle(o) => &mut o.material,
+            Self::Cuboid(o) => &mut o.material,
+            Self::Disc(o) => &mut o.material,
+            Self::Quad(o) => &mut o.material,
+            Self::Cylinder(o) => &mut o.cone.material,
+            Self::Cone(o) => &mut o.material,
+            Self::Torus(o) => &mut o.material,
+            Self::Transformed(o) => return o.object.replace_materials(replace),
+            Self::Moving(o) => return o.object.replace_materials(replace),
+            Self::Instance(Instance {
+                material: Some(material),
+                ..
+            }) => material,
+            Self::Instance(o) => {
+                let mut objects = o.geometry.objects().to_vec();
+                let mut replaced = false;
+                for object in &mut objects {
+                    replaced |= object.replace_materials(replace);
+                }
+                if replaced {
+                    o.geometry = Arc::new(Geometry::new(objects));
+                }
+                return replaced;
+            }
+        };
+        match replace(material) {
+            Some(replacement) => {
+                *material = replacement;
+                true
+            }
+            None => false,
         }
     }
 
@@ -36,15 +206,58 @@ impl Object {
         match self {
             Self::Plane(o) => o.intercept(ray),
             Self::Sphere(o) => o.intercept(ray),
+            Self::Triangle(o) => o.intercept(ray),
+            Self::Cuboid(o) => o.intercept(ray),
+            Self::Disc(o) => o.intercept(ray),
+            Self::Quad(o) => o.intercept(ray),
+            Self::Cylinder(o) => o.intercept(ray),
+            Self::Cone(o) => o.intercept(ray),
+            Self::Torus(o) => o.intercept(ray),
+            Self::Transformed(o) => o.intercept(ray),
+            Self::Instance(o) => o.intercept(ray),
+            Self::Moving(o) => o.hit(ray).map(|i| i.distance),
         }
     }
 
-    pub fn compute_normal(&self, hitpoint: Vertex) -> Vector {
+    pub fn bounds(&self) -> Option<Aabb> {
         match self {
-            Self::Plane(o) => o.compute_normal(hitpoint),
-            Self::Sphere(o) => o.compute_normal(hitpoint),
+            Self::Plane(o) => o.bounds(),
+            Self::Sphere(o) => o.bounds(),
+            Self::Triangle(o) => o.bounds(),
+            Self::Cuboid(o) => o.bounds(),
+            Self::Disc(o) => o.bounds(),
+            Self::Quad(o) => o.bounds(),
+            Self::Cylinder(o) => o.bounds(),
+            Self::Cone(o) => o.bounds(),
+            Self::Torus(o) => o.bounds(),
+            Self::Transformed(o) => o.bounds(),
+            Self::Instance(o) => o.bounds(),
+            Self::Moving(o) => o.bounds(),
         }
     }
+
+    /// Closest interception along `ray`, going down into instances to find
+    /// the primitive hit.
+    pub fn hit(&self, ray: &Ray) -> Option<Interception<'_>> {
+        let primitive: &dyn RenderableObject = match self {
+            Self::Plane(o) => o,
+            Self::Sphere(o) => o,
+            Self::Triangle(o) => o,
+            Self::Cuboid(o) => o,
+            Self::Disc(o) => o,
+            Self::Quad(o) => o,
+            Self::Cylinder(o) => o,
+            Self::Cone(o) => o,
+            Self::Torus(o) => o,
+            Self::Transformed(o) => return o.hit(ray),
+            Self::Instance(o) => return o.hit(ray),
+            Self::Moving(o) => return o.hit(ray),
+        };
+        let (distance, face) = primitive.intercept_face(ray)?;
+        let mut interception = Interception::new(primitive, ray, distance);
+        interception.face = face;
+        Some(interception)
+    }
 }
 
 impl From<Plane> for Object {
@@ -58,21 +271,82 @@ impl From<Sphere> for Object {
         Object::Sphere(o)
     }
 }
-#[derive(Copy, Clone)]
+
+impl From<Triangle> for Object {
+    fn from(o: Triangle) -> Object {
+        Object::Triangle(o)
+    }
+}
+
+impl From<Cuboid> for Object {
+    fn from(o: Cuboid) -> Object {
+        Object::Cuboid(o)
+    }
+}
+
+impl From<Disc> for Object {
+    fn from(o: Disc) -> Object {
+        Object::Disc(o)
+    }
+}
+
+impl From<Quad> for Object {
+    fn from(o: Quad) -> Object {
+        Object::Quad(o)
+    }
+}
+
+impl From<Cylinder> for Object {
+    fn from(o: Cylinder) -> Object {
+        Object::Cylinder(o)
+    }
+}
+
+impl From<Cone> for Object {
+    fn from(o: Cone) -> Object {
+        Object::Cone(o)
+    }
+}
+
+impl From<Torus> for Object {
+    fn from(o: Torus) -> Object {
+        Object::Torus(o)
+    }
+}
+
+impl From<Instance> for Object {
+    fn from(o: Instance) -> Object {
+        Object::Instance(o)
+    }
+}
+
+impl From<Transformed> for Object {
+    fn from(o: Transformed) -> Object {
+        Object::Transformed(o)
+    }
+}
+
+impl From<Moving> for Object {
+    fn from(o: Moving) -> Object {
+        Object::Moving(o)
+    }
+}
+/// Plane textured in scene units, along two directions perpendicular to its
+/// normal.
+#[derive(Clone)]
 pub struct Plane {
     pub point: Vertex,
     pub normal: Vector,
-    pub base_color: Color,
-    pub base_albedo: f32,
+    pub material: Material,
 }
 
 impl RenderableObject for Plane {
-    fn color(&self) -> Color {
-        self.base_color
+    fn kind(&self) -> &'static str {
+        "plane"
     }
 
-    fn albedo(&self) -> f32 {
-        self.base_albedo
+    fn material(&self) -> &Material {
+        &self.material
     }
 
     fn intercept(&self, ray: &Ray) -> Option<f32> {
@@ -80,33 +354,55 @@ impl RenderableObject for Plane {
         if denom.is_sign_positive() {
             let v = Vector::from_vertices(ray.origin, self.point);
             let distance = v.dot(self.normal) / denom;
-            if distance >= 0.0 {
+            // rays parallel to the plane, as orthographic ones can be, miss it
+            if distance >= 0.0 && distance.is_finite() {
                 return Some(distance);
             }
         }
         None
     }
 
-    fn compute_normal(&self, _: Vertex) -> Vector {
+    fn compute_normal(&self, _: Vertex, _: usize) -> Vector {
         -self.normal
     }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let (dpdu, dpdv) = orthonormal_basis(self.normal.normalize());
+        let offset = Vector::from_vertices(self.point, hitpoint);
+        Surface {
+            uv: Uv {
+                u: offset.dot(dpdu),
+                v: offset.dot(dpdv),
+            },
+            dpdu,
+            dpdv,
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
 }
 
-#[derive(Copy, Clone)]
+/// Sphere textured by longitude and latitude, `u` going around the vertical
+/// axis from the `+x` direction and `v` from the south pole to the north
+/// one.
+#[derive(Clone)]
 pub struct Sphere {
     pub center: Vertex,
     pub radius: f32,
-    pub base_color: Color,
-    pub base_albedo: f32,
+    pub material: Material,
 }
 
 impl RenderableObject for Sphere {
-    fn color(&self) -> Color {
-        self.base_color
+    fn kind(&self) -> &'static str {
+        "sphere"
     }
 
-    fn albedo(&self) -> f32 {
-        self.base_albedo
+    fn material(&self) -> &Material {
+        &self.material
     }
 
     fn intercept(&self, ray: &Ray) -> Option<f32> {
@@ -137,7 +433,1396 @@ impl RenderableObject for Sphere {
         }
     }
 
-    fn compute_normal(&self, hitpoint: Vertex) -> Vector {
+    fn compute_normal(&self, hitpoint: Vertex, _: usize) -> Vector {
         Vector::from_vertices(self.center, hitpoint).normalize()
     }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let d = Vector::from_vertices(self.center, hitpoint) / self.radius;
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let phi = d.z.atan2(d.x).rem_euclid(2.0 * PI);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let r = self.radius;
+        Surface {
+            uv: Uv {
+                u: phi / (2.0 * PI),
+                v: 1.0 - theta / PI,
+            },
+            dpdu: Vector {
+                x: -sin_theta * sin_phi,
+                y: 0.0,
+                z: sin_theta * cos_phi,
+            } * (2.0 * PI * r),
+            dpdv: Vector {
+                x: cos_theta * cos_phi,
+                y: -sin_theta,
+                z: cos_theta * sin_phi,
+            } * (-PI * r),
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vector {
+            x: self.radius,
+            y: self.radius,
+            z: self.radius,
+        };
+        Some(Aabb::new(self.center + -r, self.center + r))
+    }
+}
+
+/// Triangle facing the side from which its vertices are seen
+/// counterclockwise.
+#[derive(Clone)]
+pub struct Triangle {
+    pub vertices: [Vertex; 3],
+    /// Texture coordinates of the vertices.
+    pub uvs: [Uv; 3],
+    pub material: Material,
+}
+
+impl Triangle {
+    /// Triangle mapped to the lower left half of the unit texture square.
+    pub fn new(vertices: [Vertex; 3], material: Material) -> Triangle {
+        let uv = |u, v| Uv { u, v };
+        Triangle {
+            vertices,
+            uvs: [uv(0.0, 0.0), uv(1.0, 0.0), uv(0.0, 1.0)],
+            material,
+        }
+    }
+}
+
+impl RenderableObject for Triangle {
+    fn kind(&self) -> &'static str {
+        "triangle"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Möller-Trumbore intersection.
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        let [a, b, c] = self.vertices;
+        let (ab, ac) = (Vector::from_vertices(a, b), Vector::from_vertices(a, c));
+        let p = ray.direction.cross(ac);
+        let determinant = ab.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let t = Vector::from_vertices(a, ray.origin);
+        let u = t.dot(p) / determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t.cross(ab);
+        let v = ray.direction.dot(q) / determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = ac.dot(q) / determinant;
+        if distance > 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    fn compute_normal(&self, _: Vertex, _: usize) -> Vector {
+        let [a, b, c] = self.vertices;
+        Vector::from_vertices(a, b)
+            .cross(Vector::from_vertices(a, c))
+            .normalize()
+    }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let [a, b, c] = self.vertices;
+        let (ab, ac) = (Vector::from_vertices(a, b), Vector::from_vertices(a, c));
+        let ap = Vector::from_vertices(a, hitpoint);
+        // barycentric coordinates of the hitpoint
+        let normal = ab.cross(ac);
+        let area = normal.dot(normal);
+        let wb = ap.cross(ac).dot(normal) / area;
+        let wc = ab.cross(ap).dot(normal) / area;
+        let [ta, tb, tc] = self.uvs;
+        let uv = Uv {
+            u: ta.u + (tb.u - ta.u) * wb + (tc.u - ta.u) * wc,
+            v: ta.v + (tb.v - ta.v) * wb + (tc.v - ta.v) * wc,
+        };
+        // solves ab and ac for the derivatives along the uv edges
+        let (du1, dv1) = (tb.u - ta.u, tb.v - ta.v);
+        let (du2, dv2) = (tc.u - ta.u, tc.v - ta.v);
+        let determinant = du1 * dv2 - dv1 * du2;
+        let (dpdu, dpdv) = if determinant.abs() < 1e-12 {
+            orthonormal_basis(normal.normalize())
+        } else {
+            (
+                (ab * dv2 - ac * dv1) / determinant,
+                (ac * du1 - ab * du2) / determinant,
+            )
+        };
+        Surface {
+            uv,
+            dpdu,
+            dpdv,
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let [a, b, c] = self.vertices;
+        Some(Aabb::new(a, b).include(c))
+    }
+}
+
+/// Orthonormal axes placed at `origin`, in which shapes are intersected,
+/// round ones turning around the `y` axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub origin: Vertex,
+    pub x: Vector,
+    pub y: Vector,
+    pub z: Vector,
+}
+
+impl Frame {
+    /// Frame with its `y` axis along `axis` and its `x` axis along the
+    /// scene's one as far as possible, the scene's `z` axis when `axis` is
+    /// close to `x`.
+    pub fn new(origin: Vertex, axis: Vector) -> Frame {
+        let y = axis.normalize();
+        let reference = if y.x.abs() > 0.9 {
+            Vector {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            }
+        } else {
+            Vector {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+        };
+        let x = (reference - y * reference.dot(y)).normalize();
+        Frame {
+            origin,
+            x,
+            y,
+            z: x.cross(y),
+        }
+    }
+
+    /// Coordinates of a point of the scene.
+    fn local(&self, p: Vertex) -> Vector {
+        self.local_vector(Vector::from_vertices(self.origin, p))
+    }
+
+    fn local_vector(&self, v: Vector) -> Vector {
+        Vector {
+            x: v.dot(self.x),
+            y: v.dot(self.y),
+            z: v.dot(self.z),
+        }
+    }
+
+    /// Vector of the scene from local coordinates.
+    fn vector(&self, v: Vector) -> Vector {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    /// Box of the scene containing the local box from `min` to `max`.
+    fn bounds(&self, min: Vector, max: Vector) -> Aabb {
+        let corner = |i: usize| {
+            let pick = |bit: usize, min: f32, max: f32| if i & bit == 0 { min } else { max };
+            self.origin
+                + self.vector(Vector {
+                    x: pick(1, min.x, max.x),
+                    y: pick(2, min.y, max.y),
+                    z: pick(4, min.z, max.z),
+                })
+        };
+        (1..8).fold(Aabb::new(corner(0), corner(0)), |b, i| b.include(corner(i)))
+    }
+}
+
+/// Closest distance in front of the ray among `distances`.
+fn nearest(distances: impl IntoIterator<Item = f32>) -> Option<f32> {
+    distances
+        .into_iter()
+        .filter(|&d| d > 0.0 && d.is_finite())
+        .min_by(f32::total_cmp)
+}
+
+/// Distances along the local ray to the plane `y = height`, within `radius`
+/// of the axis.
+fn cap(origin: Vector, direction: Vector, height: f32, radius: f32) -> Option<f32> {
+    let t = (height - origin.y) / direction.y;
+    let (x, z) = (origin.x + t * direction.x, origin.z + t * direction.z);
+    if x * x + z * z <= radius * radius {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Texture coordinates of a point of a disc of `radius` around the local
+/// `y` axis, `u` going around from `+x` and `v` outward from the center.
+fn polar_surface(frame: &Frame, local: Vector, radius: f32) -> Surface {
+    let phi = local.z.atan2(local.x).rem_euclid(2.0 * PI);
+    let rho = (local.x * local.x + local.z * local.z).sqrt();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    Surface {
+        uv: Uv {
+            u: phi / (2.0 * PI),
+            v: rho / radius,
+        },
+        dpdu: frame.vector(Vector {
+            x: -local.z,
+            y: 0.0,
+            z: local.x,
+        }) * (2.0 * PI),
+        dpdv: frame.vector(Vector {
+            x: cos_phi,
+            y: 0.0,
+            z: sin_phi,
+        }) * radius,
+        ..Surface::default()
+    }
+}
+
+/// Box, axis-aligned when its frame is the scene's, each face mapped to the
+/// unit texture square.
+#[derive(Clone)]
+pub struct Cuboid {
+    /// Center and axes of the box.
+    pub frame: Frame,
+    /// Half the size along each axis.
+    pub extents: Vector,
+    pub material: Material,
+}
+
+impl Cuboid {
+    /// Box with sides along the axes of the scene.
+    pub fn aligned(min: Vertex, max: Vertex, material: Material) -> Cuboid {
+        let half = Vector::from_vertices(min, max) / 2.0;
+        Cuboid {
+            frame: Frame {
+                origin: min + half,
+                x: Vector {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                y: Vector {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+                z: Vector {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 1.0,
+                },
+            },
+            extents: half,
+            material,
+        }
+    }
+
+    /// Axis of the face of a local point, with its side.
+    fn face(&self, local: Vector) -> (usize, f32) {
+        let ratios = [
+            local.x / self.extents.x,
+            local.y / self.extents.y,
+            local.z / self.extents.z,
+        ];
+        let axis = (0..3)
+            .max_by(|&a, &b| ratios[a].abs().total_cmp(&ratios[b].abs()))
+            .unwrap_or(0);
+        (axis, ratios[axis].signum())
+    }
+}
+
+impl RenderableObject for Cuboid {
+    fn kind(&self) -> &'static str {
+        "box"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Slabs intersection.
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        let origin = self.frame.local(ray.origin);
+        let direction = self.frame.local_vector(ray.direction);
+        let (mut near, mut far) = (f32::NEG_INFINITY, f32::INFINITY);
+        for (o, d, e) in [
+            (origin.x, direction.x, self.extents.x),
+            (origin.y, direction.y, self.extents.y),
+            (origin.z, direction.z, self.extents.z),
+        ] {
+            if d == 0.0 {
+                if o.abs() > e {
+                    return None;
+                }
+                continue;
+            }
+            let (t1, t2) = ((-e - o) / d, (e - o) / d);
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+        if near > far {
+            return None;
+        }
+        nearest([near, far])
+    }
+
+    fn compute_normal(&self, hitpoint: Vertex, _: usize) -> Vector {
+        let (axis, side) = self.face(self.frame.local(hitpoint));
+        [self.frame.x, self.frame.y, self.frame.z][axis] * side
+    }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let local = self.frame.local(hitpoint);
+        let (axis, _) = self.face(local);
+        // u along the first other axis and v along the second one
+        let (u, v) = match axis {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let coords = [local.x, local.y, local.z];
+        let extents = [self.extents.x, self.extents.y, self.extents.z];
+        let axes = [self.frame.x, self.frame.y, self.frame.z];
+        Surface {
+            uv: Uv {
+                u: 0.5 + 0.5 * coords[u] / extents[u],
+                v: 0.5 + 0.5 * coords[v] / extents[v],
+            },
+            dpdu: axes[u] * (2.0 * extents[u]),
+            dpdv: axes[v] * (2.0 * extents[v]),
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.frame.bounds(-self.extents, self.extents))
+    }
+}
+
+/// Disc facing the side of its `normal`, seen from both.
+#[derive(Clone)]
+pub struct Disc {
+    /// Center and normal, as the `y` axis.
+    pub frame: Frame,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Disc {
+    pub fn new(center: Vertex, normal: Vector, radius: f32, material: Material) -> Disc {
+        Disc {
+            frame: Frame::new(center, normal),
+            radius,
+            material,
+        }
+    }
+}
+
+impl RenderableObject for Disc {
+    fn kind(&self) -> &'static str {
+        "disc"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        let origin = self.frame.local(ray.origin);
+        let direction = self.frame.local_vector(ray.direction);
+        nearest(cap(origin, direction, 0.0, self.radius))
+    }
+
+    fn compute_normal(&self, _: Vertex, _: usize) -> Vector {
+        self.frame.y
+    }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        Surface {
+            point: hitpoint,
+            ..polar_surface(&self.frame, self.frame.local(hitpoint), self.radius)
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(self.frame.bounds(
+            Vector {
+                x: -r,
+                y: 0.0,
+                z: -r,
+            },
+            Vector { x: r, y: 0.0, z: r },
+        ))
+    }
+}
+
+/// Parallelogram spanned by two edges from a corner, rectangles having
+/// perpendicular ones, mapped to the unit texture square and facing the
+/// side from which the edges are seen counterclockwise.
+#[derive(Clone)]
+pub struct Quad {
+    pub corner: Vertex,
+    /// Edges along `u` and `v`.
+    pub edges: [Vector; 2],
+    pub material: Material,
+}
+
+impl Quad {
+    /// Coordinates of a point of the plane of the quad along its edges.
+    fn coordinates(&self, p: Vertex) -> (f32, f32) {
+        let [a, b] = self.edges;
+        let normal = a.cross(b);
+        let offset = Vector::from_vertices(self.corner, p);
+        let area = normal.dot(normal);
+        (
+            offset.cross(b).dot(normal) / area,
+            a.cross(offset).dot(normal) / area,
+        )
+    }
+}
+
+impl RenderableObject for Quad {
+    fn kind(&self) -> &'static str {
+        "quad"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        let normal = self.edges[0].cross(self.edges[1]);
+        let t =
+            Vector::from_vertices(ray.origin, self.corner).dot(normal) / ray.direction.dot(normal);
+        let (u, v) = self.coordinates(ray.origin + ray.direction * t);
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            nearest([t])
+        } else {
+            None
+        }
+    }
+
+    fn compute_normal(&self, _: Vertex, _: usize) -> Vector {
+        self.edges[0].cross(self.edges[1]).normalize()
+    }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let (u, v) = self.coordinates(hitpoint);
+        Surface {
+            uv: Uv { u, v },
+            dpdu: self.edges[0],
+            dpdv: self.edges[1],
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let [a, b] = self.edges;
+        let c = self.corner;
+        Some(Aabb::new(c, c + a).include(c + b).include(c + a + b))
+    }
+}
+
+/// Cone from a base disc of `radius` to a top one of `top_radius` at
+/// `height` along the frame's `y` axis, truncated when the top radius is
+/// not zero, closed by the discs when `capped`.
+///
+/// The side is textured like a sphere, `u` going around from `+x` and `v`
+/// up from the base, the caps like discs.
+#[derive(Clone)]
+pub struct Cone {
+    /// Center of the base and axis, as the `y` axis.
+    pub frame: Frame,
+    pub radius: f32,
+    pub top_radius: f32,
+    pub height: f32,
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Cone {
+    /// Capped cone from the center of its `base` to its apex or the center
+    /// of its top.
+    pub fn new(
+        base: Vertex,
+        top: Vertex,
+        radius: f32,
+        top_radius: f32,
+        material: Material,
+    ) -> Cone {
+        let axis = Vector::from_vertices(base, top);
+        Cone {
+            frame: Frame::new(base, axis),
+            radius,
+            top_radius,
+            height: axis.norm(),
+            capped: true,
+            material,
+        }
+    }
+
+    /// Faces of the cone, the caps meeting the side along their rims.
+    const SIDE: usize = 0;
+    const BASE: usize = 1;
+    const TOP: usize = 2;
+
+    /// Change of the radius along the axis.
+    fn slope(&self) -> f32 {
+        (self.top_radius - self.radius) / self.height
+    }
+}
+
+impl RenderableObject for Cone {
+    fn kind(&self) -> &'static str {
+        "cone"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        self.intercept_face(ray).map(|(distance, _)| distance)
+    }
+
+    fn intercept_face(&self, ray: &Ray) -> Option<(f32, usize)> {
+        let o = self.frame.local(ray.origin);
+        let d = self.frame.local_vector(ray.direction);
+        // x² + z² = (r + k y)², within the height
+        let k = self.slope();
+        let r = self.radius + k * o.y;
+        let a = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z - k * r * d.y);
+        let c = o.x * o.x + o.z * o.z - r * r;
+        let side = match solve_quadratic(a, b, c) {
+            QuadraticSolution::Two(t1, t2) => vec![t1, t2],
+            QuadraticSolution::One(t) => vec![t],
+            QuadraticSolution::None => Vec::new(),
+        };
+        let side = side
+            .into_iter()
+            .filter(|&t| {
+                let y = o.y + t * d.y;
+                (0.0..=self.height).contains(&y)
+            })
+            .map(|t| (t, Self::SIDE));
+        let caps = if self.capped {
+            vec![
+                cap(o, d, 0.0, self.radius).map(|t| (t, Self::BASE)),
+                cap(o, d, self.height, self.top_radius).map(|t| (t, Self::TOP)),
+            ]
+        } else {
+            Vec::new()
+        };
+        side.chain(caps.into_iter().flatten())
+            .filter(|&(t, _)| t > 0.0 && t.is_finite())
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    fn compute_normal(&self, hitpoint: Vertex, face: usize) -> Vector {
+        let local = self.frame.local(hitpoint);
+        match face {
+            Self::BASE => -self.frame.y,
+            Self::TOP => self.frame.y,
+            _ => {
+                let k = self.slope();
+                let radial = Vector {
+                    x: local.x,
+                    y: 0.0,
+                    z: local.z,
+                }
+                .normalize();
+                // the radius shrinking upward tilts the side up
+                self.frame.vector(Vector { y: -k, ..radial }).normalize()
+            }
+        }
+    }
+
+    fn surface(&self, hitpoint: Vertex, face: usize) -> Surface {
+        let local = self.frame.local(hitpoint);
+        let surface = match face {
+            Self::BASE => polar_surface(&self.frame, local, self.radius),
+            Self::TOP => polar_surface(&self.frame, local, self.top_radius),
+            _ => {
+                let phi = local.z.atan2(local.x).rem_euclid(2.0 * PI);
+                let (sin_phi, cos_phi) = phi.sin_cos();
+                let k = self.slope();
+                Surface {
+                    uv: Uv {
+                        u: phi / (2.0 * PI),
+                        v: local.y / self.height,
+                    },
+                    dpdu: self.frame.vector(Vector {
+                        x: -local.z,
+                        y: 0.0,
+                        z: local.x,
+                    }) * (2.0 * PI),
+                    dpdv: self.frame.vector(Vector {
+                        x: k * cos_phi,
+                        y: 1.0,
+                        z: k * sin_phi,
+                    }) * self.height,
+                    ..Surface::default()
+                }
+            }
+        };
+        Surface {
+            point: hitpoint,
+            ..surface
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius.max(self.top_radius);
+        Some(self.frame.bounds(
+            Vector {
+                x: -r,
+                y: 0.0,
+                z: -r,
+            },
+            Vector {
+                x: r,
+                y: self.height,
+                z: r,
+            },
+        ))
+    }
+}
+
+/// Cylinder of `radius` around the frame's `y` axis, from its base up to
+/// `height`, textured like a cone.
+#[derive(Clone)]
+pub struct Cylinder {
+    pub cone: Cone,
+}
+
+impl Cylinder {
+    /// Capped cylinder between the centers of its `base` and its `top`.
+    pub fn new(base: Vertex, top: Vertex, radius: f32, material: Material) -> Cylinder {
+        Cylinder {
+            cone: Cone::new(base, top, radius, radius, material),
+        }
+    }
+}
+
+impl RenderableObject for Cylinder {
+    fn kind(&self) -> &'static str {
+        "cylinder"
+    }
+
+    fn material(&self) -> &Material {
+        &self.cone.material
+    }
+
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        self.cone.intercept(ray)
+    }
+
+    fn intercept_face(&self, ray: &Ray) -> Option<(f32, usize)> {
+        self.cone.intercept_face(ray)
+    }
+
+    fn compute_normal(&self, hitpoint: Vertex, face: usize) -> Vector {
+        self.cone.compute_normal(hitpoint, face)
+    }
+
+    fn surface(&self, hitpoint: Vertex, face: usize) -> Surface {
+        self.cone.surface(hitpoint, face)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.cone.bounds()
+    }
+}
+
+/// Ring of a tube of `minor_radius` around a circle of `major_radius`
+/// around the frame's `y` axis, `u` going around the axis from `+x` and `v`
+/// around the tube from the outside.
+#[derive(Clone)]
+pub struct Torus {
+    /// Center and axis, as the `y` axis.
+    pub frame: Frame,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vertex,
+        axis: Vector,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Material,
+    ) -> Torus {
+        Torus {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    /// Point of the central circle closest to a local point.
+    fn ring_point(&self, local: Vector) -> Vector {
+        let radial = Vector {
+            x: local.x,
+            y: 0.0,
+            z: local.z,
+        }
+        .normalize();
+        radial * self.major_radius
+    }
+}
+
+impl RenderableObject for Torus {
+    fn kind(&self) -> &'static str {
+        "torus"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn intercept(&self, ray: &Ray) -> Option<f32> {
+        let o = self.frame.local(ray.origin);
+        let d = self.frame.local_vector(ray.direction);
+        let (big, small) = (self.major_radius as f64, self.minor_radius as f64);
+        // starting from the bounding sphere for the roots to stay precise
+        let bound = (big + small) as f32;
+        let start = match solve_quadratic(d.dot(d), 2.0 * o.dot(d), o.dot(o) - bound * bound) {
+            QuadraticSolution::Two(t1, t2) if t1.max(t2) > 0.0 => t1.min(t2).max(0.0),
+            _ => return None,
+        };
+        let o = o + d * start;
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        // (|p|² + R² - r²)² = 4R² (x² + z²)
+        let alpha = dx * dx + dy * dy + dz * dz;
+        let beta = 2.0 * (ox * dx + oy * dy + oz * dz);
+        let gamma = ox * ox + oy * oy + oz * oz + big * big - small * small;
+        let four = 4.0 * big * big;
+        let roots = solve_quartic(
+            alpha * alpha,
+            2.0 * alpha * beta,
+            beta * beta + 2.0 * alpha * gamma - four * (dx * dx + dz * dz),
+            2.0 * beta * gamma - 2.0 * four * (ox * dx + oz * dz),
+            gamma * gamma - four * (ox * ox + oz * oz),
+        );
+        nearest(roots.into_iter().map(|t| start + t as f32))
+    }
+
+    fn compute_normal(&self, hitpoint: Vertex, _: usize) -> Vector {
+        let local = self.frame.local(hitpoint);
+        self.frame
+            .vector(local - self.ring_point(local))
+            .normalize()
+    }
+
+    fn surface(&self, hitpoint: Vertex, _: usize) -> Surface {
+        let local = self.frame.local(hitpoint);
+        let phi = local.z.atan2(local.x).rem_euclid(2.0 * PI);
+        let rho = (local.x * local.x + local.z * local.z).sqrt();
+        let theta = local.y.atan2(rho - self.major_radius).rem_euclid(2.0 * PI);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let r = self.minor_radius;
+        Surface {
+            uv: Uv {
+                u: phi / (2.0 * PI),
+                v: theta / (2.0 * PI),
+            },
+            dpdu: self.frame.vector(Vector {
+                x: -local.z,
+                y: 0.0,
+                z: local.x,
+            }) * (2.0 * PI),
+            dpdv: self.frame.vector(Vector {
+                x: -r * sin_theta * cos_phi,
+                y: r * cos_theta,
+                z: -r * sin_theta * sin_phi,
+            }) * (2.0 * PI),
+            point: hitpoint,
+            ..Surface::default()
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (big, small) = (self.major_radius + self.minor_radius, self.minor_radius);
+        Some(self.frame.bounds(
+            Vector {
+                x: -big,
+                y: -small,
+                z: -big,
+            },
+            Vector {
+                x: big,
+                y: small,
+                z: big,
+            },
+        ))
+    }
+}
+
+/// Object intersected in its own space, `transform` mapping it to the scene.
+#[derive(Clone)]
+pub struct Transformed {
+    pub transform: Transform,
+    pub object: Box<Object>,
+}
+
+impl Transformed {
+    /// Material of the object, `None` when it is an instance shaded through
+    /// its geometry, see [`Object::material`].
+    pub fn material(&self) -> Option<&Material> {
+        self.object.material()
+    }
+
+    /// Closest interception along `ray`, in the space of the object.
+    pub fn hit(&self, ray: &Ray) -> Option<Interception<'_>> {
+        // the direction is not normalized so that distances stay the same
+        let inverse = self.transform.inverse();
+        let local = Ray {
+            kind: ray.kind.clone(),
+            origin: inverse.point(ray.origin),
+            direction: inverse.vector(ray.direction),
+            time: ray.time,
+            differentials: None,
+        };
+        let mut interception = self.object.hit(&local)?;
+        interception.hitpoint = ray.origin + ray.direction * interception.distance;
+        interception.transform = Some(match interception.transform {
+            Some(inner) => self.transform * inner,
+            None => self.transform,
+        });
+        Some(interception)
+    }
+
+    pub fn intercept(&self, ray: &Ray) -> Option<f32> {
+        self.hit(ray).map(|i| i.distance)
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.object.bounds().map(|b| b.transformed(&self.transform))
+    }
+}
+
+/// Objects shared between instances, with their own hierarchy.
+pub struct Geometry {
+    objects: Vec<Object>,
+    bvh: Bvh,
+}
+
+impl Geometry {
+    pub fn new(objects: Vec<Object>) -> Geometry {
+        let bounds: Vec<_> = objects.iter().map(Object::bounds).collect();
+        Geometry {
+            bvh: Bvh::build(&bounds),
+            objects,
+        }
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+
+    pub fn hit(&self, ray: &Ray) -> Option<Interception<'_>> {
+        self.bvh
+            .closest(ray, |index| {
+                self.objects[index].hit(ray).map(|i| (i.distance, i))
+            })
+            .map(|(_, i)| i)
+    }
+}
+
+/// Copy of a shared geometry placed by `transform`, only costing the
+/// transform and the optional material replacing the geometry ones.
+#[derive(Clone)]
+pub struct Instance {
+    pub geometry: Arc<Geometry>,
+    pub transform: Transform,
+    pub material: Option<Material>,
+}
+
+impl Instance {
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        let inverse = self.transform.inverse();
+        Ray {
+            kind: ray.kind.clone(),
+            origin: inverse.point(ray.origin),
+            direction: inverse.vector(ray.direction),
+            time: ray.time,
+            differentials: None,
+        }
+    }
+
+    /// Interception with the primitive hit in the geometry, which shading
+    /// sees through the instance transform and material.
+    pub fn hit(&self, ray: &Ray) -> Option<Interception<'_>> {
+        let mut interception = self.geometry.hit(&self.local_ray(ray))?;
+        interception.hitpoint = ray.origin + ray.direction * interception.distance;
+        interception.transform = Some(match interception.transform {
+            Some(inner) => self.transform * inner,
+            None => self.transform,
+        });
+        if self.material.is_some() {
+            interception.material = self.material.as_ref();
+        }
+        Some(interception)
+    }
+
+    pub fn intercept(&self, ray: &Ray) -> Option<f32> {
+        self.hit(ray).map(|i| i.distance)
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.geometry
+            .bounds()
+            .map(|b| b.transformed(&self.transform))
+    }
+}
+
+/// Object moving over the shutter interval, placed by `transforms` at
+/// evenly spaced times from its opening to its closing and in between by
+/// interpolating them.
+#[derive(Clone)]
+pub struct Moving {
+    pub transforms: Vec<Transform>,
+    pub object: Box<Object>,
+}
+
+impl Moving {
+    /// Placement at `time` of the shutter interval, `None` where the
+    /// interpolated transform is singular.
+    pub fn transform_at(&self, time: f32) -> Option<Transform> {
+        let segments = self.transforms.len() - 1;
+        if segments == 0 {
+            return Some(self.transforms[0]);
+        }
+        let position = time.clamp(0.0, 1.0) * segments as f32;
+        let i = (position as usize).min(segments - 1);
+        self.transforms[i].interpolate(&self.transforms[i + 1], position - i as f32)
+    }
+
+    /// Interception with the object placed where it is at the ray time.
+    pub fn hit(&self, ray: &Ray) -> Option<Interception<'_>> {
+        let transform = self.transform_at(ray.time)?;
+        let inverse = transform.inverse();
+        let local = Ray {
+            kind: ray.kind.clone(),
+            origin: inverse.point(ray.origin),
+            direction: inverse.vector(ray.direction),
+            time: ray.time,
+            differentials: None,
+        };
+        let mut interception = self.object.hit(&local)?;
+        interception.hitpoint = ray.origin + ray.direction * interception.distance;
+        interception.transform = Some(match interception.transform {
+            Some(inner) => transform * inner,
+            None => transform,
+        });
+        Some(interception)
+    }
+
+    /// Box containing the object all along its motion, interpolated points
+    /// staying between their placements at the sampled times.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.object.bounds()?;
+        self.transforms
+            .iter()
+            .map(|t| bounds.transformed(t))
+            .reduce(|a, b| a.union(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex { x, y, z }
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    fn ray(origin: Vertex, x: f32, y: f32, z: f32) -> Ray {
+        Ray {
+            kind: crate::tracer::RayKind::Primary,
+            origin,
+            direction: Vector { x, y, z },
+            time: 0.0,
+            differentials: None,
+        }
+    }
+
+    fn material() -> Material {
+        Material::new(Color::default(), 1.0)
+    }
+
+    fn assert_normal(object: &impl RenderableObject, p: Vertex, x: f32, y: f32, z: f32) {
+        assert_face_normal(object, p, 0, x, y, z);
+    }
+
+    fn assert_face_normal(
+        object: &impl RenderableObject,
+        p: Vertex,
+        face: usize,
+        x: f32,
+        y: f32,
+        z: f32,
+    ) {
+        let normal = object.compute_normal(p, face);
+        assert_near(normal.x, x);
+        assert_near(normal.y, y);
+        assert_near(normal.z, z);
+    }
+
+    /// Normal where `ray` hits `object`, on the face the interception found.
+    fn assert_hit_normal(object: &impl RenderableObject, ray: &Ray, x: f32, y: f32, z: f32) {
+        let (distance, face) = object.intercept_face(ray).unwrap();
+        let p = ray.origin + ray.direction * distance;
+        assert_face_normal(object, p, face, x, y, z);
+    }
+
+    #[test]
+    fn sphere_surface() {
+        let sphere = Sphere {
+            center: vertex(1.0, 0.0, 0.0),
+            radius: 2.0,
+            material: Material::new(Color::default(), 1.0),
+        };
+        let s = sphere.surface(vertex(3.0, 0.0, 0.0), 0);
+        assert_near(s.uv.u, 0.0);
+        assert_near(s.uv.v, 0.5);
+        // around the equator and toward the north pole
+        assert_near(s.dpdu.z, 2.0 * PI * 2.0);
+        assert_near(s.dpdv.y, PI * 2.0);
+        let s = sphere.surface(vertex(1.0, 0.0, 2.0), 0);
+        assert_near(s.uv.u, 0.25);
+        assert_near(sphere.surface(vertex(1.0, 2.0, 0.0), 0).uv.v, 1.0);
+    }
+
+    #[test]
+    fn triangle_surface() {
+        let mut triangle = Triangle::new(
+            [
+                vertex(0.0, 0.0, 0.0),
+                vertex(2.0, 0.0, 0.0),
+                vertex(0.0, 4.0, 0.0),
+            ],
+            Material::new(Color::default(), 1.0),
+        );
+        triangle.uvs[1] = Uv { u: 0.5, v: 0.0 };
+        let s = triangle.surface(vertex(1.0, 1.0, 0.0), 0);
+        assert_near(s.uv.u, 0.25);
+        assert_near(s.uv.v, 0.25);
+        assert_near(s.dpdu.x, 4.0);
+        assert_near(s.dpdv.y, 4.0);
+    }
+
+    #[test]
+    fn cuboid() {
+        let cuboid = Cuboid::aligned(vertex(-1.0, -1.0, -1.0), vertex(1.0, 2.0, 1.0), material());
+        assert_eq!(
+            cuboid.intercept(&ray(vertex(0.0, 0.0, 5.0), 0.0, 0.0, -1.0)),
+            Some(4.0)
+        );
+        // from inside, and missing it
+        assert_eq!(
+            cuboid.intercept(&ray(vertex(0.0, 0.0, 0.0), 1.0, 0.0, 0.0)),
+            Some(1.0)
+        );
+        assert_eq!(
+            cuboid.intercept(&ray(vertex(0.0, 3.0, 5.0), 0.0, 0.0, -1.0)),
+            None
+        );
+        assert_normal(&cuboid, vertex(0.0, 0.0, 1.0), 0.0, 0.0, 1.0);
+        assert_normal(&cuboid, vertex(0.5, -1.0, 0.0), 0.0, -1.0, 0.0);
+        let s = cuboid.surface(vertex(0.0, 0.0, 1.0), 0);
+        assert_near(s.uv.u, 0.5);
+        assert_near(s.uv.v, 1.0 / 3.0);
+        assert_near(s.dpdv.y, 3.0);
+
+        // turned by 45 degrees around y, an edge facing +x
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let turn = |v: Vector| v.rotate(up, crate::math::Radian(PI / 4.0));
+        let mut oriented =
+            Cuboid::aligned(vertex(-1.0, -1.0, -1.0), vertex(1.0, 1.0, 1.0), material());
+        oriented.frame = Frame {
+            x: turn(oriented.frame.x),
+            z: turn(oriented.frame.z),
+            ..oriented.frame
+        };
+        let d = oriented
+            .intercept(&ray(vertex(5.0, 0.0, 0.0), -1.0, 0.0, 0.0))
+            .unwrap();
+        assert_near(d, 5.0 - 2f32.sqrt());
+        assert_near(oriented.bounds().unwrap().max.x, 2f32.sqrt());
+    }
+
+    #[test]
+    fn disc_and_quad() {
+        let disc = Disc::new(
+            vertex(0.0, 0.0, 0.0),
+            Vector {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            1.0,
+            material(),
+        );
+        assert_eq!(
+            disc.intercept(&ray(vertex(0.5, 3.0, 0.0), 0.0, -1.0, 0.0)),
+            Some(3.0)
+        );
+        assert_eq!(
+            disc.intercept(&ray(vertex(0.5, -3.0, 0.0), 0.0, 1.0, 0.0)),
+            Some(3.0)
+        );
+        assert_eq!(
+            disc.intercept(&ray(vertex(1.5, 3.0, 0.0), 0.0, -1.0, 0.0)),
+            None
+        );
+        assert_normal(&disc, vertex(0.5, 0.0, 0.0), 0.0, 1.0, 0.0);
+        assert_near(disc.surface(vertex(0.0, 0.0, 0.5), 0).uv.v, 0.5);
+
+        let quad = Quad {
+            corner: vertex(0.0, 0.0, 0.0),
+            edges: [
+                Vector {
+                    x: 2.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                Vector {
+                    x: 0.0,
+                    y: 1.0,
+                    z: 0.0,
+                },
+            ],
+            material: material(),
+        };
+        assert_eq!(
+            quad.intercept(&ray(vertex(1.5, 0.5, 2.0), 0.0, 0.0, -1.0)),
+            Some(2.0)
+        );
+        assert_eq!(
+            quad.intercept(&ray(vertex(2.5, 0.5, 2.0), 0.0, 0.0, -1.0)),
+            None
+        );
+        assert_normal(&quad, vertex(1.5, 0.5, 0.0), 0.0, 0.0, 1.0);
+        let s = quad.surface(vertex(1.5, 0.5, 0.0), 0);
+        assert_near(s.uv.u, 0.75);
+        assert_near(s.uv.v, 0.5);
+        assert_eq!(quad.bounds().unwrap().max, vertex(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn cylinder_and_cone() {
+        let mut cylinder = Cylinder::new(
+            vertex(0.0, 0.0, 0.0),
+            vertex(0.0, 2.0, 0.0),
+            1.0,
+            material(),
+        );
+        assert_eq!(
+            cylinder.intercept(&ray(vertex(5.0, 1.0, 0.0), -1.0, 0.0, 0.0)),
+            Some(4.0)
+        );
+        assert_normal(&cylinder, vertex(1.0, 1.0, 0.0), 1.0, 0.0, 0.0);
+        assert_near(cylinder.surface(vertex(1.0, 1.0, 0.0), 0).uv.v, 0.5);
+        // through the caps
+        let down = ray(vertex(0.5, 5.0, 0.0), 0.0, -1.0, 0.0);
+        assert_eq!(cylinder.intercept(&down), Some(3.0));
+        assert_hit_normal(&cylinder, &down, 0.0, 1.0, 0.0);
+        let up = ray(vertex(0.5, -3.0, 0.0), 0.0, 1.0, 0.0);
+        assert_eq!(cylinder.intercept(&up), Some(3.0));
+        assert_hit_normal(&cylinder, &up, 0.0, -1.0, 0.0);
+        // grazing the side right by the rims, which are not caps
+        assert_hit_normal(
+            &cylinder,
+            &ray(vertex(5.0, 1e-5, 0.0), -1.0, 0.0, 0.0),
+            1.0,
+            0.0,
+            0.0,
+        );
+        assert_hit_normal(
+            &cylinder,
+            &ray(vertex(5.0, 2.0 - 1e-5, 0.0), -1.0, 0.0, 0.0),
+            1.0,
+            0.0,
+            0.0,
+        );
+        cylinder.cone.capped = false;
+        assert_eq!(
+            cylinder.intercept(&ray(vertex(0.5, 5.0, 0.0), 0.0, -1.0, 0.0)),
+            None
+        );
+        assert_eq!(cylinder.bounds().unwrap().max, vertex(1.0, 2.0, 1.0));
+
+        let cone = Cone::new(
+            vertex(0.0, 0.0, 0.0),
+            vertex(0.0, 2.0, 0.0),
+            1.0,
+            0.0,
+            material(),
+        );
+        let d = cone
+            .intercept(&ray(vertex(5.0, 1.0, 0.0), -1.0, 0.0, 0.0))
+            .unwrap();
+        assert_near(d, 4.5);
+        let (x, y) = (2.0 / 5f32.sqrt(), 1.0 / 5f32.sqrt());
+        assert_normal(&cone, vertex(0.5, 1.0, 0.0), x, y, 0.0);
+        assert_eq!(
+            cone.intercept(&ray(vertex(0.5, 3.0, 0.0), 0.0, -1.0, 0.0)),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn torus() {
+        let torus = Torus::new(
+            vertex(0.0, 0.0, 0.0),
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            2.0,
+            0.5,
+            material(),
+        );
+        let d = torus
+            .intercept(&ray(vertex(10.0, 0.0, 0.0), -1.0, 0.0, 0.0))
+            .unwrap();
+        assert_near(d, 7.5);
+        // far away, and through the hole
+        let d = torus
+            .intercept(&ray(vertex(1000.0, 0.0, 0.0), -2.0, 0.0, 0.0))
+            .unwrap();
+        assert!((d - 498.75).abs() < 1e-3, "{}", d);
+        assert_eq!(
+            torus.intercept(&ray(vertex(0.0, 10.0, 0.0), 0.0, -1.0, 0.0)),
+            None
+        );
+        let d = torus
+            .intercept(&ray(vertex(2.0, 10.0, 0.0), 0.0, -1.0, 0.0))
+            .unwrap();
+        assert_near(d, 9.5);
+        assert_normal(&torus, vertex(2.0, 0.5, 0.0), 0.0, 1.0, 0.0);
+        assert_normal(&torus, vertex(0.0, 0.0, -1.5), 0.0, 0.0, 1.0);
+        let s = torus.surface(vertex(2.0, 0.5, 0.0), 0);
+        assert_near(s.uv.u, 0.0);
+        assert_near(s.uv.v, 0.25);
+        assert_eq!(torus.bounds().unwrap().max, vertex(2.5, 0.5, 2.5));
+    }
+
+    #[test]
+    fn transformed_instance() {
+        let sphere = Sphere {
+            center: vertex(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: material(),
+        };
+        let instance = Instance {
+            geometry: Arc::new(Geometry::new(vec![sphere.into()])),
+            transform: Transform::default(),
+            material: None,
+        };
+        let transformed = Transformed {
+            transform: Transform::new(crate::math::matrix::Matrix4::translation(Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            }))
+            .unwrap(),
+            object: Box::new(instance.into()),
+        };
+        // shaded through the material of the sphere
+        assert!(transformed.material().is_none());
+        let origin = vertex(0.0, 0.0, 0.0);
+        let hit = transformed.hit(&ray(origin, 0.0, 0.0, -1.0)).unwrap();
+        assert_near(hit.distance, 4.0);
+        assert_near(hit.normal().z, 1.0);
+        assert_eq!(hit.material().albedo, 1.0);
+    }
+
+    #[test]
+    fn moving_rotation_keeps_size() {
+        use crate::math::matrix::Matrix4;
+        use crate::math::Radian;
+
+        let sphere = Sphere {
+            center: vertex(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: material(),
+        };
+        let up = Vector {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let offset = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: -5.0,
+        };
+        let turned = Matrix4::translation(offset) * Matrix4::from_axis_angle(up, Radian(PI / 2.0));
+        let moving = Moving {
+            transforms: vec![
+                Transform::new(Matrix4::translation(offset)).unwrap(),
+                Transform::new(turned).unwrap(),
+            ],
+            object: Box::new(sphere.into()),
+        };
+        let middle = moving.transform_at(0.5).unwrap();
+        for axis in [(1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, 0.0, 1.0)] {
+            let (x, y, z) = axis;
+            assert_near(middle.vector(Vector { x, y, z }).norm(), 1.0);
+        }
+        assert_near(middle.vector(up).y, 1.0);
+        // halfway through the quarter turn
+        let x = middle.vector(Vector {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        assert_near(x.x, (PI / 4.0).cos());
+        assert_near(x.z, -(PI / 4.0).sin());
+        let mut grazing = ray(vertex(0.99, 0.0, 0.0), 0.0, 0.0, -1.0);
+        grazing.time = 0.5;
+        assert!(moving.hit(&grazing).is_some());
+    }
 }
@@ -0,0 +1,303 @@
+//! Procedural textures, computed from the position of the surface points
+//! rather than looked up in images.
+//!
+//! Patterns are scalar textures in `[0, 1]`, turned into colors or other
+//! values by [`Ramp`]s.
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::math::noise::Fractal;
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::Lerp;
+use crate::tracer::texture::{Surface, Texture};
+
+/// Space the textures are evaluated in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Space {
+    /// Moving along with the objects and their instances.
+    Object,
+    /// Fixed in the scene, objects moving through it.
+    World,
+}
+
+impl Space {
+    /// Surface point in the space, scaled by `scale`.
+    fn point(self, surface: &Surface, scale: f32) -> Vertex {
+        let p = match self {
+            Self::Object => surface.point,
+            Self::World => surface.scene_point,
+        };
+        Vertex {
+            x: p.x * scale,
+            y: p.y * scale,
+            z: p.z * scale,
+        }
+    }
+}
+
+/// Fractional Brownian motion, clouds of noise.
+#[derive(Clone, Copy, Debug)]
+pub struct Fbm {
+    pub fractal: Fractal,
+    /// Frequency of the first octave, in features per unit.
+    pub scale: f32,
+    pub space: Space,
+}
+
+impl Texture<f32> for Fbm {
+    fn value(&self, surface: &Surface) -> f32 {
+        0.5 + 0.5 * self.fractal.fbm(self.space.point(surface, self.scale))
+    }
+}
+
+/// Absolute octaves of noise, billowing with sharp creases.
+#[derive(Clone, Copy, Debug)]
+pub struct Turbulence {
+    pub fractal: Fractal,
+    pub scale: f32,
+    pub space: Space,
+}
+
+impl Texture<f32> for Turbulence {
+    fn value(&self, surface: &Surface) -> f32 {
+        self.fractal
+            .turbulence(self.space.point(surface, self.scale))
+    }
+}
+
+/// Veins across the `x` axis, `scale` of them per unit, bent by
+/// `distortion` times the turbulence.
+#[derive(Clone, Copy, Debug)]
+pub struct Marble {
+    pub fractal: Fractal,
+    pub scale: f32,
+    pub distortion: f32,
+    pub space: Space,
+}
+
+impl Texture<f32> for Marble {
+    fn value(&self, surface: &Surface) -> f32 {
+        let p = self.space.point(surface, self.scale);
+        let phase = p.x + self.distortion * self.fractal.turbulence(p);
+        0.5 + 0.5 * (phase * PI).sin()
+    }
+}
+
+/// Growth rings around the `y` axis, `scale` of them per unit, each
+/// going from 0 to 1 outward, wobbling by `distortion` times the noise.
+#[derive(Clone, Copy, Debug)]
+pub struct Wood {
+    pub fractal: Fractal,
+    pub scale: f32,
+    pub distortion: f32,
+    pub space: Space,
+}
+
+impl Texture<f32> for Wood {
+    fn value(&self, surface: &Surface) -> f32 {
+        let p = self.space.point(surface, self.scale);
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        (radius + self.distortion * self.fractal.fbm(p)).rem_euclid(1.0)
+    }
+}
+
+/// Cubes of alternating values filling the space, `scale` of them per
+/// unit.
+#[derive(Clone, Copy, Debug)]
+pub struct Checker3d<T> {
+    pub even: T,
+    pub odd: T,
+    pub scale: f32,
+    pub space: Space,
+}
+
+impl<T: Copy + fmt::Debug + Send + Sync> Texture<T> for Checker3d<T> {
+    fn value(&self, surface: &Surface) -> T {
+        let p = self.space.point(surface, self.scale);
+        let sum = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+        if sum.rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+/// Going from 0 at `from` to 1 at `to`, constant across.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    pub from: Vertex,
+    pub to: Vertex,
+    pub space: Space,
+}
+
+impl Texture<f32> for Gradient {
+    fn value(&self, surface: &Surface) -> f32 {
+        let axis = Vector::from_vertices(self.from, self.to);
+        let offset = Vector::from_vertices(self.from, self.space.point(surface, 1.0));
+        (offset.dot(axis) / axis.dot(axis)).clamp(0.0, 1.0)
+    }
+}
+
+/// Values interpolated between stops placed along the values of a pattern.
+#[derive(Clone, Debug)]
+pub struct Ramp<T> {
+    pub input: Arc<dyn Texture<f32>>,
+    /// Positions and values, by increasing positions.
+    stops: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Ramp<T> {
+    /// `None` without stops.
+    pub fn new(input: Arc<dyn Texture<f32>>, mut stops: Vec<(f32, T)>) -> Option<Ramp<T>> {
+        if stops.is_empty() {
+            return None;
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Some(Ramp { input, stops })
+    }
+
+    /// Value at the position `t`, the end values extending beyond the
+    /// stops.
+    pub fn at(&self, t: f32) -> T {
+        let next = self.stops.partition_point(|&(position, _)| position <= t);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1;
+        }
+        let ((t0, a), (t1, b)) = (self.stops[next - 1], self.stops[next]);
+        a.lerp(b, (t - t0) / (t1 - t0))
+    }
+}
+
+impl<T: Lerp + fmt::Debug + Send + Sync> Texture<T> for Ramp<T> {
+    fn value(&self, surface: &Surface) -> T {
+        self.at(self.input.value(surface))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Color;
+    use crate::math::noise::Noise;
+
+    fn at(x: f32, y: f32, z: f32) -> Surface {
+        let p = Vertex { x, y, z };
+        Surface {
+            point: p,
+            scene_point: Vertex { x: x + 10.0, ..p },
+            ..Surface::default()
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        let fractal = Fractal {
+            noise: Noise::Simplex,
+            ..Fractal::default()
+        };
+        for i in 0..100 {
+            let s = at(i as f32 * 0.37, 1.3, -0.2 * i as f32);
+            let fbm = Fbm {
+                fractal,
+                scale: 2.0,
+                space: Space::Object,
+            };
+            let marble = Marble {
+                fractal,
+                scale: 2.0,
+                distortion: 4.0,
+                space: Space::Object,
+            };
+            let wood = Wood {
+                fractal,
+                scale: 3.0,
+                distortion: 0.5,
+                space: Space::World,
+            };
+            for value in [fbm.value(&s), marble.value(&s), wood.value(&s)] {
+                assert!((0.0..=1.0).contains(&value), "{}", value);
+            }
+        }
+        // undistorted rings
+        let wood = Wood {
+            fractal,
+            scale: 2.0,
+            distortion: 0.0,
+            space: Space::Object,
+        };
+        assert!((wood.value(&at(0.0, 5.0, 0.6)) - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn spaces() {
+        let checker = Checker3d {
+            even: 1.0,
+            odd: 0.0,
+            scale: 1.0,
+            space: Space::Object,
+        };
+        assert_eq!(checker.value(&at(0.5, 0.5, 0.5)), 1.0);
+        assert_eq!(checker.value(&at(1.5, 0.5, 0.5)), 0.0);
+        assert_eq!(checker.value(&at(-0.5, 0.5, 0.5)), 0.0);
+        let world = Checker3d {
+            space: Space::World,
+            ..checker
+        };
+        assert_eq!(world.value(&at(1.5, 0.5, 0.5)), 0.0);
+        assert_eq!(world.value(&at(0.5, 0.5, 0.5)), 1.0);
+
+        let gradient = Gradient {
+            from: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            to: Vertex {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+            space: Space::Object,
+        };
+        assert_eq!(gradient.value(&at(5.0, 1.0, 3.0)), 0.5);
+        assert_eq!(gradient.value(&at(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn ramp() {
+        let input = Arc::new(Gradient {
+            from: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            to: Vertex {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            space: Space::Object,
+        });
+        let red = Color {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0,
+        };
+        let blue = Color {
+            red: 0.0,
+            green: 0.0,
+            blue: 1.0,
+        };
+        let ramp = Ramp::new(input.clone(), vec![(0.75, blue), (0.25, red)]).unwrap();
+        assert_eq!(ramp.value(&at(0.1, 0.0, 0.0)), red);
+        assert_eq!(ramp.value(&at(0.5, 0.0, 0.0)).blue, 0.5);
+        assert_eq!(ramp.value(&at(0.9, 0.0, 0.0)), blue);
+        assert!(Ramp::<f32>::new(input, Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Projections of the image onto the camera rays.
+//!
+//! Image positions go from -1 to 1 across the width and the height, up
+//! being positive, rays being given in the camera frame of right, up and
+//! backward axes.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use crate::math::vec3::Vector;
+use crate::math::Radian;
+
+pub trait CameraProjection {
+    /// Origin offset from the camera position and direction of the ray
+    /// through the image position `(x, y)` of an image `aspect` times wider
+    /// than high, `None` where the projection does not cover the image.
+    fn ray(&self, x: f32, y: f32, aspect: f32, fov: Radian) -> Option<(Vector, Vector)>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective(Perspective),
+    Orthographic(Orthographic),
+    Fisheye(Fisheye),
+    Equirectangular(Equirectangular),
+    Cubemap(Cubemap),
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Perspective.into()
+    }
+}
+
+impl Projection {
+    pub fn ray(&self, x: f32, y: f32, aspect: f32, fov: Radian) -> Option<(Vector, Vector)> {
+        match self {
+            Self::Perspective(p) => p.ray(x, y, aspect, fov),
+            Self::Orthographic(p) => p.ray(x, y, aspect, fov),
+            Self::Fisheye(p) => p.ray(x, y, aspect, fov),
+            Self::Equirectangular(p) => p.ray(x, y, aspect, fov),
+            Self::Cubemap(p) => p.ray(x, y, aspect, fov),
+        }
+    }
+
+    /// Whether rays go through a lens, panoramas seeing all around from a
+    /// single point.
+    pub fn has_lens(&self) -> bool {
+        !matches!(self, Self::Equirectangular(_) | Self::Cubemap(_))
+    }
+}
+
+impl From<Perspective> for Projection {
+    fn from(p: Perspective) -> Projection {
+        Projection::Perspective(p)
+    }
+}
+
+impl From<Orthographic> for Projection {
+    fn from(p: Orthographic) -> Projection {
+        Projection::Orthographic(p)
+    }
+}
+
+impl From<Fisheye> for Projection {
+    fn from(p: Fisheye) -> Projection {
+        Projection::Fisheye(p)
+    }
+}
+
+impl From<Equirectangular> for Projection {
+    fn from(p: Equirectangular) -> Projection {
+        Projection::Equirectangular(p)
+    }
+}
+
+impl From<Cubemap> for Projection {
+    fn from(p: Cubemap) -> Projection {
+        Projection::Cubemap(p)
+    }
+}
+
+/// Pinhole projection, `fov` being the vertical field of view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perspective;
+
+impl CameraProjection for Perspective {
+    fn ray(&self, x: f32, y: f32, aspect: f32, Radian(fov): Radian) -> Option<(Vector, Vector)> {
+        let half_height = (fov / 2.0).tan();
+        let direction = Vector {
+            x: x * aspect * half_height,
+            y: y * half_height,
+            z: -1.0,
+        };
+        Some((Vector::default(), direction))
+    }
+}
+
+/// Parallel rays leaving a `height` high view plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orthographic {
+    pub height: f32,
+}
+
+impl CameraProjection for Orthographic {
+    fn ray(&self, x: f32, y: f32, aspect: f32, _: Radian) -> Option<(Vector, Vector)> {
+        let origin = Vector {
+            x: x * aspect * self.height / 2.0,
+            y: y * self.height / 2.0,
+            z: 0.0,
+        };
+        let direction = Vector {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        Some((origin, direction))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    /// Image distance from the center proportional to the angle.
+    Equidistant,
+    /// Image distance preserving solid angles.
+    Equisolid,
+}
+
+/// Circular fisheye fitting the image height, `fov` being the field of view
+/// across the circle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fisheye {
+    pub mapping: FisheyeMapping,
+}
+
+impl CameraProjection for Fisheye {
+    fn ray(&self, x: f32, y: f32, aspect: f32, Radian(fov): Radian) -> Option<(Vector, Vector)> {
+        let x = x * aspect;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * fov / 2.0,
+            FisheyeMapping::Equisolid => 2.0 * (r * (fov / 4.0).sin()).asin(),
+        };
+        let (sin, cos) = theta.sin_cos();
+        // the center looks straight ahead
+        let (dx, dy) = if r > 0.0 { (x / r, y / r) } else { (0.0, 0.0) };
+        let direction = Vector {
+            x: dx * sin,
+            y: dy * sin,
+            z: -cos,
+        };
+        Some((Vector::default(), direction))
+    }
+}
+
+/// Latitude and longitude panorama of an image twice wider than high, the
+/// view direction being at its center.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Equirectangular;
+
+impl CameraProjection for Equirectangular {
+    fn ray(&self, x: f32, y: f32, _: f32, _: Radian) -> Option<(Vector, Vector)> {
+        let (longitude, latitude) = (x * PI, y * FRAC_PI_2);
+        let direction = Vector {
+            x: longitude.sin() * latitude.cos(),
+            y: latitude.sin(),
+            z: -longitude.cos() * latitude.cos(),
+        };
+        Some((Vector::default(), direction))
+    }
+}
+
+/// Six square cube faces in a grid of three columns and two rows, the
+/// first row holding the right, left and up faces, the second one the down,
+/// back and front faces, oriented as OpenGL cube maps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cubemap;
+
+impl CameraProjection for Cubemap {
+    fn ray(&self, x: f32, y: f32, _: f32, _: Radian) -> Option<(Vector, Vector)> {
+        let (column, row) = ((x + 1.0) * 1.5, 1.0 - y);
+        let (i, j) = (column.floor().min(2.0), row.floor().min(1.0));
+        // face coordinates going right and down
+        let (s, t) = ((column - i) * 2.0 - 1.0, (row - j) * 2.0 - 1.0);
+        let (x, y, z) = match (j as usize) * 3 + i as usize {
+            0 => (1.0, -t, -s),
+            1 => (-1.0, -t, s),
+            2 => (s, 1.0, t),
+            3 => (s, -1.0, -t),
+            4 => (s, -t, 1.0),
+            _ => (-s, -t, -1.0),
+        };
+        Some((Vector::default(), Vector { x, y, z }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Degree;
+
+    fn direction(projection: impl Into<Projection>, x: f32, y: f32, fov: f32) -> Vector {
+        let fov = Degree(fov).into();
+        let (_, d) = projection.into().ray(x, y, 2.0, fov).unwrap();
+        d.normalize()
+    }
+
+    fn assert_near(a: Vector, [x, y, z]: [f32; 3]) {
+        let close = (a.x - x).abs() < 1e-5 && (a.y - y).abs() < 1e-5 && (a.z - z).abs() < 1e-5;
+        assert!(close, "{:?} != {:?}", a, [x, y, z]);
+    }
+
+    #[test]
+    fn fisheye() {
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let fisheye = Fisheye { mapping };
+            assert_near(direction(fisheye, 0.0, 0.0, 180.0), [0.0, 0.0, -1.0]);
+            // the edge of a 180 degree circle looks sideways
+            assert_near(direction(fisheye, 0.0, 1.0, 180.0), [0.0, 1.0, 0.0]);
+            assert_near(direction(fisheye, -0.5, 0.0, 180.0), [-1.0, 0.0, 0.0]);
+            let outside = fisheye.ray(0.9, 0.9, 2.0, Degree(180.0).into());
+            assert!(outside.is_none());
+        }
+        let half = direction(
+            Fisheye {
+                mapping: FisheyeMapping::Equidistant,
+            },
+            0.0,
+            0.5,
+            180.0,
+        );
+        assert!((half.y - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn panoramas() {
+        assert_near(direction(Equirectangular, 0.0, 0.0, 0.0), [0.0, 0.0, -1.0]);
+        assert_near(direction(Equirectangular, 0.5, 0.0, 0.0), [1.0, 0.0, 0.0]);
+        assert_near(direction(Equirectangular, 1.0, 0.0, 0.0), [0.0, 0.0, 1.0]);
+        assert_near(direction(Equirectangular, 0.3, 1.0, 0.0), [0.0, 1.0, 0.0]);
+
+        // face centers
+        let centers = [
+            ([-2.0 / 3.0, 0.5], [1.0, 0.0, 0.0]),
+            ([0.0, 0.5], [-1.0, 0.0, 0.0]),
+            ([2.0 / 3.0, 0.5], [0.0, 1.0, 0.0]),
+            ([-2.0 / 3.0, -0.5], [0.0, -1.0, 0.0]),
+            ([0.0, -0.5], [0.0, 0.0, 1.0]),
+            ([2.0 / 3.0, -0.5], [0.0, 0.0, -1.0]),
+        ];
+        for ([x, y], expected) in centers {
+            assert_near(direction(Cubemap, x, y, 0.0), expected);
+        }
+        // the top of the front face is toward up
+        assert!(direction(Cubemap, 2.0 / 3.0, -0.1, 0.0).y > 0.0);
+    }
+
+    #[test]
+    fn orthographic() {
+        let projection = Orthographic { height: 4.0 };
+        let (origin, direction) = projection.ray(1.0, -0.5, 2.0, Radian(0.0)).unwrap();
+        assert_near(origin, [4.0, -1.0, 0.0]);
+        assert_near(direction, [0.0, 0.0, -1.0]);
+    }
+}
@@ -0,0 +1,545 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::graphics::{Canvas, Color, Region, RenderControl};
+use crate::math::sampling::Rng;
+use crate::math::vec3::Vector;
+use crate::tracer::denoise::Denoiser;
+use crate::tracer::{Scene, Screen};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    /// Direct lighting only, one shadow ray per light.
+    Direct,
+    /// Diffuse path tracing with direct lighting at every bounce.
+    Path,
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+    pub max_depth: usize,
+    pub integrator: Integrator,
+    pub seed: u64,
+    pub denoise: bool,
+    pub threads: usize,
+    /// Only renders these pixels, leaving the rest of the frame black.
+    pub region: Option<Region>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            width: 800,
+            height: 600,
+            samples: 1,
+            max_depth: 4,
+            integrator: Integrator::Direct,
+            seed: 0,
+            denoise: false,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            region: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Rendered pixels, the region of interest clipped to the image or the
+    /// whole image.
+    pub fn region(&self) -> Region {
+        let full = Region {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        };
+        match self.region {
+            Some(region) => region.intersect(full),
+            None => full,
+        }
+    }
+}
+
+/// Rendered image along with the feature buffers used by the denoiser.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels actually rendered.
+    pub region: Region,
+    pub beauty: Vec<Color>,
+    pub albedo: Vec<Color>,
+    pub normal: Vec<Vector>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            region: Region {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            beauty: vec![Color::default(); width * height],
+            albedo: vec![Color::default(); width * height],
+            normal: vec![Vector::default(); width * height],
+        }
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    /// Writes the rendered region of the beauty buffer into `canvas`.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let region = self.region;
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                canvas.set(x, y, self.beauty[self.index(x, y)]);
+            }
+        }
+    }
+}
+
+/// Side of the pixel blocks of the coarse preview pass.
+const PREVIEW_BLOCK: usize = 8;
+const PIXEL_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+const PASS_SEED: u64 = 0xC2B2_AE3D_27D4_EB4F;
+
+/// Running sums of the samples taken so far, one per pixel.
+struct Film {
+    width: usize,
+    height: usize,
+    sums: Vec<[f32; 3]>,
+    counts: Vec<usize>,
+    albedo: Vec<Color>,
+    normal: Vec<Vector>,
+}
+
+/// New samples of a row: pixel index, color, and features on the first one.
+type RowSamples = Vec<(usize, Color, Option<(Color, Vector)>)>;
+
+impl Film {
+    fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            sums: vec![[0.0; 3]; width * height],
+            counts: vec![0; width * height],
+            albedo: vec![Color::default(); width * height],
+            normal: vec![Vector::default(); width * height],
+        }
+    }
+
+    /// Average of the pixel at `index` once `color` is added to it.
+    fn average_with(&self, index: usize, color: Color) -> Color {
+        let [r, g, b] = self.sums[index];
+        let n = (self.counts[index] + 1) as f32;
+        Color {
+            red: (r + color.red) / n,
+            green: (g + color.green) / n,
+            blue: (b + color.blue) / n,
+        }
+    }
+
+    fn add(&mut self, samples: RowSamples) {
+        for (index, color, features) in samples {
+            let sum = &mut self.sums[index];
+            sum[0] += color.red;
+            sum[1] += color.green;
+            sum[2] += color.blue;
+            self.counts[index] += 1;
+            if let Some((albedo, normal)) = features {
+                self.albedo[index] = albedo;
+                self.normal[index] = normal;
+            }
+        }
+    }
+
+    fn frame(&self, region: Region) -> Frame {
+        let mut frame = Frame::new(self.width, self.height);
+        frame.region = region;
+        for (index, (sum, count)) in self.sums.iter().zip(&self.counts).enumerate() {
+            let n = (*count).max(1) as f32;
+            frame.beauty[index] = Color {
+                red: sum[0] / n,
+                green: sum[1] / n,
+                blue: sum[2] / n,
+            };
+        }
+        frame.albedo = self.albedo.clone();
+        frame.normal = self.normal.clone();
+        // a stopped render keeps the coarse preview of the pixels it missed
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let index = frame.index(x, y);
+                let block = frame.index(
+                    x - (x - region.x) % PREVIEW_BLOCK,
+                    y - (y - region.y) % PREVIEW_BLOCK,
+                );
+                if self.counts[index] == 0 && self.counts[block] > 0 {
+                    frame.beauty[index] = frame.beauty[block];
+                    frame.albedo[index] = frame.albedo[block];
+                    frame.normal[index] = frame.normal[block];
+                }
+            }
+        }
+        frame
+    }
+}
+
+/// Renders `scene` progressively on `settings.threads` threads, calling
+/// `on_pixels` with the running averages of the pixels of a row as soon as
+/// they get a new sample so that a live view can follow the progress, the
+/// run of pixels starting at the given coordinates. Only the pixels of
+/// [`Settings::region`] are rendered.
+///
+/// A coarse pass first traces one pixel out of each block of
+/// `PREVIEW_BLOCK` pixels side and reports it for the whole block, then
+/// each pass adds one sample to every pixel until `settings.samples` is
+/// reached. Each pixel and pass has its own random sequence, so the result
+/// only depends on the seed and not on the number of threads or the region.
+///
+/// A paused render waits between rows. A stopped render returns what has
+/// converged so far, the pixels it did not reach keeping their coarse
+/// preview, a cancelled one returns nothing. The returned frame is
+/// already denoised when `settings.denoise` is set.
+pub fn render(
+    scene: &Scene,
+    settings: &Settings,
+    control: &RenderControl,
+    on_pixels: impl Fn(usize, usize, &[Color]) + Sync,
+) -> Option<Frame> {
+    let screen = Screen::with_camera(scene.camera, settings.width, settings.height)
+        .with_motion(scene.closing_camera);
+    let mut film = Film::new(settings.width, settings.height);
+    let region = settings.region();
+    let (right, bottom) = (region.x + region.width, region.y + region.height);
+    let target = settings.samples.max(1);
+    control.begin(target);
+    for pass in 0..=target {
+        let step = if pass == 0 { PREVIEW_BLOCK } else { 1 };
+        let next_row = AtomicUsize::new(region.y);
+        let rows: Vec<RowSamples> = thread::scope(|s| {
+            let workers: Vec<_> = (0..settings.threads.max(1))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut rows = Vec::new();
+                        loop {
+                            control.wait_while_paused();
+                            let y = next_row.fetch_add(step, Ordering::Relaxed);
+                            if y >= bottom || control.is_cancelled() || control.is_stopped() {
+                                return rows;
+                            }
+                            let mut row = Vec::new();
+                            // averages of the pixels from `start`, each one
+                            // standing for its block in the coarse pass
+                            let mut run = Vec::new();
+                            let mut start = region.x;
+                            let report = |start: usize, run: &mut Vec<Color>| {
+                                if !run.is_empty() {
+                                    for by in y..(y + step).min(bottom) {
+                                        on_pixels(start, by, run);
+                                    }
+                                    run.clear();
+                                }
+                            };
+                            for x in (region.x..right).step_by(step) {
+                                let index = x + y * settings.width;
+                                if film.counts[index] >= pass.max(1) {
+                                    report(start, &mut run);
+                                    start = x + step;
+                                    continue;
+                                }
+                                let first = film.counts[index] == 0;
+                                let mut rng = Rng::new(
+                                    settings.seed
+                                        ^ (index as u64).wrapping_mul(PIXEL_SEED)
+                                        ^ (pass as u64).wrapping_mul(PASS_SEED),
+                                );
+                                let (color, features) =
+                                    render_sample(scene, settings, &screen, x, y, first, &mut rng);
+                                let average = film.average_with(index, color);
+                                run.resize((x + step).min(right) - start, average);
+                                row.push((index, color, features));
+                            }
+                            report(start, &mut run);
+                            rows.push(row);
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("render thread"))
+                .collect()
+        });
+        rows.into_iter().for_each(|row| film.add(row));
+        if control.is_cancelled() {
+            return None;
+        }
+        if control.is_stopped() {
+            break;
+        }
+        control.report(pass);
+    }
+
+    let mut frame = film.frame(region);
+    if settings.denoise {
+        frame.beauty = Denoiser::default().apply(&frame);
+    }
+    control.finish();
+    Some(frame)
+}
+
+/// Traces one sample of the pixel `(x, y)`, jittered over the pixel, the
+/// lens and the shutter interval. The first sample also gives the albedo and
+/// normal features, from a ray through the pixel center and the lens center
+/// in the middle of the shutter interval which is left out of the color.
+fn render_sample(
+    scene: &Scene,
+    settings: &Settings,
+    screen: &Screen,
+    x: usize,
+    y: usize,
+    first: bool,
+    rng: &mut Rng,
+) -> (Color, Option<(Color, Vector)>) {
+    let (dx, dy, time) = (rng.next_f32(), rng.next_f32(), rng.next_f32());
+    let lens = (rng.next_f32(), rng.next_f32());
+    let ray = screen.lens_ray(x as f32 + dx, y as f32 + dy, time, lens);
+    // outside of the projected image, left black
+    let color = match (settings.integrator, ray.and_then(|ray| scene.trace(&ray))) {
+        (_, None) => Color::default(),
+        (Integrator::Direct, Some(i)) => scene.compute_color(&i),
+        (Integrator::Path, Some(i)) => scene.compute_path(&i, settings.max_depth, rng),
+    };
+    let features = if first {
+        let center = screen.ray_at_time(x as f32 + 0.5, y as f32 + 0.5, 0.5);
+        Some(match center.and_then(|ray| scene.trace(&ray)) {
+            Some(i) => (i.color() * i.albedo(), i.shading_normal()),
+            None => (Color::default(), Vector::default()),
+        })
+    } else {
+        None
+    };
+    (color, features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::Vertex;
+    use crate::tracer::lights::DirectionalLight;
+    use crate::tracer::objects::{Material, Sphere};
+
+    fn scene() -> Scene {
+        let mut scene = Scene::default();
+        scene.add_object(Sphere {
+            center: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: -4.0,
+            },
+            radius: 1.5,
+            material: Material::new("#FF8800".parse().unwrap(), 0.8),
+        });
+        scene.add_light(DirectionalLight {
+            direction: Vector {
+                x: -1.0,
+                y: -1.0,
+                z: -1.0,
+            },
+            base_color: "#FFFFFF".parse().unwrap(),
+            base_intensity: 2.0,
+        });
+        scene
+    }
+
+    #[test]
+    fn same_result_on_any_thread_count() {
+        let scene = scene();
+        let mut settings = Settings {
+            width: 24,
+            height: 17,
+            samples: 3,
+            integrator: Integrator::Path,
+            threads: 1,
+            ..Settings::default()
+        };
+        let control = RenderControl::default();
+        let single = render(&scene, &settings, &control, |_, _, _| {}).unwrap();
+        settings.threads = 4;
+        let multi = render(&scene, &settings, &control, |_, _, _| {}).unwrap();
+        assert_eq!(single.beauty, multi.beauty);
+        let progress = control.progress().unwrap();
+        assert_eq!(progress.samples, 3);
+        assert!(progress.done);
+    }
+
+    #[test]
+    fn stopped_render_keeps_samples() {
+        // a sphere filling the view, lit from the camera
+        let mut scene = Scene::default();
+        scene.add_object(Sphere {
+            center: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: -30.0,
+            },
+            radius: 27.0,
+            material: Material::new("#FF8800".parse().unwrap(), 0.8),
+        });
+        scene.add_light(DirectionalLight {
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            base_color: "#FFFFFF".parse().unwrap(),
+            base_intensity: 2.0,
+        });
+        let mut settings = Settings {
+            width: 16,
+            height: 16,
+            samples: 100,
+            threads: 1,
+            ..Settings::default()
+        };
+        // the coarse pass reports all 256 pixels, the first full pass the
+        // 252 pixels it traces, stopping once the row of the given pixel is
+        // reported
+        for (pixels, samples, denoise) in [
+            (256 + 40, 0, false),
+            (256 + 40, 0, true),
+            (256 + 252 + 40, 1, false),
+        ] {
+            settings.denoise = denoise;
+            let control = RenderControl::default();
+            let count = AtomicUsize::new(0);
+            let frame = render(&scene, &settings, &control, |_, _, run| {
+                let before = count.fetch_add(run.len(), Ordering::Relaxed);
+                if before < pixels && before + run.len() >= pixels {
+                    control.stop();
+                }
+            })
+            .unwrap();
+            assert_eq!(control.progress().unwrap().samples, samples);
+            assert!(frame.beauty.iter().all(|c| c.red > 0.0));
+        }
+
+        let control = RenderControl::default();
+        control.cancel();
+        assert!(render(&scene, &settings, &control, |_, _, _| {}).is_none());
+    }
+
+    #[test]
+    fn region_matches_full_render() {
+        let scene = scene();
+        let mut settings = Settings {
+            width: 24,
+            height: 17,
+            samples: 2,
+            integrator: Integrator::Path,
+            ..Settings::default()
+        };
+        let control = RenderControl::default();
+        let full = render(&scene, &settings, &control, |_, _, _| {}).unwrap();
+        settings.region = Some(Region {
+            x: 5,
+            y: 3,
+            width: 40,
+            height: 6,
+        });
+        let touched = std::sync::Mutex::new(Vec::new());
+        let part = render(&scene, &settings, &control, |x, y, run| {
+            touched
+                .lock()
+                .unwrap()
+                .extend((x..x + run.len()).map(|x| (x, y)));
+        })
+        .unwrap();
+        assert!(touched
+            .into_inner()
+            .unwrap()
+            .iter()
+            .all(|&(x, y)| (5..24).contains(&x) && (3..9).contains(&y)));
+        for y in 0..17 {
+            for x in 0..24 {
+                let index = full.index(x, y);
+                if (5..24).contains(&x) && (3..9).contains(&y) {
+                    assert_eq!(part.beauty[index], full.beauty[index]);
+                } else {
+                    assert_eq!(part.beauty[index], Color::default());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn depth_of_field_from_the_first_sample() {
+        let mut scene = scene();
+        let settings = Settings {
+            width: 24,
+            height: 17,
+            samples: 1,
+            denoise: false,
+            ..Settings::default()
+        };
+        let control = RenderControl::default();
+        let sharp = render(&scene, &settings, &control, |_, _, _| {}).unwrap();
+        scene.camera.lens.aperture = 0.5;
+        scene.camera.lens.focus_distance = 100.0;
+        let blurred = render(&scene, &settings, &control, |_, _, _| {}).unwrap();
+        assert_ne!(sharp.beauty, blurred.beauty);
+        // the features stay those of the sharp image
+        assert_eq!(sharp.albedo, blurred.albedo);
+    }
+
+    #[test]
+    fn paused_render_resumes() {
+        let scene = scene();
+        let settings = Settings {
+            width: 8,
+            height: 8,
+            samples: 2,
+            threads: 2,
+            ..Settings::default()
+        };
+        // waits for the render to begin, it then cannot get past the pause
+        let started = |control: &RenderControl| loop {
+            match control.progress() {
+                Some(progress) if !progress.done => return progress,
+                _ => thread::yield_now(),
+            }
+        };
+        let control = RenderControl::default();
+        control.set_paused(true);
+        let resume = control.clone();
+        let resumer = thread::spawn(move || {
+            let progress = started(&resume);
+            assert!(progress.paused);
+            assert_eq!(progress.samples, 0);
+            resume.set_paused(false);
+        });
+        let frame = render(&scene, &settings, &control, |_, _, _| {});
+        resumer.join().unwrap();
+        assert!(frame.is_some());
+        assert_eq!(control.progress().unwrap().samples, 2);
+
+        // cancelling wakes a paused render up
+        control.set_paused(true);
+        let cancel = control.clone();
+        let canceller = thread::spawn(move || {
+            started(&cancel);
+            cancel.cancel();
+        });
+        assert!(render(&scene, &settings, &control, |_, _, _| {}).is_none());
+        canceller.join().unwrap();
+    }
+}
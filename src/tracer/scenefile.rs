@@ -0,0 +1,2467 @@
+//! TOML scene description.
+//!
+//! ```toml
+//! [render]
+//! samples = 16
+//! integrator = "path"
+//!
+//! [camera]
+//! position = [0, 1, 5]
+//! look_at = [0, 0, -10]
+//! fov = 60
+//! # or "orthographic" with a `view_height`, "fisheye" with an "equidistant"
+//! # or "equisolid" `mapping`, "equirectangular" for panoramas twice wider
+//! # than high, "cubemap" for faces in three columns and two rows
+//! projection = "perspective"
+//! # distance between the eyes of stereo renders, converging on the target
+//! # unless given a `convergence` distance
+//! interocular = 0.064
+//! # thin lens radius in scene units, or `f_stop` for a full frame camera in
+//! # meters, focused on the target unless given a `focus_distance`
+//! aperture = 0.05
+//! # hexagonal bokeh, the aperture being round without blades
+//! blades = 6
+//! blade_rotation = 15
+//!
+//! [materials.cyan]
+//! color = "#00FFFF"
+//! albedo = 0.8
+//!
+//! # parameters varying with the texture coordinates of the surfaces, images
+//! # repeating over the unit square, checkers making `scale` squares per unit
+//! [materials.floor]
+//! color = { texture = "checker", even = "#FFFFFF", odd = "#202020", scale = 4 }
+//! # 8 bit images being sRGB for colors and linear for scalars unless given a
+//! # "srgb" or "linear" `color_space`, "repeat"ed or "clamp"ed or "mirror"ed
+//! # out of the unit square, "trilinear" mipmapping unless "bilinear" or
+//! # "nearest" `filter`ed, `.hdr` images being linear
+//! albedo = { texture = "image", path = "rough.png", wrap = "mirror" }
+//!
+//! # procedural patterns of the points in the "object" (default) or "world"
+//! # space, "fbm", "turbulence", "marble" or "wood" of "perlin" (default),
+//! # "simplex" or "worley" noise, or a "gradient" between two points, all
+//! # going from 0 to 1 and mapped onto the values of a ramp, as grays without
+//! # one; "checker3d" alternating cubes
+//! [materials.stone]
+//! color = { texture = "marble", scale = 2, distortion = 4, octaves = 5, ramp = [[0, "#F0F0F0"], [1, "#404050"]] }
+//! albedo = { texture = "fbm", noise = "worley", scale = 8, space = "world", ramp = [[0, 0.6], [1, 0.9]] }
+//!
+//! # shading normals perturbed by a linear image of tangent space normals,
+//! # red along `u`, green along `v` and blue out of the surface, or by the
+//! # slopes of a scalar texture of heights, `bump_height` scene units for a
+//! # change of 1 (1 by default), shadows still leaving the true surface
+//! [materials.bricks]
+//! color = "#A05040"
+//! albedo = 0.7
+//! normal_map = { path = "bricks_normal.png" }
+//!
+//! [materials.hammered]
+//! color = "#C0C0C8"
+//! albedo = 0.6
+//! bump = { texture = "fbm", noise = "worley", octaves = 1, scale = 20 }
+//! bump_height = 0.005
+//!
+//! [[objects]]
+//! type = "triangle"
+//! vertices = [[-4, -2, 0], [4, -2, 0], [0, -2, -8]]
+//! uvs = [[0, 0], [2, 0], [1, 2]]
+//! material = "floor"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0, 0, -10]
+//! radius = 2
+//! material = "cyan"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0, 0, 0]
+//! radius = 1
+//! material = "cyan"
+//! # scaled, then rotated around the axis by an angle in degrees, then moved
+//! transform = { scale = [2, 1, 1], rotate = { axis = [0, 0, 1], angle = 30 }, translate = [4, 0, -10] }
+//!
+//! # boxes between two corners, turned around their center by `rotate`
+//! [[objects]]
+//! type = "box"
+//! min = [-1, -2, -14]
+//! max = [1, 0, -12]
+//! rotate = { axis = [0, 1, 0], angle = 20 }
+//! material = "cyan"
+//!
+//! # facing the side of the normal, or of the edges seen counterclockwise
+//! [[objects]]
+//! type = "disc"
+//! center = [0, -2, -6]
+//! normal = [0, 1, 0]
+//! radius = 1
+//! material = "cyan"
+//!
+//! [[objects]]
+//! type = "quad"
+//! corner = [-2, -2, -16]
+//! edges = [[4, 0, 0], [0, 3, 0]]
+//! material = "cyan"
+//!
+//! # closed by discs unless `capped = false`, cones narrowing to a point
+//! # unless given a `top_radius`
+//! [[objects]]
+//! type = "cylinder"
+//! base = [-3, -2, -8]
+//! top = [-3, 0, -8]
+//! radius = 0.5
+//! material = "cyan"
+//!
+//! [[objects]]
+//! type = "cone"
+//! base = [3, -2, -8]
+//! top = [3, 0, -8]
+//! radius = 0.8
+//! material = "cyan"
+//!
+//! # a tube of `minor_radius` around a circle of `major_radius`, turning
+//! # around the `y` axis unless given another `axis`
+//! [[objects]]
+//! type = "torus"
+//! center = [0, -1.5, -4]
+//! major_radius = 1
+//! minor_radius = 0.25
+//! material = "cyan"
+//!
+//! # shared by any number of instances, paths being relative to the file
+//! [geometries.bolt]
+//! mesh = "bolt.obj"
+//! material = "cyan"
+//! # meshes of "catmull-clark" quads or "loop" triangles split `levels`
+//! # times, then their vertices moved along the normals by `scale` units
+//! # times a scalar texture of `height`s, or by a linear image of `vector`
+//! # offsets in object space, 0.5 grays staying in place
+//! subdivision = { scheme = "catmull-clark", levels = 2 }
+//! displacement = { height = { texture = "fbm", scale = 8 }, scale = 0.05 }
+//!
+//! [[objects]]
+//! type = "instance"
+//! geometry = "bolt"
+//! transform = { translate = [2, 0, -8] }
+//!
+//! # groups moving their children, addressed by `/` separated paths
+//! [[nodes]]
+//! path = "car"
+//! transform = { translate = [0, -1, -12] }
+//!
+//! [[nodes]]
+//! path = "car/wheels"
+//! visible = false
+//!
+//! [[objects]]
+//! name = "wheel_front_left"
+//! parent = "car/wheels"
+//! type = "sphere"
+//! center = [-1, 0, 1]
+//! radius = 0.4
+//! material = "cyan"
+//! # moving in the parent space, in units per second, blurred by the shutter
+//! velocity = [0, 0, 2]
+//!
+//! [[lights]]
+//! type = "spherical"
+//! position = [1, -1, -1]
+//! color = "#FFFFFF"
+//! intensity = 1000
+//!
+//! # frames 1 to 48 at 24 frames per second, frame 1 being at time 0
+//! [animation]
+//! fps = 24
+//! frames = [1, 48]
+//! # fraction of a frame the shutter stays open for, 0 disabling motion blur
+//! shutter = 0.5
+//!
+//! # keys of a `node` (translate, rotate, scale and for lights color and
+//! # intensity), a `material` (color, albedo) or the `camera = true`
+//! # (position, look_at, fov, aperture, focus_distance), time being in seconds
+//! [[animation.tracks]]
+//! node = "car"
+//! property = "translate"
+//! keys = [
+//!     { time = 0, value = [0, 0, 0], interpolation = "bezier" },
+//!     { time = 2, value = [4, 0, 0] },
+//! ]
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, Deserializer, IntoDeserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::graphics::Color;
+use crate::math::matrix::Matrix4;
+use crate::math::noise::{Fractal, Noise};
+use crate::math::transform::Transform;
+use crate::math::vec3::{Vector, Vertex};
+use crate::math::{Degree, Lerp, Radian};
+use crate::tracer::animation::{
+    Animated, Animation, Channel, Interpolation, Keyframe, Rotation, Target, Track,
+};
+use crate::tracer::camera::{Camera, Lens};
+use crate::tracer::graph::{Content, Node, SceneGraph};
+use crate::tracer::lights::{DirectionalLight, Light, SphericalLight};
+use crate::tracer::mesh::Mesh;
+use crate::tracer::objects::{
+    Cone, Cuboid, Cylinder, Disc, Frame, Geometry, Instance, Material, Object, Plane, Quad, Sphere,
+    Torus, Triangle,
+};
+use crate::tracer::procedural::{Checker3d, Fbm, Gradient, Marble, Ramp, Space, Turbulence, Wood};
+use crate::tracer::projection::{
+    Cubemap, Equirectangular, Fisheye, FisheyeMapping, Orthographic, Perspective, Projection,
+};
+use crate::tracer::render::{Integrator, Settings};
+use crate::tracer::subdivision::{subdivide, Displacement, Scheme};
+use crate::tracer::texture::{
+    Checker, ColorSpace, Filter, ImageTexture, Relief, Texture, Uv, Wrap,
+};
+use crate::tracer::Scene;
+
+/// Poses of the scene over the shutter interval, the objects moving
+/// linearly between them.
+const MOTION_STEPS: usize = 5;
+
+/// Levels of subdivision beyond which meshes would not fit in memory, each
+/// one making about four times more faces.
+const MAX_SUBDIVISION_LEVELS: u32 = 8;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Syntax(toml::de::Error),
+    Invalid { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Syntax(e) => write!(f, "{}", e),
+            Self::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(val: std::io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+impl From<toml::de::Error> for SceneError {
+    fn from(val: toml::de::Error) -> Self {
+        Self::Syntax(val)
+    }
+}
+
+/// A parsed scene file.
+pub struct SceneFile {
+    /// Flattened from `graph`, posed at the first frame of `animation`.
+    pub scene: Scene,
+    pub graph: SceneGraph,
+    pub animation: Animation,
+    pub settings: Settings,
+    /// Unanimated camera and materials.
+    camera: Camera,
+    materials: HashMap<String, Material>,
+}
+
+impl SceneFile {
+    /// Reads a scene file, the meshes it refers to being relative to it.
+    pub fn load(path: impl AsRef<Path>) -> Result<SceneFile, SceneError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let raw: RawScene = toml::from_str(&src)?;
+        raw.build(&src, dir)
+    }
+}
+
+impl SceneFile {
+    /// Scene posed at `time`, in seconds, moving over the shutter interval
+    /// starting then.
+    pub fn scene_at(&self, time: f32) -> Scene {
+        let exposure = self.animation.shutter / self.animation.fps;
+        let steps = if exposure > 0.0 { MOTION_STEPS } else { 1 };
+        let (cameras, poses): (Vec<Camera>, Vec<SceneGraph>) = (0..steps)
+            .map(|i| self.pose(time + exposure * i as f32 / (steps - 1).max(1) as f32))
+            .unzip();
+        let mut scene = Scene {
+            camera: cameras[0],
+            closing_camera: Some(cameras[steps - 1]).filter(|c| *c != cameras[0]),
+            ..Scene::default()
+        };
+        scene.load_motion(&poses);
+        scene
+    }
+
+    fn pose(&self, time: f32) -> (Camera, SceneGraph) {
+        let (mut camera, mut graph) = (self.camera, self.graph.clone());
+        graph.advance(time);
+        self.animation
+            .apply(time, &mut camera, &mut graph, &self.materials);
+        (camera, graph)
+    }
+}
+
+impl FromStr for SceneFile {
+    type Err = SceneError;
+
+    fn from_str(src: &str) -> Result<SceneFile, SceneError> {
+        let raw: RawScene = toml::from_str(src)?;
+        raw.build(src, Path::new(""))
+    }
+}
+
+/// `[camera]` table describing `camera`, ready to be pasted in a scene file.
+pub fn format_camera(camera: &Camera) -> String {
+    let Camera {
+        position: p,
+        direction,
+        target_distance,
+        up: u,
+        fov: Degree(fov),
+        projection,
+        lens,
+        stereo,
+        ..
+    } = *camera;
+    let d = direction.normalize() * target_distance;
+    let mut table = format!(
+        "[camera]\n\
+         position = [{:.3}, {:.3}, {:.3}]\n\
+         look_at = [{:.3}, {:.3}, {:.3}]\n\
+         up = [{:.3}, {:.3}, {:.3}]\n\
+         fov = {:.1}\n",
+        p.x,
+        p.y,
+        p.z,
+        p.x + d.x,
+        p.y + d.y,
+        p.z + d.z,
+        u.x,
+        u.y,
+        u.z,
+        fov,
+    );
+    table += match projection {
+        Projection::Perspective(_) => String::new(),
+        Projection::Orthographic(p) => {
+            format!(
+                "projection = \"orthographic\"\nview_height = {:.3}\n",
+                p.height
+            )
+        }
+        Projection::Fisheye(p) => match p.mapping {
+            FisheyeMapping::Equidistant => "projection = \"fisheye\"\n".into(),
+            FisheyeMapping::Equisolid => {
+                "projection = \"fisheye\"\nmapping = \"equisolid\"\n".into()
+            }
+        },
+        Projection::Equirectangular(_) => "projection = \"equirectangular\"\n".into(),
+        Projection::Cubemap(_) => "projection = \"cubemap\"\n".into(),
+    }
+    .as_str();
+    if stereo.interocular > 0.0 {
+        table += &format!(
+            "interocular = {:.4}\nconvergence = {:.3}\n",
+            stereo.interocular, stereo.convergence
+        );
+    }
+    if lens.aperture > 0.0 {
+        table += &format!(
+            "aperture = {:.4}\nfocus_distance = {:.3}\n",
+            lens.aperture, lens.focus_distance
+        );
+        if lens.blades >= 3 {
+            table += &format!(
+                "blades = {}\nblade_rotation = {:.1}\n",
+                lens.blades, lens.rotation.0
+            );
+        }
+    }
+    table
+}
+
+fn line_of(src: &str, offset: usize) -> usize {
+    src[..offset.min(src.len())].matches('\n').count() + 1
+}
+
+struct ColorValue(Color);
+
+impl<'de> Deserialize<'de> for ColorValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let src = String::deserialize(deserializer)?;
+        src.parse()
+            .map(ColorValue)
+            .map_err(|_| de::Error::custom(format!("invalid color `{}`, expected `#RRGGBB`", src)))
+    }
+}
+
+fn vertex([x, y, z]: [f32; 3]) -> Vertex {
+    Vertex { x, y, z }
+}
+
+fn vector([x, y, z]: [f32; 3]) -> Vector {
+    Vector { x, y, z }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScene {
+    #[serde(default)]
+    render: RawRender,
+    camera: Option<Spanned<RawCamera>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<RawMaterial>>,
+    #[serde(default)]
+    geometries: HashMap<String, Spanned<RawGeometry>>,
+    #[serde(default)]
+    nodes: Vec<Spanned<RawNode>>,
+    #[serde(default)]
+    objects: Vec<Spanned<Named<RawObject>>>,
+    #[serde(default)]
+    lights: Vec<Spanned<Named<RawLight>>>,
+    animation: Option<RawAnimation>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAnimation {
+    fps: Option<f32>,
+    shutter: Option<f32>,
+    frames: Option<[usize; 2]>,
+    #[serde(default)]
+    tracks: Vec<Spanned<RawTrack>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTrack {
+    node: Option<String>,
+    material: Option<String>,
+    camera: Option<bool>,
+    property: String,
+    keys: Vec<RawKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawKey {
+    time: f32,
+    value: RawValue,
+    interpolation: Option<RawInterpolation>,
+    handles: Option<[f32; 4]>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawInterpolation {
+    Linear,
+    Bezier,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawValue {
+    Number(f32),
+    Triple([f32; 3]),
+    Color(ColorValue),
+    Rotation(RawRotation),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawNode {
+    path: String,
+    transform: Option<RawTransform>,
+    visible: Option<bool>,
+    velocity: Option<[f32; 3]>,
+}
+
+/// Object or light placed in the scene graph.
+#[derive(Deserialize)]
+struct Named<T> {
+    name: Option<String>,
+    parent: Option<String>,
+    velocity: Option<[f32; 3]>,
+    #[serde(flatten)]
+    item: T,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGeometry {
+    mesh: Option<String>,
+    material: Option<String>,
+    subdivision: Option<RawSubdivision>,
+    displacement: Option<RawDisplacement>,
+    #[serde(default)]
+    objects: Vec<Spanned<RawObject>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRender {
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: Option<usize>,
+    max_depth: Option<usize>,
+    integrator: Option<RawIntegrator>,
+    seed: Option<u64>,
+    denoise: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawProjection {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+    Cubemap,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawMapping {
+    Equidistant,
+    Equisolid,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawIntegrator {
+    Direct,
+    Path,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCamera {
+    position: Option<[f32; 3]>,
+    look_at: Option<[f32; 3]>,
+    up: Option<[f32; 3]>,
+    fov: Option<f32>,
+    projection: Option<RawProjection>,
+    mapping: Option<RawMapping>,
+    view_height: Option<f32>,
+    aperture: Option<f32>,
+    f_stop: Option<f32>,
+    focus_distance: Option<f32>,
+    blades: Option<u32>,
+    blade_rotation: Option<f32>,
+    interocular: Option<f32>,
+    convergence: Option<f32>,
+}
+
+impl RawCamera {
+    fn build(self, line: usize) -> Result<Camera, SceneError> {
+        let invalid = |message: &str| SceneError::Invalid {
+            line,
+            message: message.into(),
+        };
+        let default = Camera::default();
+        let position = self.position.map(vertex).unwrap_or(default.position);
+        let target = self
+            .look_at
+            .map(vertex)
+            .unwrap_or(position + default.direction);
+        let mut camera = Camera::look_at(
+            position,
+            target,
+            self.up.map(vector).unwrap_or(default.up),
+            self.fov.map(Degree).unwrap_or(default.fov),
+        );
+        camera.projection = match (self.projection, self.mapping, self.view_height) {
+            (Some(RawProjection::Orthographic), None, height) => {
+                // framing the target as the perspective view would
+                let height = height.unwrap_or_else(|| {
+                    let Radian(fov) = camera.fov.into();
+                    let distance = Vector::from_vertices(position, target).norm();
+                    2.0 * (fov / 2.0).tan() * distance
+                });
+                if !(height > 0.0 && height.is_finite()) {
+                    return Err(invalid("view_height must be positive"));
+                }
+                Orthographic { height }.into()
+            }
+            (Some(RawProjection::Fisheye), mapping, None) => {
+                let mapping = match mapping {
+                    Some(RawMapping::Equidistant) | None => FisheyeMapping::Equidistant,
+                    Some(RawMapping::Equisolid) if camera.fov.0 < 360.0 => {
+                        FisheyeMapping::Equisolid
+                    }
+                    Some(RawMapping::Equisolid) => {
+                        return Err(invalid("an equisolid fisheye sees less than 360 degrees"))
+                    }
+                };
+                Fisheye { mapping }.into()
+            }
+            (_, Some(_), _) => return Err(invalid("only a fisheye projection has a mapping")),
+            (_, _, Some(_)) => {
+                return Err(invalid("only an orthographic projection has a view_height"))
+            }
+            (Some(RawProjection::Perspective), None, None) | (None, None, None) => {
+                Perspective.into()
+            }
+            (Some(RawProjection::Equirectangular), None, None) => Equirectangular.into(),
+            (Some(RawProjection::Cubemap), None, None) => Cubemap.into(),
+        };
+        camera.lens.aperture = match (self.aperture, self.f_stop) {
+            (Some(_), Some(_)) => return Err(invalid("give either an aperture or an f_stop")),
+            (Some(aperture), None) if aperture >= 0.0 => aperture,
+            (None, Some(f_stop)) if f_stop > 0.0 => Lens::aperture_of(f_stop, camera.fov),
+            (None, None) => default.lens.aperture,
+            _ => return Err(invalid("aperture must not be negative")),
+        };
+        // focused on the target unless told otherwise
+        camera.lens.focus_distance = match self.focus_distance {
+            Some(distance) if distance > 0.0 => distance,
+            Some(_) => return Err(invalid("focus_distance must be positive")),
+            None if self.look_at.is_some() => Vector::from_vertices(position, target).norm(),
+            None => default.lens.focus_distance,
+        };
+        camera.lens.blades = match self.blades {
+            Some(blades) if blades < 3 => {
+                return Err(invalid("an aperture needs at least 3 blades"))
+            }
+            blades => blades.unwrap_or(default.lens.blades),
+        };
+        camera.lens.rotation = self.blade_rotation.map_or(default.lens.rotation, Degree);
+        camera.stereo.interocular = match self.interocular {
+            Some(distance) if distance < 0.0 => {
+                return Err(invalid("interocular must not be negative"))
+            }
+            distance => distance.unwrap_or(default.stereo.interocular),
+        };
+        // converging on the target unless told otherwise
+        camera.stereo.convergence = match self.convergence {
+            Some(distance) if distance > 0.0 => distance,
+            Some(_) => return Err(invalid("convergence must be positive")),
+            None if self.look_at.is_some() => Vector::from_vertices(position, target).norm(),
+            None => default.stereo.convergence,
+        };
+        Ok(camera)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMaterial {
+    color: RawParam<ColorValue>,
+    albedo: RawParam<f32>,
+    normal_map: Option<RawImage>,
+    bump: Option<RawTexture<f32>>,
+    bump_height: Option<f32>,
+}
+
+/// Image of vectors, tangent space normals or offsets, always linear.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawImage {
+    path: String,
+    wrap: Option<RawWrap>,
+    filter: Option<RawFilter>,
+}
+
+/// Material parameter, constant or varying with a texture given as a table.
+enum RawParam<T> {
+    Constant(T),
+    Texture(RawTexture<T>),
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for RawParam<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // by hand, untagged enums hiding the errors of the constants
+        struct ParamVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ParamVisitor<T> {
+            type Value = RawParam<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a value or a texture")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                T::deserialize(v.into_deserializer()).map(RawParam::Constant)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                T::deserialize(v.into_deserializer()).map(RawParam::Constant)
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                T::deserialize(v.into_deserializer()).map(RawParam::Constant)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                RawTexture::deserialize(MapAccessDeserializer::new(map)).map(RawParam::Texture)
+            }
+        }
+
+        deserializer.deserialize_any(ParamVisitor(PhantomData))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "texture", rename_all = "lowercase", deny_unknown_fields)]
+enum RawTexture<T> {
+    Checker {
+        even: T,
+        odd: T,
+        scale: Option<f32>,
+    },
+    Image {
+        path: String,
+        color_space: Option<RawColorSpace>,
+        wrap: Option<RawWrap>,
+        filter: Option<RawFilter>,
+    },
+    Checker3d {
+        even: T,
+        odd: T,
+        scale: Option<f32>,
+        space: Option<RawSpace>,
+    },
+    Fbm(RawPattern<T>),
+    Turbulence(RawPattern<T>),
+    Marble(RawPattern<T>),
+    Wood(RawPattern<T>),
+    Gradient {
+        from: [f32; 3],
+        to: [f32; 3],
+        space: Option<RawSpace>,
+        ramp: Option<Vec<(f32, T)>>,
+    },
+}
+
+/// Noise pattern, mapped onto values by its ramp.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPattern<T> {
+    noise: Option<RawNoise>,
+    octaves: Option<u32>,
+    lacunarity: Option<f32>,
+    gain: Option<f32>,
+    scale: Option<f32>,
+    distortion: Option<f32>,
+    space: Option<RawSpace>,
+    ramp: Option<Vec<(f32, T)>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawNoise {
+    Perlin,
+    Simplex,
+    Worley,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawSpace {
+    Object,
+    World,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawColorSpace {
+    Srgb,
+    Linear,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawWrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawFilter {
+    Nearest,
+    Bilinear,
+    Trilinear,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSubdivision {
+    scheme: RawScheme,
+    levels: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawScheme {
+    CatmullClark,
+    Loop,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDisplacement {
+    height: Option<RawTexture<f32>>,
+    vector: Option<RawImage>,
+    scale: Option<f32>,
+}
+
+impl RawMaterial {
+    /// Textured parameters being the texture values, scaled by 1.
+    fn build(self, dir: &Path, line: usize) -> Result<Material, SceneError> {
+        let white = Color {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        };
+        let mut material = Material::new(white, 1.0);
+        match self.color {
+            RawParam::Constant(color) => material.color = color.0,
+            RawParam::Texture(t) => {
+                let texture = t.build(|c| c.0, dir, line)?;
+                material.color_texture = Some(texture);
+            }
+        }
+        match self.albedo {
+            RawParam::Constant(albedo) => material.albedo = albedo,
+            RawParam::Texture(t) => {
+                let texture = t.build(|a| a, dir, line)?;
+                material.albedo_texture = Some(texture);
+            }
+        }
+        let invalid = |message: &str| SceneError::Invalid {
+            line,
+            message: message.into(),
+        };
+        material.relief = match (self.normal_map, self.bump) {
+            (Some(_), Some(_)) => return Err(invalid("both a normal map and a bump")),
+            (Some(map), None) => {
+                let image = load_image(
+                    &map.path,
+                    ColorSpace::Linear,
+                    map.wrap,
+                    map.filter,
+                    dir,
+                    line,
+                )?;
+                Some(Relief::NormalMap(Arc::new(image)))
+            }
+            (None, Some(bump)) => Some(Relief::Bump {
+                height: bump.build(|h| h, dir, line)?,
+                scale: self.bump_height.unwrap_or(1.0),
+            }),
+            (None, None) => None,
+        };
+        if self.bump_height.is_some() && !matches!(material.relief, Some(Relief::Bump { .. })) {
+            return Err(invalid("bump_height without a bump"));
+        }
+        Ok(material)
+    }
+}
+
+/// Values of the material parameters.
+trait ParamValue: Lerp + fmt::Debug + Send + Sync + 'static {
+    /// Color space of the 8 bit images giving the values.
+    const COLOR_SPACE: ColorSpace;
+
+    /// Value of a pattern without a ramp.
+    fn gray(value: f32) -> Self;
+}
+
+impl ParamValue for Color {
+    const COLOR_SPACE: ColorSpace = ColorSpace::Srgb;
+
+    fn gray(value: f32) -> Color {
+        Color {
+            red: value,
+            green: value,
+            blue: value,
+        }
+    }
+}
+
+impl ParamValue for f32 {
+    const COLOR_SPACE: ColorSpace = ColorSpace::Linear;
+
+    fn gray(value: f32) -> f32 {
+        value
+    }
+}
+
+fn space(space: Option<RawSpace>) -> Space {
+    match space {
+        Some(RawSpace::Object) | None => Space::Object,
+        Some(RawSpace::World) => Space::World,
+    }
+}
+
+impl<T> RawTexture<T> {
+    /// Texture of `value`s.
+    fn build<V: ParamValue>(
+        self,
+        value: impl Fn(T) -> V,
+        dir: &Path,
+        line: usize,
+    ) -> Result<Arc<dyn Texture<V>>, SceneError>
+    where
+        ImageTexture: Texture<V>,
+    {
+        let invalid = |message: String| SceneError::Invalid { line, message };
+        // values looked up, or patterns and their ramps
+        let (pattern, ramp): (Arc<dyn Texture<f32>>, _) = match self {
+            RawTexture::Checker { even, odd, scale } => {
+                return Ok(Arc::new(Checker {
+                    even: value(even),
+                    odd: value(odd),
+                    scale: scale.unwrap_or(1.0),
+                }))
+            }
+            RawTexture::Image {
+                path,
+                color_space: space,
+                wrap,
+                filter,
+            } => {
+                let color_space = match space {
+                    Some(RawColorSpace::Srgb) => ColorSpace::Srgb,
+                    Some(RawColorSpace::Linear) => ColorSpace::Linear,
+                    None => V::COLOR_SPACE,
+                };
+                let image = load_image(&path, color_space, wrap, filter, dir, line)?;
+                return Ok(Arc::new(image));
+            }
+            RawTexture::Checker3d {
+                even,
+                odd,
+                scale,
+                space: s,
+            } => {
+                return Ok(Arc::new(Checker3d {
+                    even: value(even),
+                    odd: value(odd),
+                    scale: scale.unwrap_or(1.0),
+                    space: space(s),
+                }))
+            }
+            RawTexture::Fbm(p) | RawTexture::Turbulence(p) if p.distortion.is_some() => {
+                return Err(invalid("only marble and wood have a distortion".into()));
+            }
+            RawTexture::Fbm(p) => {
+                let pattern = Fbm {
+                    fractal: p.fractal(),
+                    scale: p.scale.unwrap_or(1.0),
+                    space: space(p.space),
+                };
+                (Arc::new(pattern), p.ramp)
+            }
+            RawTexture::Turbulence(p) => {
+                let pattern = Turbulence {
+                    fractal: p.fractal(),
+                    scale: p.scale.unwrap_or(1.0),
+                    space: space(p.space),
+                };
+                (Arc::new(pattern), p.ramp)
+            }
+            RawTexture::Marble(p) => {
+                let pattern = Marble {
+                    fractal: p.fractal(),
+                    scale: p.scale.unwrap_or(1.0),
+                    distortion: p.distortion.unwrap_or(4.0),
+                    space: space(p.space),
+                };
+                (Arc::new(pattern), p.ramp)
+            }
+            RawTexture::Wood(p) => {
+                let pattern = Wood {
+                    fractal: p.fractal(),
+                    scale: p.scale.unwrap_or(1.0),
+                    distortion: p.distortion.unwrap_or(0.3),
+                    space: space(p.space),
+                };
+                (Arc::new(pattern), p.ramp)
+            }
+            RawTexture::Gradient {
+                from,
+                to,
+                space: s,
+                ramp,
+            } => {
+                if from == to {
+                    return Err(invalid("gradient `from` and `to` are the same".into()));
+                }
+                let pattern = Gradient {
+                    from: vertex(from),
+                    to: vertex(to),
+                    space: space(s),
+                };
+                (Arc::new(pattern), ramp)
+            }
+        };
+        let stops = match ramp {
+            Some(stops) => stops.into_iter().map(|(t, v)| (t, value(v))).collect(),
+            None => vec![(0.0, V::gray(0.0)), (1.0, V::gray(1.0))],
+        };
+        match Ramp::new(pattern, stops) {
+            Some(ramp) => Ok(Arc::new(ramp)),
+            None => Err(invalid("empty ramp".into())),
+        }
+    }
+}
+
+fn load_image(
+    path: &str,
+    color_space: ColorSpace,
+    wrap: Option<RawWrap>,
+    filter: Option<RawFilter>,
+    dir: &Path,
+    line: usize,
+) -> Result<ImageTexture, SceneError> {
+    let mut image =
+        ImageTexture::load(dir.join(path), color_space).map_err(|e| SceneError::Invalid {
+            line,
+            message: format!("{}: {}", path, e),
+        })?;
+    image.wrap = match wrap {
+        Some(RawWrap::Repeat) | None => Wrap::Repeat,
+        Some(RawWrap::Clamp) => Wrap::Clamp,
+        Some(RawWrap::Mirror) => Wrap::Mirror,
+    };
+    image.filter = match filter {
+        Some(RawFilter::Nearest) => Filter::Nearest,
+        Some(RawFilter::Bilinear) => Filter::Bilinear,
+        Some(RawFilter::Trilinear) | None => Filter::Trilinear,
+    };
+    Ok(image)
+}
+
+impl<T> RawPattern<T> {
+    fn fractal(&self) -> Fractal {
+        let default = Fractal::default();
+        Fractal {
+            noise: match self.noise {
+                Some(RawNoise::Perlin) | None => Noise::Perlin,
+                Some(RawNoise::Simplex) => Noise::Simplex,
+                Some(RawNoise::Worley) => Noise::Worley,
+            },
+            octaves: self.octaves.unwrap_or(default.octaves),
+            lacunarity: self.lacunarity.unwrap_or(default.lacunarity),
+            gain: self.gain.unwrap_or(default.gain),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawObject {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Plane {
+        point: [f32; 3],
+        normal: [f32; 3],
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Triangle {
+        vertices: [[f32; 3]; 3],
+        uvs: Option<[[f32; 2]; 3]>,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Box {
+        min: [f32; 3],
+        max: [f32; 3],
+        rotate: Option<RawRotation>,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Disc {
+        center: [f32; 3],
+        normal: [f32; 3],
+        radius: f32,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Quad {
+        corner: [f32; 3],
+        edges: [[f32; 3]; 2],
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Cylinder {
+        base: [f32; 3],
+        top: [f32; 3],
+        radius: f32,
+        capped: Option<bool>,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Cone {
+        base: [f32; 3],
+        top: [f32; 3],
+        radius: f32,
+        top_radius: Option<f32>,
+        capped: Option<bool>,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Torus {
+        center: [f32; 3],
+        axis: Option<[f32; 3]>,
+        major_radius: f32,
+        minor_radius: f32,
+        material: String,
+        transform: Option<RawTransform>,
+    },
+    Mesh {
+        path: String,
+        material: String,
+        subdivision: Option<RawSubdivision>,
+        displacement: Option<RawDisplacement>,
+        transform: Option<RawTransform>,
+    },
+    Instance {
+        geometry: String,
+        material: Option<String>,
+        transform: Option<RawTransform>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTransform {
+    translate: Option<[f32; 3]>,
+    rotate: Option<RawRotation>,
+    scale: Option<RawScale>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRotation {
+    axis: [f32; 3],
+    angle: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawScale {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl RawTransform {
+    /// Scale, then rotation, then translation.
+    fn matrix(&self) -> Matrix4 {
+        let scale = match self.scale {
+            Some(RawScale::Uniform(s)) => Matrix4::scaling(Vector { x: s, y: s, z: s }),
+            Some(RawScale::Axes(s)) => Matrix4::scaling(vector(s)),
+            None => Matrix4::identity(),
+        };
+        let rotation = match &self.rotate {
+            Some(r) => Matrix4::from_axis_angle(vector(r.axis), Degree(r.angle).into()),
+            None => Matrix4::identity(),
+        };
+        let translation = match self.translate {
+            Some(t) => Matrix4::translation(vector(t)),
+            None => Matrix4::identity(),
+        };
+        translation * rotation * scale
+    }
+
+    fn build(&self, line: usize) -> Result<Transform, SceneError> {
+        Transform::new(self.matrix()).ok_or_else(|| SceneError::Invalid {
+            line,
+            message: "transform is not invertible".into(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum RawLight {
+    Directional {
+        direction: [f32; 3],
+        color: ColorValue,
+        intensity: f32,
+    },
+    Spherical {
+        position: [f32; 3],
+        color: ColorValue,
+        intensity: f32,
+    },
+}
+
+impl RawScene {
+    fn build(self, src: &str, dir: &Path) -> Result<SceneFile, SceneError> {
+        let defaults = Settings::default();
+        let settings = Settings {
+            width: self.render.width.unwrap_or(defaults.width),
+            height: self.render.height.unwrap_or(defaults.height),
+            samples: self.render.samples.unwrap_or(defaults.samples),
+            max_depth: self.render.max_depth.unwrap_or(defaults.max_depth),
+            integrator: match self.render.integrator {
+                Some(RawIntegrator::Direct) => Integrator::Direct,
+                Some(RawIntegrator::Path) => Integrator::Path,
+                None => defaults.integrator,
+            },
+            seed: self.render.seed.unwrap_or(defaults.seed),
+            denoise: self.render.denoise.unwrap_or(defaults.denoise),
+            threads: defaults.threads,
+            region: defaults.region,
+        };
+
+        let look_at = self.camera.as_ref().and_then(|raw| raw.get_ref().look_at);
+        let look_at = look_at.map(vertex);
+        let camera = match self.camera {
+            Some(raw) => {
+                let line = line_of(src, raw.span().start);
+                raw.into_inner().build(line)?
+            }
+            None => Camera::default(),
+        };
+
+        let mut materials = HashMap::new();
+        for (name, raw) in self.materials {
+            let line = line_of(src, raw.span().start);
+            let material = Material {
+                name: Some(name.as_str().into()),
+                ..raw.into_inner().build(dir, line)?
+            };
+            materials.insert(name, material);
+        }
+        let mut builder = Builder {
+            src,
+            dir,
+            materials,
+            geometries: HashMap::new(),
+        };
+        let mut geometries = HashMap::new();
+        for (name, raw) in self.geometries {
+            let line = line_of(src, raw.span().start);
+            let geometry = builder.geometry(raw.into_inner(), line)?;
+            geometries.insert(name, Arc::new(geometry));
+        }
+        builder.geometries = geometries;
+
+        let mut graph = SceneGraph::default();
+        for raw in self.nodes {
+            let line = line_of(src, raw.span().start);
+            let raw = raw.into_inner();
+            let (parent, name) = raw.path.rsplit_once('/').unwrap_or(("", &raw.path));
+            let mut node = Node::group(name);
+            if let Some(transform) = raw.transform {
+                node.transform = transform.build(line)?;
+            }
+            node.visible = raw.visible.unwrap_or(true);
+            node.velocity = raw.velocity.map(vector).unwrap_or_default();
+            insert(&mut graph, parent, node, line)?;
+        }
+        for raw in self.objects {
+            let line = line_of(src, raw.span().start);
+            let Named {
+                name,
+                parent,
+                velocity,
+                item,
+            } = raw.into_inner();
+            let object = builder.object(item, line)?;
+            let mut node = Node::object(name.as_deref().unwrap_or(""), object);
+            node.velocity = velocity.map(vector).unwrap_or_default();
+            insert(&mut graph, parent.as_deref().unwrap_or(""), node, line)?;
+        }
+
+        for raw in self.lights {
+            let line = line_of(src, raw.span().start);
+            let Named {
+                name,
+                parent,
+                velocity,
+                item,
+            } = raw.into_inner();
+            let light: Light = match item {
+                RawLight::Directional {
+                    direction,
+                    color,
+                    intensity,
+                } => DirectionalLight {
+                    direction: vector(direction),
+                    base_color: color.0,
+                    base_intensity: intensity,
+                }
+                .into(),
+                RawLight::Spherical {
+                    position,
+                    color,
+                    intensity,
+                } => SphericalLight {
+                    position: vertex(position),
+                    base_color: color.0,
+                    base_intensity: intensity,
+                }
+                .into(),
+            };
+            let mut node = Node::light(name.as_deref().unwrap_or(""), light);
+            node.velocity = velocity.map(vector).unwrap_or_default();
+            insert(&mut graph, parent.as_deref().unwrap_or(""), node, line)?;
+        }
+
+        let mut animation = match self.animation {
+            Some(raw) => raw.build(src, &graph, &builder.materials)?,
+            None => Animation::default(),
+        };
+        animation.look_at = look_at;
+        let mut file = SceneFile {
+            scene: Scene::default(),
+            graph,
+            animation,
+            settings,
+            camera,
+            materials: builder.materials,
+        };
+        file.scene = file.scene_at(file.animation.start());
+        Ok(file)
+    }
+}
+
+impl RawAnimation {
+    fn build(
+        self,
+        src: &str,
+        graph: &SceneGraph,
+        materials: &HashMap<String, Material>,
+    ) -> Result<Animation, SceneError> {
+        let defaults = Animation::default();
+        let mut animation = Animation {
+            fps: self.fps.unwrap_or(defaults.fps),
+            shutter: self.shutter.unwrap_or(defaults.shutter),
+            ..defaults
+        };
+        let line = line_of(src, self.tracks.first().map_or(0, |t| t.span().start));
+        if animation.fps <= 0.0 {
+            return Err(SceneError::Invalid {
+                line,
+                message: "fps must be positive".into(),
+            });
+        }
+        if !(0.0..=1.0).contains(&animation.shutter) {
+            return Err(SceneError::Invalid {
+                line,
+                message: "shutter must be a fraction of the frame interval".into(),
+            });
+        }
+        for raw in self.tracks {
+            let line = line_of(src, raw.span().start);
+            animation
+                .tracks
+                .push(raw.into_inner().build(graph, materials, line)?);
+        }
+        animation.frames = match self.frames {
+            Some([first, last]) if first <= last => (first, last),
+            Some(_) => {
+                return Err(SceneError::Invalid {
+                    line,
+                    message: "frames must be [first, last]".into(),
+                })
+            }
+            // up to the last keyframe
+            None => (
+                1,
+                1 + (animation.duration() * animation.fps).ceil() as usize,
+            ),
+        };
+        Ok(animation)
+    }
+}
+
+impl RawTrack {
+    fn build(
+        self,
+        graph: &SceneGraph,
+        materials: &HashMap<String, Material>,
+        line: usize,
+    ) -> Result<Animated, SceneError> {
+        let invalid = |message: String| SceneError::Invalid { line, message };
+        let target = match (self.node, self.material, self.camera) {
+            (Some(path), None, None) => match graph.find(&path) {
+                Some(_) => Target::Node(path),
+                None => return Err(invalid(format!("unknown node `{}`", path))),
+            },
+            (None, Some(name), None) => match materials.get(&name) {
+                Some(_) => Target::Material(name),
+                None => return Err(invalid(format!("unknown material `{}`", name))),
+            },
+            (None, None, Some(true)) => Target::Camera,
+            _ => {
+                return Err(invalid(
+                    "a track animates one of `node`, `material` or `camera = true`".into(),
+                ))
+            }
+        };
+        let light = match &target {
+            Target::Node(path) => matches!(
+                graph.find(path).map(|n| &n.content),
+                Some(Content::Light(_))
+            ),
+            _ => false,
+        };
+        let keys = self.keys;
+        let point = |v: &RawValue| match v {
+            RawValue::Triple(p) => Some(*p),
+            _ => None,
+        };
+        let number = |v: &RawValue| match v {
+            RawValue::Number(n) => Some(*n),
+            _ => None,
+        };
+        let color = |v: &RawValue| match v {
+            RawValue::Color(c) => Some(c.0),
+            _ => None,
+        };
+        let channel = match (&target, self.property.as_str()) {
+            (Target::Node(_), "translate") => {
+                Channel::Translate(track(keys, line, "[x, y, z]", |v| point(v).map(vector))?)
+            }
+            (Target::Node(_), "rotate") => {
+                Channel::Rotate(track(keys, line, "{ axis, angle }", |v| match v {
+                    RawValue::Rotation(r) => Some(Rotation {
+                        axis: vector(r.axis),
+                        angle: Degree(r.angle).into(),
+                    }),
+                    _ => None,
+                })?)
+            }
+            (Target::Node(_), "scale") => {
+                Channel::Scale(track(keys, line, "a number or [x, y, z]", |v| match v {
+                    RawValue::Number(s) => Some(Vector {
+                        x: *s,
+                        y: *s,
+                        z: *s,
+                    }),
+                    RawValue::Triple(s) => Some(vector(*s)),
+                    _ => None,
+                })?)
+            }
+            (Target::Node(_), "color") if light => {
+                Channel::Color(track(keys, line, "a color", color)?)
+            }
+            (Target::Node(_), "intensity") if light => {
+                Channel::Intensity(track(keys, line, "a number", number)?)
+            }
+            (Target::Material(_), "color") => Channel::Color(track(keys, line, "a color", color)?),
+            (Target::Material(_), "albedo") => {
+                Channel::Albedo(track(keys, line, "a number", number)?)
+            }
+            (Target::Camera, "position") => {
+                Channel::Position(track(keys, line, "[x, y, z]", |v| point(v).map(vertex))?)
+            }
+            (Target::Camera, "look_at") => {
+                Channel::LookAt(track(keys, line, "[x, y, z]", |v| point(v).map(vertex))?)
+            }
+            (Target::Camera, "fov") => Channel::Fov(track(keys, line, "a number", number)?),
+            (Target::Camera, "aperture") => {
+                Channel::Aperture(track(keys, line, "a number", number)?)
+            }
+            (Target::Camera, "focus_distance") => {
+                Channel::Focus(track(keys, line, "a number", number)?)
+            }
+            (_, property) => {
+                return Err(invalid(format!("`{}` cannot be animated here", property)))
+            }
+        };
+        Ok(Animated { target, channel })
+    }
+}
+
+/// Track of the values read by `value`, `expected` describing them.
+fn track<T: Lerp>(
+    keys: Vec<RawKey>,
+    line: usize,
+    expected: &str,
+    value: impl Fn(&RawValue) -> Option<T>,
+) -> Result<Track<T>, SceneError> {
+    let invalid = |message: String| SceneError::Invalid { line, message };
+    let keys = keys
+        .into_iter()
+        .map(|key| {
+            let interpolation = match (key.interpolation, key.handles) {
+                (None | Some(RawInterpolation::Linear), None) => Interpolation::Linear,
+                (Some(RawInterpolation::Bezier), None) => Interpolation::EASE,
+                (Some(RawInterpolation::Bezier), Some(h))
+                    if (0.0..=1.0).contains(&h[0]) && (0.0..=1.0).contains(&h[2]) =>
+                {
+                    Interpolation::Bezier(h)
+                }
+                (Some(RawInterpolation::Bezier), Some(_)) => {
+                    return Err(invalid("bezier handles need x1 and x2 in [0, 1]".into()))
+                }
+                (_, Some(_)) => return Err(invalid("handles are only for bezier keys".into())),
+            };
+            Ok(Keyframe {
+                time: key.time,
+                value: value(&key.value)
+                    .ok_or_else(|| invalid(format!("expected keys with {} values", expected)))?,
+                interpolation,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Track::new(keys).ok_or_else(|| invalid("a track needs at least one key".into()))
+}
+
+fn insert(graph: &mut SceneGraph, parent: &str, node: Node, line: usize) -> Result<(), SceneError> {
+    graph
+        .insert(parent, node)
+        .map(|_| ())
+        .map_err(|e| SceneError::Invalid {
+            line,
+            message: e.to_string(),
+        })
+}
+
+/// What objects may refer to while being built.
+struct Builder<'a> {
+    src: &'a str,
+    dir: &'a Path,
+    materials: HashMap<String, Material>,
+    geometries: HashMap<String, Arc<Geometry>>,
+}
+
+impl Builder<'_> {
+    fn material(&self, name: &str, line: usize) -> Result<Material, SceneError> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SceneError::Invalid {
+                line,
+                message: format!("unknown material `{}`", name),
+            })
+    }
+
+    fn mesh(
+        &self,
+        path: &str,
+        material: Material,
+        subdivision: Option<RawSubdivision>,
+        displacement: Option<RawDisplacement>,
+        line: usize,
+    ) -> Result<Vec<Object>, SceneError> {
+        let invalid = |message: String| SceneError::Invalid { line, message };
+        let mut mesh =
+            Mesh::load(self.dir.join(path)).map_err(|e| invalid(format!("{}: {}", path, e)))?;
+        if let Some(subdivision) = subdivision {
+            if subdivision.levels > MAX_SUBDIVISION_LEVELS {
+                return Err(invalid(format!(
+                    "at most {} levels of subdivision",
+                    MAX_SUBDIVISION_LEVELS
+                )));
+            }
+            let scheme = match subdivision.scheme {
+                RawScheme::CatmullClark => Scheme::CatmullClark,
+                RawScheme::Loop => Scheme::Loop,
+            };
+            mesh = subdivide(&mesh, scheme, subdivision.levels);
+        }
+        if let Some(displacement) = displacement {
+            let scale = displacement.scale.unwrap_or(1.0);
+            let displacement = match (displacement.height, displacement.vector) {
+                (Some(height), None) => Displacement::Scalar {
+                    height: height.build(|h| h, self.dir, line)?,
+                    scale,
+                },
+                (None, Some(image)) => {
+                    let offset = load_image(
+                        &image.path,
+                        ColorSpace::Linear,
+                        image.wrap,
+                        image.filter,
+                        self.dir,
+                        line,
+                    )?;
+                    Displacement::Vector {
+                        offset: Arc::new(offset),
+                        scale,
+                    }
+                }
+                _ => {
+                    return Err(invalid(
+                        "a displacement needs either a height or a vector".into(),
+                    ))
+                }
+            };
+            displacement.displace(&mut mesh);
+        }
+        Ok(mesh
+            .triangles()
+            .map(|(vertices, uvs)| {
+                let mut triangle = Triangle::new(vertices, material.clone());
+                if let Some(uvs) = uvs {
+                    triangle.uvs = uvs;
+                }
+                triangle.into()
+            })
+            .collect())
+    }
+
+    fn geometry(&self, raw: RawGeometry, line: usize) -> Result<Geometry, SceneError> {
+        let mut objects = Vec::new();
+        if let Some(path) = raw.mesh {
+            let name = raw.material.ok_or_else(|| SceneError::Invalid {
+                line,
+                message: "a mesh needs a material".into(),
+            })?;
+            let material = self.material(&name, line)?;
+            objects = self.mesh(&path, material, raw.subdivision, raw.displacement, line)?;
+        } else if raw.subdivision.is_some() || raw.displacement.is_some() {
+            return Err(SceneError::Invalid {
+                line,
+                message: "a subdivision or displacement needs a mesh".into(),
+            });
+        }
+        for raw in raw.objects {
+            let line = line_of(self.src, raw.span().start);
+            objects.push(self.object(raw.into_inner(), line)?);
+        }
+        Ok(Geometry::new(objects))
+    }
+
+    fn object(&self, raw: RawObject, line: usize) -> Result<Object, SceneError> {
+        let invalid = |message: &str| SceneError::Invalid {
+            line,
+            message: message.into(),
+        };
+        let (object, transform): (Object, _) = match raw {
+            RawObject::Sphere {
+                center,
+                radius,
+                material,
+                transform,
+            } => {
+                let m = self.material(&material, line)?;
+                let sphere = Sphere {
+                    center: vertex(center),
+                    radius,
+                    material: m,
+                };
+                (sphere.into(), transform)
+            }
+            RawObject::Plane {
+                point,
+                normal,
+                material,
+                transform,
+            } => {
+                let m = self.material(&material, line)?;
+                let plane = Plane {
+                    point: vertex(point),
+                    normal: vector(normal),
+                    material: m,
+                };
+                (plane.into(), transform)
+            }
+            RawObject::Triangle {
+                vertices: [a, b, c],
+                uvs,
+                material,
+                transform,
+            } => {
+                let m = self.material(&material, line)?;
+                let mut triangle = Triangle::new([vertex(a), vertex(b), vertex(c)], m);
+                if let Some(uvs) = uvs {
+                    triangle.uvs = uvs.map(|[u, v]| Uv { u, v });
+                }
+                (triangle.into(), transform)
+            }
+            RawObject::Box {
+                min,
+                max,
+                rotate,
+                material,
+                transform,
+            } => {
+                if (0..3).any(|i| min[i] >= max[i]) {
+                    return Err(invalid("a box needs its min below its max"));
+                }
+                let m = self.material(&material, line)?;
+                let mut cuboid = Cuboid::aligned(vertex(min), vertex(max), m);
+                if let Some(r) = rotate {
+                    // around its center
+                    let (axis, angle) = (vector(r.axis).normalize(), Degree(r.angle).into());
+                    let frame = cuboid.frame;
+                    cuboid.frame = Frame {
+                        x: frame.x.rotate(axis, angle),
+                        y: frame.y.rotate(axis, angle),
+                        z: frame.z.rotate(axis, angle),
+                        ..frame
+                    };
+                }
+                (cuboid.into(), transform)
+            }
+            RawObject::Disc {
+                center,
+                normal,
+                radius,
+                material,
+                transform,
+            } => {
+                if vector(normal).norm() == 0.0 {
+                    return Err(invalid("a disc needs a normal"));
+                }
+                let m = self.material(&material, line)?;
+                let disc = Disc::new(vertex(center), vector(normal), radius, m);
+                (disc.into(), transform)
+            }
+            RawObject::Quad {
+                corner,
+                edges: [a, b],
+                material,
+                transform,
+            } => {
+                if vector(a).cross(vector(b)).norm() == 0.0 {
+                    return Err(invalid("a quad needs edges along two directions"));
+                }
+                let m = self.material(&material, line)?;
+                let quad = Quad {
+                    corner: vertex(corner),
+                    edges: [vector(a), vector(b)],
+                    material: m,
+                };
+                (quad.into(), transform)
+            }
+            RawObject::Cylinder {
+                base,
+                top,
+                radius,
+                capped,
+                material,
+                transform,
+            } => {
+                if base == top {
+                    return Err(invalid("a cylinder needs its top apart from its base"));
+                }
+                let m = self.material(&material, line)?;
+                let mut cylinder = Cylinder::new(vertex(base), vertex(top), radius, m);
+                cylinder.cone.capped = capped.unwrap_or(true);
+                (cylinder.into(), transform)
+            }
+            RawObject::Cone {
+                base,
+                top,
+                radius,
+                top_radius,
+                capped,
+                material,
+                transform,
+            } => {
+                if base == top {
+                    return Err(invalid("a cone needs its top apart from its base"));
+                }
+                let top_radius = top_radius.unwrap_or(0.0);
+                let m = self.material(&material, line)?;
+                let mut cone = Cone::new(vertex(base), vertex(top), radius, top_radius, m);
+                cone.capped = capped.unwrap_or(true);
+                (cone.into(), transform)
+            }
+            RawObject::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                material,
+                transform,
+            } => {
+                let axis = vector(axis.unwrap_or([0.0, 1.0, 0.0]));
+                if axis.norm() == 0.0 {
+                    return Err(invalid("a torus needs an axis"));
+                }
+                let m = self.material(&material, line)?;
+                let torus = Torus::new(vertex(center), axis, major_radius, minor_radius, m);
+                (torus.into(), transform)
+            }
+            RawObject::Mesh {
+                path,
+                material,
+                subdivision,
+                displacement,
+                transform,
+            } => {
+                let m = self.material(&material, line)?;
+                let triangles = self.mesh(&path, m, subdivision, displacement, line)?;
+                let instance = Instance {
+                    geometry: Arc::new(Geometry::new(triangles)),
+                    transform: Transform::default(),
+                    material: None,
+                };
+                (instance.into(), transform)
+            }
+            RawObject::Instance {
+                geometry,
+                material,
+                transform,
+            } => {
+                let shared = self
+                    .geometries
+                    .get(&geometry)
+                    .ok_or_else(|| SceneError::Invalid {
+                        line,
+                        message: format!("unknown geometry `{}`", geometry),
+                    })?;
+                let instance = Instance {
+                    geometry: shared.clone(),
+                    transform: Transform::default(),
+                    material: match material {
+                        Some(name) => Some(self.material(&name, line)?),
+                        None => None,
+                    },
+                };
+                (instance.into(), transform)
+            }
+        };
+        match transform {
+            Some(raw) => Ok(object.transformed(raw.build(line)?)),
+            None => Ok(object),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::texture::Surface;
+    use crate::tracer::{Ray, RayKind};
+
+    const SCENE: &str = r##"
+[render]
+samples = 4
+integrator = "path"
+
+[materials.red]
+color = "#FF0000"
+albedo = 0.5
+
+[[objects]]
+type = "sphere"
+center = [0, 0, -10]
+radius = 2
+material = "red"
+
+[[lights]]
+type = "directional"
+direction = [0, -1, 0]
+color = "#FFFFFF"
+intensity = 1
+"##;
+
+    #[test]
+    fn parse() {
+        let file: SceneFile = SCENE.parse().unwrap();
+        assert_eq!(file.settings.samples, 4);
+        assert_eq!(file.settings.integrator, Integrator::Path);
+        assert_eq!(file.scene.objects.len(), 1);
+        assert_eq!(file.scene.lights.len(), 1);
+        assert_eq!(file.scene.objects[0].color().red, 1.0);
+        assert_eq!(file.scene.camera, Camera::default());
+    }
+
+    #[test]
+    fn textures() {
+        let src = SCENE.replace(
+            "color = \"#FF0000\"",
+            "color = { texture = \"checker\", even = \"#FF0000\", odd = \"#0000FF\", scale = 2 }",
+        );
+        let file: SceneFile = src.parse().unwrap();
+        let material = file.scene.objects[0].material().unwrap();
+        let at = |u, v| Surface {
+            uv: Uv { u, v },
+            ..Surface::default()
+        };
+        assert_eq!(material.color_at(&at(0.1, 0.1)).red, 1.0);
+        assert_eq!(material.color_at(&at(0.6, 0.1)).blue, 1.0);
+        assert_eq!(material.albedo_at(&at(0.6, 0.1)), 0.5);
+
+        let src = SCENE.replace(
+            "albedo = 0.5",
+            "albedo = { texture = \"image\", path = \"missing.png\" }",
+        );
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 6),
+            _ => panic!("expected a missing image"),
+        }
+        let src = SCENE.replace("albedo = 0.5", "albedo = { texture = \"bricks\" }");
+        assert!(matches!(
+            src.parse::<SceneFile>(),
+            Err(SceneError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn procedural_textures() {
+        let gradient = "{ texture = \"gradient\", from = [0, 0, 0], to = [0, 2, 0], \
+                        ramp = [[0, \"#000000\"], [1, \"#FF0000\"]] }";
+        let src = SCENE
+            .replace("color = \"#FF0000\"", &format!("color = {}", gradient))
+            .replace(
+                "albedo = 0.5",
+                "albedo = { texture = \"marble\", noise = \"simplex\", octaves = 3 }",
+            );
+        let file: SceneFile = src.parse().unwrap();
+        let material = file.scene.objects[0].material().unwrap();
+        let at = |y| Surface {
+            point: Vertex { x: 0.3, y, z: 0.0 },
+            ..Surface::default()
+        };
+        assert_eq!(material.color_at(&at(1.0)).red, 0.5);
+        assert_eq!(material.color_at(&at(3.0)).red, 1.0);
+        assert!((0.0..=1.0).contains(&material.albedo_at(&at(1.0))));
+
+        for (texture, message) in [
+            ("{ texture = \"fbm\", distortion = 2 }", "distortion"),
+            ("{ texture = \"wood\", ramp = [] }", "empty ramp"),
+            (
+                "{ texture = \"gradient\", from = [1, 1, 1], to = [1, 1, 1] }",
+                "the same",
+            ),
+        ] {
+            let src = SCENE.replace("albedo = 0.5", &format!("albedo = {}", texture));
+            match src.parse::<SceneFile>() {
+                Err(e @ SceneError::Invalid { line: 6, .. }) => {
+                    assert!(e.to_string().contains(message), "{}", e)
+                }
+                _ => panic!("expected an invalid {}", texture),
+            }
+        }
+    }
+
+    #[test]
+    fn relief() {
+        let bumped = "albedo = 0.5\nbump = { texture = \"fbm\", scale = 4 }\nbump_height = 0.1";
+        let file: SceneFile = SCENE.replace("albedo = 0.5", bumped).parse().unwrap();
+        let material = file.scene.objects[0].material().unwrap();
+        assert!(matches!(
+            material.relief,
+            Some(Relief::Bump { scale, .. }) if scale == 0.1
+        ));
+
+        for invalid in [
+            "bump_height = 0.1",
+            "normal_map = { path = \"missing.png\" }",
+            "normal_map = { path = \"n.png\" }\nbump = { texture = \"wood\" }",
+        ] {
+            let src = SCENE.replace("albedo = 0.5", &format!("albedo = 0.5\n{}", invalid));
+            match src.parse::<SceneFile>() {
+                Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 6),
+                _ => panic!("expected an invalid relief"),
+            }
+        }
+    }
+
+    #[test]
+    fn camera_round_trip() {
+        let camera = Camera::look_at(
+            Vertex {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            Vertex {
+                x: 1.0,
+                y: 2.0,
+                z: -7.0,
+            },
+            Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Degree(60.0),
+        );
+        let file: SceneFile = format_camera(&camera).parse().unwrap();
+        assert_eq!(file.scene.camera, camera);
+    }
+
+    #[test]
+    fn lens() {
+        let src = "[camera]\nposition = [0, 0, 0]\nlook_at = [0, 0, -5]\nf_stop = 2.8\n";
+        let camera = src.parse::<SceneFile>().unwrap().scene.camera;
+        assert_eq!(camera.lens.focus_distance, 5.0);
+        assert_eq!(camera.target_distance, 5.0);
+        let file: SceneFile = format_camera(&camera).parse().unwrap();
+        assert_eq!(file.scene.camera.target_distance, 5.0);
+        assert_eq!(camera.lens.aperture, Lens::aperture_of(2.8, camera.fov));
+
+        let camera = Camera {
+            lens: Lens {
+                aperture: 0.1,
+                focus_distance: 3.0,
+                blades: 5,
+                rotation: Degree(10.0),
+            },
+            ..Camera::default()
+        };
+        let file: SceneFile = format_camera(&camera).parse().unwrap();
+        assert_eq!(file.scene.camera.lens, camera.lens);
+
+        for invalid in [
+            "aperture = 1\nf_stop = 2",
+            "blades = 2",
+            "focus_distance = 0",
+        ] {
+            match format!("[camera]\n{}\n", invalid).parse::<SceneFile>() {
+                Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 1),
+                _ => panic!("expected an invalid camera"),
+            }
+        }
+    }
+
+    #[test]
+    fn projection() {
+        let src = "[camera]\nlook_at = [0, 0, -3]\nfov = 90\nprojection = \"orthographic\"\n";
+        let camera = src.parse::<SceneFile>().unwrap().scene.camera;
+        match camera.projection {
+            Projection::Orthographic(p) => assert!((p.height - 6.0).abs() < 1e-5),
+            p => panic!("expected an orthographic projection, not {:?}", p),
+        }
+
+        let projections = [
+            Orthographic { height: 3.0 }.into(),
+            Fisheye {
+                mapping: FisheyeMapping::Equisolid,
+            }
+            .into(),
+            Equirectangular.into(),
+            Cubemap.into(),
+        ];
+        for projection in projections {
+            let camera = Camera {
+                projection,
+                ..Camera::default()
+            };
+            let file: SceneFile = format_camera(&camera).parse().unwrap();
+            assert_eq!(file.scene.camera.projection, projection);
+        }
+
+        for invalid in [
+            "projection = \"cubemap\"\nmapping = \"equisolid\"",
+            "view_height = 2",
+            "projection = \"orthographic\"\nview_height = -1",
+        ] {
+            match format!("[camera]\n{}\n", invalid).parse::<SceneFile>() {
+                Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 1),
+                _ => panic!("expected an invalid camera"),
+            }
+        }
+    }
+
+    #[test]
+    fn stereo() {
+        let src = "[camera]\nlook_at = [0, 0, -3]\ninterocular = 0.065\n";
+        let camera = src.parse::<SceneFile>().unwrap().scene.camera;
+        assert_eq!(camera.stereo.interocular, 0.065);
+        assert_eq!(camera.stereo.convergence, 3.0);
+
+        let file: SceneFile = format_camera(&camera).parse().unwrap();
+        assert_eq!(file.scene.camera.stereo, camera.stereo);
+        for invalid in ["interocular = -1", "convergence = 0"] {
+            let src = format!("[camera]\n{}\n", invalid);
+            assert!(src.parse::<SceneFile>().is_err());
+        }
+    }
+
+    #[test]
+    fn unknown_material() {
+        let src = SCENE.replace("material = \"red\"", "material = \"blue\"");
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { line, message }) => {
+                assert_eq!(line, 10);
+                assert!(message.contains("blue"));
+            }
+            _ => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn transform() {
+        let src = SCENE.replace(
+            "material = \"red\"",
+            "material = \"red\"\ntransform = { scale = [1, 2, 1], translate = [0, 0, 3] }",
+        );
+        let file: SceneFile = src.parse().unwrap();
+        let ray = Ray {
+            kind: RayKind::Primary,
+            origin: Vertex {
+                x: 0.0,
+                y: 10.0,
+                z: -7.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+            time: 0.0,
+            differentials: None,
+        };
+        // stretched twice along y and moved toward the camera
+        let hit = file.scene.trace(&ray).unwrap();
+        assert!((hit.distance - 6.0).abs() < 1e-4, "{}", hit.distance);
+
+        let src = SCENE.replace(
+            "material = \"red\"",
+            "material = \"red\"\ntransform = { scale = 0 }",
+        );
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 10),
+            _ => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn instances() {
+        let src = SCENE.replace(
+            "[[objects]]",
+            r#"[geometries.pair]
+objects = [
+    { type = "sphere", center = [-1, 0, 0], radius = 0.5, material = "red" },
+    { type = "sphere", center = [1, 0, 0], radius = 0.5, material = "red" },
+]
+
+[[objects]]
+type = "instance"
+geometry = "pair"
+transform = { translate = [0, 0, -5] }
+
+[[objects]]"#,
+        );
+        let file: SceneFile = src.parse().unwrap();
+        assert_eq!(file.scene.objects.len(), 2);
+        let ray = Ray {
+            kind: RayKind::Primary,
+            origin: Vertex {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            time: 0.0,
+            differentials: None,
+        };
+        let hit = file.scene.trace(&ray).unwrap();
+        assert!((hit.distance - 4.5).abs() < 1e-4, "{}", hit.distance);
+
+        let src = src.replace("geometry = \"pair\"", "geometry = \"trio\"");
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { message, .. }) => assert!(message.contains("trio")),
+            _ => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn subdivision() {
+        for (geometry, expected) in [
+            (
+                "subdivision = { scheme = \"loop\", levels = 1 }",
+                "needs a mesh",
+            ),
+            (
+                "mesh = \"missing.obj\"\nmaterial = \"red\"\nsubdivision = { scheme = \"loop\", levels = 1 }",
+                "missing.obj",
+            ),
+            (
+                "subdivision = { scheme = \"doo-sabin\", levels = 1 }",
+                "unknown variant",
+            ),
+        ] {
+            let src = SCENE.replace(
+                "[[objects]]",
+                &format!("[geometries.smooth]\n{}\n\n[[objects]]", geometry),
+            );
+            match src.parse::<SceneFile>() {
+                Err(e) => assert!(e.to_string().contains(expected), "{}", e),
+                _ => panic!("expected an invalid geometry"),
+            }
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let shapes = [
+            "type = \"box\"\nmin = [-1, -1, -11]\nmax = [1, 1, -9]\nrotate = { axis = [0, 1, 0], angle = 45 }",
+            "type = \"disc\"\ncenter = [0, 0, -9]\nnormal = [0, 0, 1]\nradius = 1",
+            "type = \"quad\"\ncorner = [-1, -1, -9]\nedges = [[2, 0, 0], [0, 2, 0]]",
+            "type = \"cylinder\"\nbase = [0, 0, -12]\ntop = [0, 0, -9]\nradius = 1",
+            "type = \"cone\"\nbase = [0, 0, -12]\ntop = [0, 0, -9]\nradius = 1\ntop_radius = 0.5",
+            "type = \"torus\"\ncenter = [0, 0, -10]\naxis = [1, 0, 0]\nmajor_radius = 1\nminor_radius = 0.5",
+        ];
+        let ray = Ray {
+            kind: RayKind::Primary,
+            origin: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            time: 0.0,
+            differentials: None,
+        };
+        for (shape, distance) in shapes
+            .iter()
+            .zip([10.0 - 2f32.sqrt(), 9.0, 9.0, 9.0, 9.0, 8.5])
+        {
+            let src = SCENE.replace("type = \"sphere\"\ncenter = [0, 0, -10]\nradius = 2", shape);
+            let file: SceneFile = src.parse().unwrap();
+            let kind = file.scene.objects[0].kind();
+            assert!(shape.contains(kind), "{}", kind);
+            let hit = file.scene.trace(&ray).unwrap();
+            assert!(
+                (hit.distance - distance).abs() < 1e-4,
+                "{} {}",
+                kind,
+                hit.distance
+            );
+        }
+
+        for invalid in [
+            "type = \"box\"\nmin = [0, 0, 0]\nmax = [1, 0, 1]",
+            "type = \"disc\"\ncenter = [0, 0, 0]\nnormal = [0, 0, 0]\nradius = 1",
+            "type = \"quad\"\ncorner = [0, 0, 0]\nedges = [[1, 0, 0], [2, 0, 0]]",
+            "type = \"cone\"\nbase = [0, 0, 0]\ntop = [0, 0, 0]\nradius = 1",
+        ] {
+            let src = SCENE.replace(
+                "type = \"sphere\"\ncenter = [0, 0, -10]\nradius = 2",
+                invalid,
+            );
+            match src.parse::<SceneFile>() {
+                Err(SceneError::Invalid { line, .. }) => assert_eq!(line, 10),
+                _ => panic!("expected an invalid shape"),
+            }
+        }
+    }
+
+    #[test]
+    fn nodes() {
+        let src = SCENE.replace(
+            "[[objects]]",
+            r#"[[nodes]]
+path = "car"
+transform = { translate = [0, 0, 4] }
+
+[[nodes]]
+path = "car/wheels"
+
+[[objects]]
+name = "wheel"
+parent = "car/wheels"
+type = "sphere"
+center = [0, 0, -10]
+radius = 1
+material = "red"
+
+[[objects]]"#,
+        );
+        let file: SceneFile = src.parse().unwrap();
+        assert!(file.graph.find("car/wheels/wheel").is_some());
+        assert_eq!(file.scene.objects.len(), 2);
+        assert_eq!(file.scene.path_of(0), Some("car/wheels/wheel"));
+        assert_eq!(file.scene.path_of(1), None);
+        let center = file.scene.objects[0].bounds().unwrap().centroid();
+        assert!((center.z + 6.0).abs() < 1e-5, "{:?}", center);
+
+        let hidden = src.replace(
+            "path = \"car/wheels\"",
+            "path = \"car/wheels\"\nvisible = false",
+        );
+        let file: SceneFile = hidden.parse().unwrap();
+        assert_eq!(file.scene.objects.len(), 1);
+
+        let src = src.replace("parent = \"car/wheels\"", "parent = \"car/tyres\"");
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { line, message }) => {
+                assert_eq!(line, 17);
+                assert!(message.contains("car/tyres"), "{}", message);
+            }
+            _ => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn animation() {
+        let src = format!(
+            "{}{}",
+            SCENE.replace("[[objects]]", "[[objects]]\nname = \"ball\""),
+            r##"
+[animation]
+fps = 10
+
+[[animation.tracks]]
+node = "ball"
+property = "translate"
+keys = [{ time = 0, value = [0, 0, 0] }, { time = 1, value = [0, 4, 0] }]
+
+[[animation.tracks]]
+material = "red"
+property = "color"
+keys = [{ time = 0, value = "#FF0000" }, { time = 1, value = "#0000FF" }]
+"##
+        );
+        let file: SceneFile = src.parse().unwrap();
+        assert_eq!(file.animation.frames, (1, 11));
+        assert_eq!(file.animation.time(6), 0.5);
+        let scene = file.scene_at(0.5);
+        let center = scene.objects[0].bounds().unwrap().centroid();
+        assert!((center.y - 2.0).abs() < 1e-5, "{:?}", center);
+        let color = scene.objects[0].color();
+        assert!((color.red - 0.5).abs() < 1e-5 && (color.blue - 0.5).abs() < 1e-5);
+        // posed at the first frame
+        assert_eq!(file.scene.objects[0].color().red, 1.0);
+
+        let src = src.replace("property = \"translate\"", "property = \"albedo\"");
+        match src.parse::<SceneFile>() {
+            Err(SceneError::Invalid { message, .. }) => assert!(message.contains("albedo")),
+            _ => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn animated_look_at_and_instances() {
+        let src = SCENE.replace(
+            "[[objects]]",
+            r##"[materials.green]
+color = "#00FF00"
+albedo = 0.5
+
+[camera]
+position = [0, 0, 0]
+look_at = [0, 0, -10]
+
+[geometries.pair]
+objects = [
+    { type = "sphere", center = [-1, 0, 0], radius = 0.5, material = "green" },
+    { type = "sphere", center = [1, 0, 0], radius = 0.5, material = "red" },
+]
+
+[[objects]]
+type = "instance"
+geometry = "pair"
+transform = { translate = [0, 0, -5] }
+
+[animation]
+fps = 10
+
+[[animation.tracks]]
+camera = true
+property = "position"
+keys = [{ time = 0, value = [0, 0, 0] }, { time = 1, value = [10, 0, 0] }]
+
+[[animation.tracks]]
+material = "red"
+property = "color"
+keys = [{ time = 0, value = "#FF0000" }, { time = 1, value = "#0000FF" }]
+
+[[objects]]"##,
+        );
+        let file: SceneFile = src.parse().unwrap();
+        let scene = file.scene_at(1.0);
+        // still aimed at the look_at point
+        let d = scene.camera.direction;
+        assert!((d.x + 0.5f32.sqrt()).abs() < 1e-5 && (d.z + 0.5f32.sqrt()).abs() < 1e-5);
+        assert!((scene.camera.target_distance - 200f32.sqrt()).abs() < 1e-4);
+        // the instance inherits the animated material of the geometry
+        let color = |x: f32| {
+            let ray = Ray {
+                kind: RayKind::Primary,
+                origin: Vertex { x, y: 0.0, z: 0.0 },
+                direction: Vector {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -1.0,
+                },
+                time: 0.0,
+                differentials: None,
+            };
+            scene.trace(&ray).unwrap().color()
+        };
+        assert_eq!((color(1.0).red, color(1.0).blue), (0.0, 1.0));
+        assert_eq!(color(-1.0).green, 1.0);
+    }
+
+    #[test]
+    fn motion_blur() {
+        let src = format!(
+            "{}{}",
+            SCENE.replace("radius = 2", "radius = 0.4\nvelocity = [10, 0, 0]"),
+            "[animation]\nfps = 10\nshutter = 0.5\n"
+        );
+        let file: SceneFile = src.parse().unwrap();
+        let scene = file.scene_at(0.0);
+        // half a frame at 10 units per second
+        let bounds = scene.objects[0].bounds().unwrap();
+        assert!((bounds.min.x + 0.4).abs() < 1e-5 && (bounds.max.x - 0.9).abs() < 1e-5);
+        let ray = |x: f32, time: f32| Ray {
+            kind: RayKind::Primary,
+            origin: Vertex { x, y: 0.0, z: 0.0 },
+            direction: Vector {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            time,
+            differentials: None,
+        };
+        assert!(scene.trace(&ray(0.0, 0.0)).is_some());
+        assert!(scene.trace(&ray(0.0, 1.0)).is_none());
+        assert!(scene.trace(&ray(0.5, 1.0)).is_some());
+        assert!(scene.trace(&ray(0.25, 0.5)).is_some());
+
+        // a later frame starts further
+        let bounds = file.scene_at(0.1).objects[0].bounds().unwrap();
+        assert!((bounds.min.x - 0.6).abs() < 1e-5);
+    }
+
+    #[test]
+    fn invalid_color() {
+        let src = SCENE.replace("#FF0000", "red");
+        let message = match src.parse::<SceneFile>() {
+            Err(e @ SceneError::Syntax(_)) => e.to_string(),
+            _ => panic!("expected a syntax error"),
+        };
+        assert!(message.contains("line 7"), "{}", message);
+        assert!(message.contains("#RRGGBB"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_field() {
+        let src = SCENE.replace("radius = 2", "radius = 2\nradios = 3");
+        let message = match src.parse::<SceneFile>() {
+            Err(e @ SceneError::Syntax(_)) => e.to_string(),
+            _ => panic!("expected a syntax error"),
+        };
+        assert!(message.contains("radios"), "{}", message);
+    }
+}
@@ -0,0 +1,578 @@
+//! Refinement of meshes by subdivision surfaces and displacement, before
+//! their triangles are rendered.
+//!
+//! Each level of subdivision splits every face and moves the vertices
+//! toward a smooth limit surface, boundaries being kept as smooth curves.
+//! Texture coordinates are split along without being smoothed.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::graphics::Color;
+use crate::math::vec3::{Vector, Vertex};
+use crate::tracer::mesh::{Face, Mesh};
+use crate::tracer::texture::{Surface, Texture, Uv};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// Splitting any polygon in quads.
+    CatmullClark,
+    /// Splitting triangles in four, polygons being triangulated first.
+    Loop,
+}
+
+/// Mesh subdivided `levels` times.
+pub fn subdivide(mesh: &Mesh, scheme: Scheme, levels: u32) -> Mesh {
+    let mut mesh = mesh.clone();
+    if scheme == Scheme::Loop && levels > 0 {
+        mesh = triangulate(&mesh);
+    }
+    for _ in 0..levels {
+        mesh = match scheme {
+            Scheme::CatmullClark => catmull_clark(&mesh),
+            Scheme::Loop => loop_subdivision(&mesh),
+        };
+    }
+    mesh
+}
+
+/// Sum of the points weighted by their factors.
+fn combine(terms: impl IntoIterator<Item = (f32, Vertex)>) -> Vertex {
+    terms.into_iter().fold(
+        Vertex {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        |sum, (w, p)| Vertex {
+            x: sum.x + w * p.x,
+            y: sum.y + w * p.y,
+            z: sum.z + w * p.z,
+        },
+    )
+}
+
+fn centroid(points: impl ExactSizeIterator<Item = Vertex>) -> Vertex {
+    let w = 1.0 / points.len() as f32;
+    combine(points.map(|p| (w, p)))
+}
+
+fn key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/// Edges of the faces of a mesh.
+struct Edges {
+    index: HashMap<(usize, usize), usize>,
+    /// Ends of the edges, as vertex indices.
+    ends: Vec<(usize, usize)>,
+    /// Faces along the edges, two inside the surface and one on its
+    /// boundary.
+    faces: Vec<Vec<usize>>,
+    /// Edges around the vertices.
+    around: Vec<Vec<usize>>,
+}
+
+impl Edges {
+    fn new(mesh: &Mesh) -> Edges {
+        let mut edges = Edges {
+            index: HashMap::new(),
+            ends: Vec::new(),
+            faces: Vec::new(),
+            around: vec![Vec::new(); mesh.positions.len()],
+        };
+        for (f, face) in mesh.faces.iter().enumerate() {
+            let n = face.vertices.len();
+            for i in 0..n {
+                let (a, b) = (face.vertices[i], face.vertices[(i + 1) % n]);
+                let next = edges.ends.len();
+                let e = *edges.index.entry(key(a, b)).or_insert(next);
+                if e == next {
+                    edges.ends.push((a, b));
+                    edges.faces.push(Vec::new());
+                    edges.around[a].push(e);
+                    edges.around[b].push(e);
+                }
+                edges.faces[e].push(f);
+            }
+        }
+        edges
+    }
+
+    fn get(&self, a: usize, b: usize) -> usize {
+        self.index[&key(a, b)]
+    }
+
+    fn is_smooth(&self, e: usize) -> bool {
+        self.faces[e].len() == 2
+    }
+
+    /// Point of a vertex on the boundary, between its neighbors along it,
+    /// corners of single faces and vertices on several boundaries staying
+    /// in place.
+    fn boundary_point(&self, mesh: &Mesh, v: usize) -> Vertex {
+        let boundary: Vec<usize> = self.around[v]
+            .iter()
+            .filter(|&&e| !self.is_smooth(e))
+            .map(|&e| self.other(e, v))
+            .collect();
+        let p = mesh.positions[v];
+        match boundary[..] {
+            [a, b] if self.around[v].len() > 2 => combine([
+                (0.75, p),
+                (0.125, mesh.positions[a]),
+                (0.125, mesh.positions[b]),
+            ]),
+            _ => p,
+        }
+    }
+
+    fn other(&self, e: usize, v: usize) -> usize {
+        let (a, b) = self.ends[e];
+        if a == v {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+/// Texture coordinates of a subdivided mesh, the original ones followed by
+/// the new ones, shared along the edges between faces sharing their ends.
+struct Texcoords {
+    texcoords: Vec<Uv>,
+    midpoints: HashMap<(usize, usize), usize>,
+}
+
+impl Texcoords {
+    fn new(mesh: &Mesh) -> Texcoords {
+        Texcoords {
+            texcoords: mesh.texcoords.clone(),
+            midpoints: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, uv: Uv) -> usize {
+        self.texcoords.push(uv);
+        self.texcoords.len() - 1
+    }
+
+    fn midpoint(&mut self, a: usize, b: usize) -> usize {
+        if let Some(&i) = self.midpoints.get(&key(a, b)) {
+            return i;
+        }
+        let (ta, tb) = (self.texcoords[a], self.texcoords[b]);
+        let i = self.push(Uv {
+            u: 0.5 * (ta.u + tb.u),
+            v: 0.5 * (ta.v + tb.v),
+        });
+        self.midpoints.insert(key(a, b), i);
+        i
+    }
+}
+
+/// Faces split in fans of triangles.
+fn triangulate(mesh: &Mesh) -> Mesh {
+    let faces = mesh
+        .faces
+        .iter()
+        .flat_map(|face| {
+            (1..face.vertices.len() - 1).map(move |i| {
+                let corners = [0, i, i + 1];
+                Face {
+                    vertices: corners.map(|c| face.vertices[c]).to_vec(),
+                    uvs: face
+                        .uvs
+                        .as_ref()
+                        .map(|uvs| corners.map(|c| uvs[c]).to_vec()),
+                }
+            })
+        })
+        .collect();
+    Mesh {
+        faces,
+        ..mesh.clone()
+    }
+}
+
+/// One level of Catmull-Clark subdivision, a quad per corner of each face.
+fn catmull_clark(mesh: &Mesh) -> Mesh {
+    let edges = Edges::new(mesh);
+    let (vertex_count, face_count) = (mesh.positions.len(), mesh.faces.len());
+    let face_points: Vec<Vertex> = mesh
+        .faces
+        .iter()
+        .map(|face| centroid(face.vertices.iter().map(|&v| mesh.positions[v])))
+        .collect();
+    let edge_points = edges.ends.iter().enumerate().map(|(e, &(a, b))| {
+        let ends = [mesh.positions[a], mesh.positions[b]];
+        if edges.is_smooth(e) {
+            let faces = edges.faces[e].iter().map(|&f| face_points[f]);
+            combine(ends.iter().copied().chain(faces).map(|p| (0.25, p)))
+        } else {
+            centroid(ends.iter().copied())
+        }
+    });
+    // faces around the vertices
+    let mut around: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (f, face) in mesh.faces.iter().enumerate() {
+        for &v in &face.vertices {
+            around[v].push(f);
+        }
+    }
+    let vertex_points = (0..vertex_count).map(|v| {
+        let p = mesh.positions[v];
+        let incident = &edges.around[v];
+        if incident.is_empty() {
+            p
+        } else if incident.iter().all(|&e| edges.is_smooth(e)) {
+            // (Q + 2R + (n - 3)P) / n, Q averaging the face points and R
+            // the midpoints of the edges
+            let n = incident.len() as f32;
+            let q = centroid(around[v].iter().map(|&f| face_points[f]));
+            let r = centroid(incident.iter().map(|&e| {
+                let (a, b) = edges.ends[e];
+                centroid([mesh.positions[a], mesh.positions[b]].iter().copied())
+            }));
+            combine([(1.0 / n, q), (2.0 / n, r), ((n - 3.0) / n, p)])
+        } else {
+            edges.boundary_point(mesh, v)
+        }
+    });
+    let positions: Vec<Vertex> = vertex_points
+        .chain(face_points.iter().copied())
+        .chain(edge_points)
+        .collect();
+
+    let mut texcoords = Texcoords::new(mesh);
+    let mut faces = Vec::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let n = face.vertices.len();
+        let center_uv = face.uvs.as_ref().map(|uvs| {
+            let w = 1.0 / n as f32;
+            texcoords.push(uvs.iter().fold(Uv::default(), |sum, &i| Uv {
+                u: sum.u + w * mesh.texcoords[i].u,
+                v: sum.v + w * mesh.texcoords[i].v,
+            }))
+        });
+        let edge_point = |i: usize, j: usize| {
+            vertex_count + face_count + edges.get(face.vertices[i], face.vertices[j])
+        };
+        for i in 0..n {
+            let (previous, next) = ((i + n - 1) % n, (i + 1) % n);
+            faces.push(Face {
+                vertices: vec![
+                    face.vertices[i],
+                    edge_point(i, next),
+                    vertex_count + f,
+                    edge_point(previous, i),
+                ],
+                uvs: face.uvs.as_ref().zip(center_uv).map(|(uvs, center)| {
+                    vec![
+                        uvs[i],
+                        texcoords.midpoint(uvs[i], uvs[next]),
+                        center,
+                        texcoords.midpoint(uvs[previous], uvs[i]),
+                    ]
+                }),
+            });
+        }
+    }
+    Mesh {
+        positions,
+        texcoords: texcoords.texcoords,
+        faces,
+    }
+}
+
+/// One level of Loop subdivision of a triangle mesh, four triangles per
+/// triangle.
+fn loop_subdivision(mesh: &Mesh) -> Mesh {
+    let edges = Edges::new(mesh);
+    let vertex_count = mesh.positions.len();
+    let edge_points = edges.ends.iter().enumerate().map(|(e, &(a, b))| {
+        let (pa, pb) = (mesh.positions[a], mesh.positions[b]);
+        if edges.is_smooth(e) {
+            // 3/8 of the ends and 1/8 of the opposite corners
+            let opposite = edges.faces[e].iter().map(|&f| {
+                let corner = mesh.faces[f]
+                    .vertices
+                    .iter()
+                    .find(|&&v| v != a && v != b)
+                    .copied()
+                    .unwrap_or(a);
+                (0.125, mesh.positions[corner])
+            });
+            combine([(0.375, pa), (0.375, pb)].iter().copied().chain(opposite))
+        } else {
+            centroid([pa, pb].iter().copied())
+        }
+    });
+    let vertex_points = (0..vertex_count).map(|v| {
+        let p = mesh.positions[v];
+        let incident = &edges.around[v];
+        if incident.is_empty() {
+            p
+        } else if incident.iter().all(|&e| edges.is_smooth(e)) {
+            let n = incident.len();
+            let beta = if n == 3 {
+                3.0 / 16.0
+            } else {
+                3.0 / (8.0 * n as f32)
+            };
+            let neighbors = incident
+                .iter()
+                .map(|&e| (beta, mesh.positions[edges.other(e, v)]));
+            combine(
+                [(1.0 - n as f32 * beta, p)]
+                    .iter()
+                    .copied()
+                    .chain(neighbors),
+            )
+        } else {
+            edges.boundary_point(mesh, v)
+        }
+    });
+    let positions: Vec<Vertex> = vertex_points.chain(edge_points).collect();
+
+    let mut texcoords = Texcoords::new(mesh);
+    let mut faces = Vec::new();
+    for face in &mesh.faces {
+        let [a, b, c] = [0, 1, 2].map(|i| face.vertices[i]);
+        let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(i, j)| vertex_count + edges.get(i, j));
+        let uvs = face.uvs.as_ref().map(|uvs| {
+            let [ta, tb, tc] = [uvs[0], uvs[1], uvs[2]];
+            [
+                ta,
+                tb,
+                tc,
+                texcoords.midpoint(ta, tb),
+                texcoords.midpoint(tb, tc),
+                texcoords.midpoint(tc, ta),
+            ]
+        });
+        for corners in [[0, 3, 5], [1, 4, 3], [2, 5, 4], [3, 4, 5]] {
+            let vertices = [a, b, c, ab, bc, ca];
+            faces.push(Face {
+                vertices: corners.iter().map(|&i| vertices[i]).collect(),
+                uvs: uvs.map(|uvs| corners.iter().map(|&i| uvs[i]).collect()),
+            });
+        }
+    }
+    Mesh {
+        positions,
+        texcoords: texcoords.texcoords,
+        faces,
+    }
+}
+
+/// Offsets of the vertices of a mesh, read from textures at their texture
+/// coordinates and positions.
+#[derive(Clone, Debug)]
+pub enum Displacement {
+    /// Heights along the normals, `scale` units for a height of 1.
+    Scalar {
+        height: Arc<dyn Texture<f32>>,
+        scale: f32,
+    },
+    /// Offsets in object space, the channels going from `-scale` at 0 to
+    /// `scale` at 1.
+    Vector {
+        offset: Arc<dyn Texture<Color>>,
+        scale: f32,
+    },
+}
+
+impl Displacement {
+    /// Moves the vertices of the mesh, each one taking the texture
+    /// coordinates of its first textured corner.
+    pub fn displace(&self, mesh: &mut Mesh) {
+        let mut uvs = vec![None; mesh.positions.len()];
+        // normals weighted by the areas of the faces
+        let mut normals = vec![Vector::default(); mesh.positions.len()];
+        for face in &mesh.faces {
+            let p = |i: usize| mesh.positions[face.vertices[i]];
+            let mut normal = Vector::default();
+            for i in 1..face.vertices.len() - 1 {
+                let (u, v) = (
+                    Vector::from_vertices(p(0), p(i)),
+                    Vector::from_vertices(p(0), p(i + 1)),
+                );
+                normal = normal + u.cross(v);
+            }
+            for (i, &v) in face.vertices.iter().enumerate() {
+                normals[v] = normals[v] + normal;
+                if let (None, Some(face_uvs)) = (uvs[v], &face.uvs) {
+                    uvs[v] = Some(mesh.texcoords[face_uvs[i]]);
+                }
+            }
+        }
+        for ((position, normal), uv) in mesh.positions.iter_mut().zip(normals).zip(uvs) {
+            let surface = Surface {
+                uv: uv.unwrap_or_default(),
+                point: *position,
+                scene_point: *position,
+                ..Surface::default()
+            };
+            let offset = match self {
+                Self::Scalar { height, scale } => {
+                    normal.normalize() * (height.value(&surface) * scale)
+                }
+                Self::Vector { offset, scale } => {
+                    let c = offset.value(&surface);
+                    Vector {
+                        x: (2.0 * c.red - 1.0) * scale,
+                        y: (2.0 * c.green - 1.0) * scale,
+                        z: (2.0 * c.blue - 1.0) * scale,
+                    }
+                }
+            };
+            *position = *position + offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::texture::Constant;
+
+    fn cube() -> Mesh {
+        let mut src = String::new();
+        for i in 0..8 {
+            let [x, y, z] = [i & 1, (i >> 1) & 1, (i >> 2) & 1].map(|c| c as f32 * 2.0 - 1.0);
+            src += &format!("v {} {} {}\n", x, y, z);
+        }
+        src += "f 1 3 4 2\nf 5 6 8 7\nf 1 2 6 5\nf 3 7 8 4\nf 1 5 7 3\nf 2 4 8 6\n";
+        Mesh::parse(&src).unwrap()
+    }
+
+    fn radius(p: Vertex) -> f32 {
+        (p.x * p.x + p.y * p.y + p.z * p.z).sqrt()
+    }
+
+    #[test]
+    fn catmull_clark() {
+        let cube = cube();
+        let once = subdivide(&cube, Scheme::CatmullClark, 1);
+        // vertices, face points and edge points
+        assert_eq!(once.positions.len(), 8 + 6 + 12);
+        assert_eq!(once.faces.len(), 24);
+        // corners pulled in, (Q + 2R) / 3 with Q at 1/3 and R at 2/3
+        let corner = once.positions[7];
+        assert!((corner.x - 5.0 / 9.0).abs() < 1e-5, "{:?}", corner);
+        assert_eq!(
+            once.positions[8],
+            Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0
+            }
+        );
+
+        let smooth = subdivide(&cube, Scheme::CatmullClark, 3);
+        assert_eq!(smooth.faces.len(), 6 * 4usize.pow(3));
+        let radii: Vec<f32> = smooth.positions.iter().map(|&p| radius(p)).collect();
+        let (min, max) = radii
+            .iter()
+            .fold((f32::INFINITY, 0.0f32), |(a, b), &r| (a.min(r), b.max(r)));
+        assert!(max - min < 0.15, "{} {}", min, max);
+        assert_eq!(subdivide(&cube, Scheme::CatmullClark, 0), cube);
+    }
+
+    #[test]
+    fn boundaries() {
+        // a textured square, its corners staying in place
+        let square = Mesh::parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+        let quads = subdivide(&square, Scheme::CatmullClark, 2);
+        assert_eq!(quads.faces.len(), 16);
+        assert_eq!(&quads.positions[..4], &square.positions[..]);
+        assert!(quads.positions.iter().all(|p| p.z == 0.0));
+        // shared texture coordinates, following the positions on the plane
+        assert_eq!(quads.texcoords.len(), 25);
+        for (position, uv) in quads
+            .triangles()
+            .flat_map(|(p, uv)| IntoIterator::into_iter(p).zip(uv.unwrap()))
+        {
+            assert!((position.x - uv.u).abs() < 1e-6 && (position.y - uv.v).abs() < 1e-6);
+        }
+
+        let triangles = subdivide(&square, Scheme::Loop, 2);
+        assert_eq!(triangles.faces.len(), 2 * 16);
+        assert_eq!(triangles.positions[1], square.positions[1]);
+        assert!(triangles.positions.iter().all(|p| p.z == 0.0));
+        assert!(triangles.faces.iter().all(|f| f.vertices.len() == 3));
+    }
+
+    #[test]
+    fn loop_subdivision() {
+        let tetrahedron = Mesh::parse(
+            "v 1 1 1\nv 1 -1 -1\nv -1 1 -1\nv -1 -1 1\nf 1 2 3\nf 1 4 2\nf 1 3 4\nf 2 4 3\n",
+        )
+        .unwrap();
+        let once = subdivide(&tetrahedron, Scheme::Loop, 1);
+        assert_eq!(once.positions.len(), 4 + 6);
+        assert_eq!(once.faces.len(), 16);
+        // 1 - 3 * 3/16 of the vertex and 3/16 of each neighbor
+        assert!((once.positions[0].x - 0.25).abs() < 1e-6);
+        // 3/8 of the ends and 1/8 of the opposite corners
+        let edge = once.positions[4];
+        assert!(
+            (edge.x - 0.5).abs() < 1e-6 && edge.y.abs() < 1e-6,
+            "{:?}",
+            edge
+        );
+        // consistently oriented outward
+        for (p, _) in once.triangles() {
+            let normal = Vector::from_vertices(p[0], p[1]).cross(Vector::from_vertices(p[0], p[2]));
+            let center = centroid(p.iter().copied());
+            let outward = Vector::from_vertices(
+                Vertex {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                center,
+            );
+            assert!(normal.dot(outward) > 0.0);
+        }
+    }
+
+    #[test]
+    fn displacement() {
+        let mut cube = subdivide(&cube(), Scheme::CatmullClark, 2);
+        let before = cube.positions.clone();
+        Displacement::Scalar {
+            height: Arc::new(Constant(1.0)),
+            scale: 0.5,
+        }
+        .displace(&mut cube);
+        for (&p, &q) in cube.positions.iter().zip(&before) {
+            assert!((Vector::from_vertices(q, p).norm() - 0.5).abs() < 1e-5);
+            assert!(radius(p) > radius(q));
+        }
+
+        let mut square = Mesh::parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        Displacement::Vector {
+            offset: Arc::new(Constant(Color {
+                red: 0.5,
+                green: 1.0,
+                blue: 0.25,
+            })),
+            scale: 2.0,
+        }
+        .displace(&mut square);
+        assert_eq!(
+            square.positions[1],
+            Vertex {
+                x: 1.0,
+                y: 2.0,
+                z: -1.0
+            }
+        );
+    }
+}
@@ -0,0 +1,669 @@
+//! Textures varying material parameters over the surfaces.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::hdr::HdrDecoder;
+use image::ImageFormat;
+
+use crate::graphics::Color;
+use crate::math::sampling::orthonormal_basis;
+use crate::math::vec3::{Vector, Vertex};
+
+/// Texture coordinates, `v` going up images.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Uv {
+    pub u: f32,
+    pub v: f32,
+}
+
+/// Surface point where textures are looked up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Surface {
+    pub uv: Uv,
+    /// Derivatives of the point with respect to the texture coordinates,
+    /// in scene space.
+    pub dpdu: Vector,
+    pub dpdv: Vector,
+    /// Point in the object space, for solid textures.
+    pub point: Vertex,
+    /// Point in the scene space.
+    pub scene_point: Vertex,
+    /// Changes of the texture coordinates from the pixel to its right and
+    /// lower neighbours, zero without ray differentials.
+    pub dx: Uv,
+    pub dy: Uv,
+}
+
+impl Surface {
+    /// Change of the texture coordinates for a small move `dp` along the
+    /// surface, in the least squares sense.
+    pub fn uv_along(&self, dp: Vector) -> Uv {
+        let (a, b, c) = (
+            self.dpdu.dot(self.dpdu),
+            self.dpdu.dot(self.dpdv),
+            self.dpdv.dot(self.dpdv),
+        );
+        let det = a * c - b * b;
+        if det.abs() < 1e-12 {
+            return Uv::default();
+        }
+        let (pu, pv) = (self.dpdu.dot(dp), self.dpdv.dot(dp));
+        Uv {
+            u: (c * pu - b * pv) / det,
+            v: (a * pv - b * pu) / det,
+        }
+    }
+}
+
+impl Default for Surface {
+    fn default() -> Surface {
+        Surface {
+            uv: Uv::default(),
+            dpdu: Vector::default(),
+            dpdv: Vector::default(),
+            point: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            scene_point: Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            dx: Uv::default(),
+            dy: Uv::default(),
+        }
+    }
+}
+
+/// Value of type `T`, a color or a scalar, varying over the surfaces.
+pub trait Texture<T>: fmt::Debug + Send + Sync {
+    fn value(&self, surface: &Surface) -> T;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Constant<T>(pub T);
+
+impl<T: Copy + fmt::Debug + Send + Sync> Texture<T> for Constant<T> {
+    fn value(&self, _: &Surface) -> T {
+        self.0
+    }
+}
+
+/// Squares of alternating values, `scale` of them per texture unit.
+#[derive(Clone, Copy, Debug)]
+pub struct Checker<T> {
+    pub even: T,
+    pub odd: T,
+    pub scale: f32,
+}
+
+impl<T: Copy + fmt::Debug + Send + Sync> Texture<T> for Checker<T> {
+    fn value(&self, surface: &Surface) -> T {
+        let u = (surface.uv.u * self.scale).floor() as i64;
+        let v = (surface.uv.v * self.scale).floor() as i64;
+        if (u + v).rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+/// How texture coordinates outside of the unit square reach into an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    /// Stretching the edge texels.
+    Clamp,
+    /// Repeating flipped images every other time.
+    Mirror,
+}
+
+/// Filtering of the texels covered by a pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    /// Bilinear lookups in the two mipmap levels closest to the pixel
+    /// footprint, blended, for distant surfaces not to shimmer.
+    Trilinear,
+}
+
+/// Encoding of the colors stored in 8 bit images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Encoded with the sRGB transfer curve, as most images are.
+    Srgb,
+    /// Stored as is, for data like albedos.
+    Linear,
+}
+
+impl ColorSpace {
+    fn decode(self, value: u8) -> f32 {
+        let value = value as f32 / 255.0;
+        match self {
+            Self::Srgb if value <= 0.04045 => value / 12.92,
+            Self::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Self::Linear => value,
+        }
+    }
+}
+
+/// Image mapped onto the unit square of the texture space, its lower left
+/// corner at the origin, scalar parameters taking the mean of the channels.
+#[derive(Clone)]
+pub struct ImageTexture {
+    pub wrap: Wrap,
+    pub filter: Filter,
+    /// Mipmap levels, each half the size of the previous one down to a
+    /// single texel.
+    levels: Vec<Level>,
+}
+
+#[derive(Clone)]
+struct Level {
+    width: usize,
+    height: usize,
+    /// Linear colors, in rows from the top.
+    texels: Vec<Color>,
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Level { width, height, .. } = self.levels[0];
+        write!(
+            f,
+            "ImageTexture({}x{}, {:?}, {:?})",
+            width, height, self.wrap, self.filter
+        )
+    }
+}
+
+impl ImageTexture {
+    /// Repeated image with `texels` in rows from the top, filtered
+    /// trilinearly.
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> ImageTexture {
+        assert!(width > 0 && height > 0, "empty image");
+        assert_eq!(
+            texels.len(),
+            width * height,
+            "texels of a {}x{} image",
+            width,
+            height
+        );
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(level) = levels.last().and_then(Level::downsampled) {
+            levels.push(level);
+        }
+        ImageTexture {
+            wrap: Wrap::Repeat,
+            filter: Filter::Trilinear,
+            levels,
+        }
+    }
+
+    /// Reads an image file, 8 bit colors being decoded from `color_space`
+    /// and Radiance HDR ones being linear already.
+    pub fn load(
+        path: impl AsRef<Path>,
+        color_space: ColorSpace,
+    ) -> Result<ImageTexture, image::ImageError> {
+        let path = path.as_ref();
+        if ImageFormat::from_path(path).ok() == Some(ImageFormat::Hdr) {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let meta = decoder.metadata();
+            let texels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|p| Color {
+                    red: p[0],
+                    green: p[1],
+                    blue: p[2],
+                })
+                .collect();
+            return Ok(ImageTexture::new(
+                meta.width as usize,
+                meta.height as usize,
+                texels,
+            ));
+        }
+        let img = image::open(path)?.to_rgb();
+        let texels = img
+            .pixels()
+            .map(|p| Color {
+                red: color_space.decode(p[0]),
+                green: color_space.decode(p[1]),
+                blue: color_space.decode(p[2]),
+            })
+            .collect();
+        Ok(ImageTexture::new(
+            img.width() as usize,
+            img.height() as usize,
+            texels,
+        ))
+    }
+
+    /// Filtered color at the surface, over the pixel footprint for
+    /// trilinear filtering.
+    fn lookup(&self, surface: &Surface) -> Color {
+        let base = &self.levels[0];
+        match self.filter {
+            Filter::Nearest => base.nearest(surface.uv, self.wrap),
+            Filter::Bilinear => base.bilinear(surface.uv, self.wrap),
+            Filter::Trilinear => {
+                // footprint in texels of the finest level
+                let width = [surface.dx, surface.dy]
+                    .iter()
+                    .map(|d| {
+                        (d.u * base.width as f32)
+                            .abs()
+                            .max((d.v * base.height as f32).abs())
+                    })
+                    .fold(1.0, f32::max);
+                let level = width.log2().min((self.levels.len() - 1) as f32);
+                let lower = level.floor() as usize;
+                let t = level - lower as f32;
+                let color = self.levels[lower].bilinear(surface.uv, self.wrap);
+                if t > 0.0 {
+                    let upper = self.levels[lower + 1].bilinear(surface.uv, self.wrap);
+                    color * (1.0 - t) + upper * t
+                } else {
+                    color
+                }
+            }
+        }
+    }
+}
+
+/// Texels of a row or column of `size` texels averaged into the texel
+/// `index` of the level below, with their weights. An odd size spreads
+/// each of the texels over the level below with three taps.
+fn box_taps(size: usize, index: usize) -> Vec<(usize, f32)> {
+    if size == 1 {
+        return vec![(0, 1.0)];
+    }
+    let first = index * 2;
+    if size.is_multiple_of(2) {
+        return vec![(first, 0.5), (first + 1, 0.5)];
+    }
+    let (half, index) = ((size / 2) as f32, index as f32);
+    vec![
+        (first, (half - index) / size as f32),
+        (first + 1, half / size as f32),
+        (first + 2, (index + 1.0) / size as f32),
+    ]
+}
+
+impl Level {
+    /// Level half as large, averaging blocks of texels, `None` for a
+    /// single texel.
+    fn downsampled(&self) -> Option<Level> {
+        if self.width == 1 && self.height == 1 {
+            return None;
+        }
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let rows = box_taps(self.height, y);
+            for x in 0..width {
+                let mut sum = Color::default();
+                for &(ty, wy) in &rows {
+                    for &(tx, wx) in &box_taps(self.width, x) {
+                        sum += self.texels[tx + ty * self.width] * (wx * wy);
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+        Some(Level {
+            width,
+            height,
+            texels,
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> Color {
+        let x = wrap.index(x, self.width);
+        let y = wrap.index(y, self.height);
+        self.texels[x + y * self.width]
+    }
+
+    /// Texel position of `uv`, from the top left corner.
+    fn position(&self, uv: Uv) -> (f32, f32) {
+        (uv.u * self.width as f32, (1.0 - uv.v) * self.height as f32)
+    }
+
+    fn nearest(&self, uv: Uv, wrap: Wrap) -> Color {
+        let (x, y) = self.position(uv);
+        self.texel(x.floor() as i64, y.floor() as i64, wrap)
+    }
+
+    /// Blend of the four texels around `uv`, weighted by how close their
+    /// centers are.
+    fn bilinear(&self, uv: Uv, wrap: Wrap) -> Color {
+        let (x, y) = self.position(uv);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        self.texel(x0, y0, wrap) * ((1.0 - tx) * (1.0 - ty))
+            + self.texel(x0 + 1, y0, wrap) * (tx * (1.0 - ty))
+            + self.texel(x0, y0 + 1, wrap) * ((1.0 - tx) * ty)
+            + self.texel(x0 + 1, y0 + 1, wrap) * (tx * ty)
+    }
+}
+
+impl Wrap {
+    /// Index of the texel at `i` in a row or column of `n` texels.
+    fn index(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            Self::Repeat => i.rem_euclid(n),
+            Self::Clamp => i.clamp(0, n - 1),
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+impl Texture<Color> for ImageTexture {
+    fn value(&self, surface: &Surface) -> Color {
+        self.lookup(surface)
+    }
+}
+
+impl Texture<f32> for ImageTexture {
+    fn value(&self, surface: &Surface) -> f32 {
+        let c = self.lookup(surface);
+        (c.red + c.green + c.blue) / 3.0
+    }
+}
+
+/// Detail of the surfaces perturbing their shading normals.
+#[derive(Clone, Debug)]
+pub enum Relief {
+    /// Tangent space normals encoded as linear colors, their red, green and
+    /// blue channels going from -1 to 1 along `dP/du`, `dP/dv` and the
+    /// normal.
+    NormalMap(Arc<dyn Texture<Color>>),
+    /// Heights along the normal, `scale` scene units per texture unit.
+    Bump {
+        height: Arc<dyn Texture<f32>>,
+        scale: f32,
+    },
+}
+
+impl Relief {
+    /// Shading normal at the surface of unit geometric `normal`, `local`
+    /// turning scene space vectors into the space of `surface.point`.
+    pub fn normal(
+        &self,
+        normal: Vector,
+        surface: &Surface,
+        local: impl Fn(Vector) -> Vector,
+    ) -> Vector {
+        match self {
+            Self::NormalMap(map) => {
+                let c = map.value(surface);
+                let (tangent, bitangent) = tangent_frame(normal, surface);
+                let n = tangent * (c.red * 2.0 - 1.0)
+                    + bitangent * (c.green * 2.0 - 1.0)
+                    + normal * (c.blue * 2.0 - 1.0);
+                if n.norm() > 1e-6 {
+                    n.normalize()
+                } else {
+                    normal
+                }
+            }
+            Self::Bump { height, scale } => {
+                // finite differences over about half a pixel
+                let step = |dx: f32, dy: f32| match 0.5 * (dx.abs() + dy.abs()) {
+                    d if d > 0.0 => d,
+                    _ => 5e-4,
+                };
+                let du = step(surface.dx.u, surface.dy.u);
+                let dv = step(surface.dx.v, surface.dy.v);
+                let moved = |d: Vector, uv: Uv| Surface {
+                    uv,
+                    point: surface.point + local(d),
+                    scene_point: surface.scene_point + d,
+                    ..*surface
+                };
+                let h = height.value(surface);
+                let hu = height.value(&moved(
+                    surface.dpdu * du,
+                    Uv {
+                        u: surface.uv.u + du,
+                        ..surface.uv
+                    },
+                ));
+                let hv = height.value(&moved(
+                    surface.dpdv * dv,
+                    Uv {
+                        v: surface.uv.v + dv,
+                        ..surface.uv
+                    },
+                ));
+                // derivatives of the displaced surface
+                let dpdu = surface.dpdu + normal * ((hu - h) / du * scale);
+                let dpdv = surface.dpdv + normal * ((hv - h) / dv * scale);
+                let n = dpdu.cross(dpdv);
+                if n.norm().is_nan() || n.norm() < 1e-12 {
+                    return normal;
+                }
+                let n = n.normalize();
+                if n.dot(normal) < 0.0 {
+                    -n
+                } else {
+                    n
+                }
+            }
+        }
+    }
+}
+
+/// Unit tangent along `dP/du` and bitangent toward `dP/dv` around `normal`.
+fn tangent_frame(normal: Vector, surface: &Surface) -> (Vector, Vector) {
+    let tangent = surface.dpdu - normal * normal.dot(surface.dpdu);
+    if tangent.norm() < 1e-6 {
+        return orthonormal_basis(normal);
+    }
+    let tangent = tangent.normalize();
+    let bitangent = normal.cross(tangent);
+    // mirrored texture coordinates
+    if bitangent.dot(surface.dpdv) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(u: f32, v: f32) -> Surface {
+        Surface {
+            uv: Uv { u, v },
+            ..Surface::default()
+        }
+    }
+
+    #[test]
+    fn checker() {
+        let checker = Checker {
+            even: 1.0,
+            odd: 0.0,
+            scale: 2.0,
+        };
+        assert_eq!(checker.value(&at(0.1, 0.1)), 1.0);
+        assert_eq!(checker.value(&at(0.6, 0.1)), 0.0);
+        assert_eq!(checker.value(&at(0.6, 0.6)), 1.0);
+        assert_eq!(checker.value(&at(-0.1, 0.1)), 0.0);
+    }
+
+    fn gray(v: f32) -> Color {
+        Color {
+            red: v,
+            green: v,
+            blue: v,
+        }
+    }
+
+    #[test]
+    fn image() {
+        // top row first
+        let mut image = ImageTexture::new(2, 2, vec![gray(0.0), gray(0.25), gray(0.5), gray(0.75)]);
+        image.filter = Filter::Nearest;
+        let value = |image: &ImageTexture, u, v| Texture::<f32>::value(image, &at(u, v));
+        assert_eq!(value(&image, 0.25, 0.75), 0.0);
+        assert_eq!(value(&image, 0.75, 0.25), 0.75);
+        assert_eq!(Texture::<Color>::value(&image, &at(0.75, 0.75)).red, 0.25);
+
+        // repeated, clamped or mirrored out of the unit square
+        assert_eq!(value(&image, 1.25, -0.75), 0.5);
+        image.wrap = Wrap::Clamp;
+        assert_eq!(value(&image, 1.25, -0.75), 0.75);
+        image.wrap = Wrap::Mirror;
+        assert_eq!(value(&image, 1.25, -0.75), 0.25);
+        assert_eq!(value(&image, -0.25, 0.25), 0.5);
+
+        // halfway between the texel centers
+        image.wrap = Wrap::Clamp;
+        image.filter = Filter::Bilinear;
+        assert!((value(&image, 0.5, 0.25) - 0.625).abs() < 1e-6);
+        assert!((value(&image, 0.5, 0.5) - 0.375).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mipmaps() {
+        let texels = (0..4 * 2).map(|i| gray((i % 2) as f32)).collect();
+        let image = ImageTexture::new(4, 2, texels);
+        let sizes: Vec<_> = image.levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, [(4, 2), (2, 1), (1, 1)]);
+        assert_eq!(image.levels[2].texels[0], gray(0.5));
+
+        // stripes up close, their average from afar
+        let mut surface = at(0.125, 0.5);
+        assert_eq!(Texture::<f32>::value(&image, &surface), 0.0);
+        surface.dx = Uv { u: 1.0, v: 0.0 };
+        assert_eq!(Texture::<f32>::value(&image, &surface), 0.5);
+        // halfway between the first two levels
+        surface.dx = Uv {
+            u: 2f32.sqrt() / 4.0,
+            v: 0.0,
+        };
+        assert!((Texture::<f32>::value(&image, &surface) - 0.25).abs() < 1e-5);
+
+        // odd sizes spread every texel over the level below
+        let texels = (0..9).map(|i| gray(i as f32 / 8.0)).collect();
+        let image = ImageTexture::new(3, 3, texels);
+        assert_eq!(image.levels.len(), 2);
+        assert!((image.levels[1].texels[0].red - 0.5).abs() < 1e-6);
+        let texels = (0..5).map(|i| gray(i as f32 / 4.0)).collect();
+        let image = ImageTexture::new(5, 1, texels);
+        let level = &image.levels[1];
+        assert!((level.texels[0].red - 0.2).abs() < 1e-6);
+        assert!((level.texels[1].red - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb() {
+        assert_eq!(ColorSpace::Srgb.decode(0), 0.0);
+        assert_eq!(ColorSpace::Srgb.decode(255), 1.0);
+        // linear near black
+        assert!((ColorSpace::Srgb.decode(10) - 10.0 / 255.0 / 12.92).abs() < 1e-7);
+        assert!((ColorSpace::Srgb.decode(128) - 0.2158).abs() < 1e-4);
+        assert_eq!(ColorSpace::Linear.decode(51), 0.2);
+    }
+
+    #[test]
+    fn uv_along() {
+        let surface = Surface {
+            dpdu: Vector {
+                x: 2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            dpdv: Vector {
+                x: 1.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            ..Surface::default()
+        };
+        let uv = surface.uv_along(Vector {
+            x: 3.0,
+            y: 1.0,
+            z: 0.0,
+        });
+        assert!((uv.u - 1.0).abs() < 1e-6 && (uv.v - 1.0).abs() < 1e-6);
+    }
+
+    #[derive(Debug)]
+    struct Slope;
+
+    impl Texture<f32> for Slope {
+        fn value(&self, surface: &Surface) -> f32 {
+            surface.uv.u
+        }
+    }
+
+    #[test]
+    fn relief() {
+        let vector = |x, y, z| Vector { x, y, z };
+        let surface = Surface {
+            dpdu: vector(2.0, 0.0, 0.0),
+            dpdv: vector(0.0, 0.0, -2.0),
+            ..Surface::default()
+        };
+        let up = vector(0.0, 1.0, 0.0);
+        let assert_near = |a: Vector, b: Vector| {
+            assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+        };
+
+        let flat = Relief::NormalMap(Arc::new(Constant(Color {
+            red: 0.5,
+            green: 0.5,
+            blue: 1.0,
+        })));
+        assert_near(flat.normal(up, &surface, |v| v), up);
+        let toward_u = Relief::NormalMap(Arc::new(Constant(Color {
+            red: 1.0,
+            green: 0.5,
+            blue: 0.5,
+        })));
+        assert_near(toward_u.normal(up, &surface, |v| v), vector(1.0, 0.0, 0.0));
+
+        // rising by half a unit per unit along x
+        let bump = Relief::Bump {
+            height: Arc::new(Slope),
+            scale: 1.0,
+        };
+        let n = vector(-0.5, 1.0, 0.0).normalize();
+        assert_near(bump.normal(up, &surface, |v| v), n);
+        // the same whichever way the surface is parameterized
+        let flipped = Surface {
+            dpdv: vector(0.0, 0.0, 2.0),
+            ..surface
+        };
+        assert_near(bump.normal(up, &flipped, |v| v), n);
+    }
+}
@@ -1,5 +1,7 @@
 extern crate rusty;
 
+use rusty::math::matrix::Matrix4;
+use rusty::math::transform::Transform;
 use rusty::math::*;
 
 #[test]
@@ -36,3 +38,97 @@ fn vector_from_vertices() {
     };
     assert_eq!(Vector::from_vertices(v1, v2), expected);
 }
+
+#[test]
+fn vector_rotate() {
+    let v = Vector {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    let axis = Vector {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+    let r = v.rotate(axis, Radian(std::f32::consts::FRAC_PI_2));
+    assert!(r.x.abs() < 1e-6);
+    assert!(r.y.abs() < 1e-6);
+    assert!((r.z + 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn transform_normal() {
+    let scale = Transform::new(Matrix4::scaling(Vector {
+        x: 2.0,
+        y: 1.0,
+        z: 1.0,
+    }))
+    .unwrap();
+    let normal = Vector {
+        x: 1.0,
+        y: 1.0,
+        z: 0.0,
+    }
+    .normalize();
+    let tangent = Vector {
+        x: 1.0,
+        y: -1.0,
+        z: 0.0,
+    };
+    let n = scale.normal(normal);
+    assert!(n.dot(scale.vector(tangent)).abs() < 1e-6);
+    assert!((n.norm() - 1.0).abs() < 1e-6);
+
+    let p = Vertex {
+        x: 1.0,
+        y: 2.0,
+        z: 3.0,
+    };
+    assert_eq!(scale.inverse().point(scale.point(p)), p);
+}
+
+#[test]
+fn transform_interpolation() {
+    let rotation = Matrix4::from_axis_angle(
+        Vector {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Radian(2.5),
+    );
+    let from = Matrix4::translation(Vector {
+        x: 1.0,
+        y: 2.0,
+        z: 3.0,
+    }) * Matrix4::scaling(Vector {
+        x: 2.0,
+        y: 1.0,
+        z: 1.0,
+    });
+    // sheared by the scaling under the rotation, and mirrored
+    let to = Matrix4::scaling(Vector {
+        x: 1.0,
+        y: 3.0,
+        z: -1.0,
+    }) * rotation;
+    let (from, to) = (Transform::new(from).unwrap(), Transform::new(to).unwrap());
+    let close = |a: Matrix4, b: Matrix4| {
+        let error = (0..4)
+            .flat_map(|i| (0..4).map(move |j| (a.m[i][j] - b.m[i][j]).abs()))
+            .fold(0.0, f32::max);
+        assert!(error < 1e-5, "{:?} != {:?}", a, b);
+    };
+    close(from.interpolate(&to, 0.0).unwrap().matrix(), from.matrix());
+    close(from.interpolate(&to, 1.0).unwrap().matrix(), to.matrix());
+
+    let spin = Transform::new(rotation).unwrap();
+    let half = Transform::default().interpolate(&spin, 0.5).unwrap();
+    let v = Vector {
+        x: 0.3,
+        y: -0.5,
+        z: 0.8,
+    };
+    assert!((half.vector(v).norm() - v.norm()).abs() < 1e-5);
+}
@@ -0,0 +1,111 @@
+extern crate rusty;
+
+use rusty::tracer::objects::Object;
+use rusty::tracer::scenefile::{SceneError, SceneFile};
+
+#[test]
+fn demo_scene() {
+    let file = SceneFile::load("scenes/demo.toml").unwrap();
+    assert_eq!(file.scene.objects().len(), 5);
+    assert_eq!(file.scene.lights.len(), 3);
+}
+
+#[test]
+fn instances_scene() {
+    // the mesh path is relative to the scene file
+    let file = SceneFile::load("scenes/instances.toml").unwrap();
+    assert_eq!(file.scene.objects().len(), 16);
+}
+
+#[test]
+fn turntable_scene() {
+    let file = SceneFile::load("scenes/turntable.toml").unwrap();
+    assert_eq!(file.animation.frames, (1, 24));
+    assert_eq!(file.animation.tracks.len(), 3);
+    assert!(file.graph.find("table/nut").is_some());
+}
+
+#[test]
+fn focus_scene() {
+    let file = SceneFile::load("scenes/focus.toml").unwrap();
+    let lens = file.scene.camera.lens;
+    assert!(lens.aperture > 0.0 && lens.blades == 6);
+    // focused on the red sphere
+    assert!((lens.focus_distance - 8.12).abs() < 0.01);
+}
+
+#[test]
+fn motion_scene() {
+    let file = SceneFile::load("scenes/motion.toml").unwrap();
+    assert_eq!(file.animation.shutter, 0.5);
+    // the ball and the spinning sphere move over the shutter interval
+    let moving = file.scene.objects().iter();
+    assert_eq!(moving.filter(|o| matches!(o, Object::Moving(_))).count(), 2);
+}
+
+#[test]
+fn textures_scene() {
+    let file = SceneFile::load("scenes/textures.toml").unwrap();
+    let textured = file.scene.objects().iter().filter_map(|o| o.material());
+    assert!(textured.clone().all(|m| m.color_texture.is_some()));
+    assert_eq!(
+        textured
+            .clone()
+            .filter(|m| m.albedo_texture.is_some())
+            .count(),
+        1
+    );
+    // the floor's grout
+    assert_eq!(textured.filter(|m| m.relief.is_some()).count(), 1);
+}
+
+#[test]
+fn procedural_scene() {
+    // no image to load
+    let file = SceneFile::load("scenes/procedural.toml").unwrap();
+    let textured = file.scene.objects().iter().filter_map(|o| o.material());
+    assert_eq!(textured.filter(|m| m.color_texture.is_some()).count(), 5);
+}
+
+#[test]
+fn subdivision_scene() {
+    let file = SceneFile::load("scenes/subdivision.toml").unwrap();
+    // the cage's 12 triangles, 6 * 4^3 quads and 12 * 4^3 triangles
+    let triangles: Vec<usize> = file
+        .scene
+        .objects()
+        .iter()
+        .filter_map(|o| match o {
+            Object::Instance(instance) => Some(instance.geometry.objects().len()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(triangles, [12, 2 * 384, 768, 2 * 6 * 256]);
+}
+
+#[test]
+fn shapes_scene() {
+    let file = SceneFile::load("scenes/shapes.toml").unwrap();
+    let kinds: Vec<&str> = file.scene.objects().iter().map(Object::kind).collect();
+    assert_eq!(
+        kinds,
+        ["plane", "box", "cylinder", "cone", "torus", "quad", "disc"]
+    );
+    // all bounded but the ground
+    assert_eq!(
+        file.scene
+            .objects()
+            .iter()
+            .filter(|o| o.bounds().is_some())
+            .count(),
+        6
+    );
+}
+
+#[test]
+fn missing_file() {
+    match SceneFile::load("scenes/missing.toml") {
+        Err(SceneError::Io(_)) => {}
+        _ => panic!("expected an IO error"),
+    }
+}